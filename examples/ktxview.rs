@@ -27,6 +27,7 @@ extern crate gl;
 extern crate sb6;
 
 use gl::types::*;
+use std::process;
 
 const VS_SRC: &str = r"
 #version 330 core
@@ -54,10 +55,119 @@ void main(void)
 }
 ";
 
+const INSPECTOR_FS_SRC: &str = r"
+#version 330 core
+
+uniform sampler2D s;
+uniform int level;
+out vec4 color;
+
+void main(void)
+{
+    ivec2 size = textureSize(s, level);
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    if (texel.x >= size.x || texel.y >= size.y)
+        discard;
+    color = texelFetch(s, texel, level);
+}
+";
+
+/// A second window showing one mip level of the viewer's texture at a time.
+/// The texture and program come from the main window's context; only the
+/// vertex array has to be created here.
+struct TextureInspector {
+    info: sb6::AppInfo,
+    texture: GLuint,
+    program: GLuint,
+    level_location: GLint,
+    vao: GLuint,
+    level: i32,
+    levels: i32,
+}
+
+impl TextureInspector {
+    fn new(texture: GLuint, program: GLuint) -> TextureInspector {
+        let mut info = sb6::AppInfo::default();
+        info.title = "Texture Inspector - up/down to change mip level";
+        info.window_width = 512;
+        info.window_height = 512;
+        TextureInspector {
+            info,
+            texture,
+            program,
+            level_location: -1,
+            vao: 0,
+            level: 0,
+            levels: 1,
+        }
+    }
+}
+
+impl sb6::App for TextureInspector {
+    fn get_app_info(&self) -> &sb6::AppInfo {
+        &self.info
+    }
+
+    fn startup(&mut self, _: &mut sb6::Context) -> Result<(), sb6::Error> {
+        self.level_location = try!(sb6::program::get_uniform_location(self.program, "level"));
+        unsafe {
+            let mut width = 0;
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut width);
+            let mut max_level = 0;
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, &mut max_level);
+            let full_chain = 32 - (width.max(1) as u32).leading_zeros() as i32;
+            self.levels = full_chain.min(max_level + 1);
+
+            gl::GenVertexArrays(1, &mut self.vao);
+            gl::BindVertexArray(self.vao);
+        }
+        Ok(())
+    }
+
+    fn shutdown(&mut self, _: &mut sb6::Context) {
+        // the texture and program belong to the viewer
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+        self.vao = 0;
+    }
+
+    fn render(&mut self, ctx: &mut sb6::Context, _: f64) {
+        const GREY: [GLfloat; 4] = [0.2, 0.2, 0.2, 1.0];
+
+        unsafe {
+            ctx.set_viewport();
+            gl::ClearBufferfv(gl::COLOR, 0, GREY.as_ptr());
+            gl::UseProgram(self.program);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::Uniform1i(self.level_location, self.level);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+        }
+    }
+
+    fn on_key(
+        &mut self,
+        _: &mut sb6::Context,
+        key: sb6::Key,
+        action: sb6::Action,
+        _: sb6::Modifiers,
+    ) {
+        if action != sb6::Action::Release {
+            match key {
+                sb6::Key::Up => self.level = (self.level - 1).max(0),
+                sb6::Key::Down => self.level = (self.level + 1).min(self.levels - 1),
+                _ => (),
+            }
+        }
+    }
+}
+
 struct SampleApp {
     info: sb6::AppInfo,
     texture: GLuint,
     program: GLuint,
+    inspector_program: GLuint,
     vao: GLuint,
 }
 
@@ -67,6 +177,7 @@ impl SampleApp {
             info: init,
             texture: 0,
             program: 0,
+            inspector_program: 0,
             vao: 0,
         }
     }
@@ -76,19 +187,31 @@ impl sb6::App for SampleApp {
     fn get_app_info(&self) -> &sb6::AppInfo {
         &self.info
     }
-    fn startup(&mut self) {
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
         unsafe {
             // Load texture from file
-            self.texture = load_ktx_or_panic!("media/textures/Tree.ktx");
+            self.texture = try_load_ktx!(&ctx.media_path("textures/Tree.ktx"));
             self.program = gl::CreateProgram();
 
-            let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
-            let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
+            let fs = try!(sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+            let vs = try!(sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
 
             gl::AttachShader(self.program, vs);
             gl::AttachShader(self.program, fs);
             gl::LinkProgram(self.program);
-            sb6::program::check_link_status(self.program).unwrap();
+            try!(sb6::program::check_link_status(self.program));
+
+            gl::DeleteShader(fs);
+
+            let fs = try!(sb6::shader::create_from_source(
+                INSPECTOR_FS_SRC,
+                gl::FRAGMENT_SHADER
+            ));
+            self.inspector_program = gl::CreateProgram();
+            gl::AttachShader(self.inspector_program, vs);
+            gl::AttachShader(self.inspector_program, fs);
+            gl::LinkProgram(self.inspector_program);
+            try!(sb6::program::check_link_status(self.inspector_program));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(fs);
@@ -96,34 +219,44 @@ impl sb6::App for SampleApp {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::BindVertexArray(self.vao);
         }
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         unsafe {
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteProgram(self.program);
+            gl::DeleteProgram(self.inspector_program);
             gl::DeleteTextures(1, &self.texture);
         }
         self.vao = 0;
         self.program = 0;
+        self.inspector_program = 0;
         self.texture = 0;
     }
 
-    fn render(&mut self, _: f64) {
+    fn render(&mut self, ctx: &mut sb6::Context, _: f64) {
         const GREEN: [GLfloat; 4] = [0.0, 0.25, 0.0, 1.0];
 
         unsafe {
             gl::ClearBufferfv(gl::COLOR, 0, GREEN.as_ptr());
             gl::UseProgram(self.program);
-            gl::Viewport(
-                0,
-                0,
-                self.info.window_width as i32,
-                self.info.window_height as i32,
-            );
+            ctx.set_viewport();
             gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
         }
     }
+
+    fn on_key(
+        &mut self,
+        ctx: &mut sb6::Context,
+        key: sb6::Key,
+        action: sb6::Action,
+        _: sb6::Modifiers,
+    ) {
+        if action == sb6::Action::Press && key == sb6::Key::I {
+            ctx.open_window(TextureInspector::new(self.texture, self.inspector_program));
+        }
+    }
 }
 
 fn main() {
@@ -131,6 +264,15 @@ fn main() {
     init.title = "OpenGL SuperBible - KTX Viewer";
     init.major_version = 3;
     init.minor_version = 3;
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -28,6 +28,8 @@ extern crate sb6;
 
 use gl::types::*;
 use sb6::vmath;
+use sb6::{GamepadAxis, GamepadButton};
+use std::process;
 
 const VS_SRC: &str = r"
 #version 330 core
@@ -112,6 +114,12 @@ struct SampleApp {
     proj_location: GLint,
     explode_factor_location: GLint,
     object: sb6::object::Object,
+    /// Driven by a gamepad rather than animated.
+    manual: bool,
+    yaw: f32,
+    pitch: f32,
+    explode_factor: f32,
+    last_time: f64,
 }
 
 impl SampleApp {
@@ -123,6 +131,11 @@ impl SampleApp {
             proj_location: -1,
             explode_factor_location: -1,
             object: sb6::object::Object::new(),
+            manual: false,
+            yaw: 0.0,
+            pitch: 0.0,
+            explode_factor: 0.0,
+            last_time: 0.0,
         }
     }
 }
@@ -132,39 +145,46 @@ impl sb6::App for SampleApp {
         &self.info
     }
 
-    fn startup(&mut self) {
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
         unsafe {
             self.program = gl::CreateProgram();
 
-            let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
-            let gs = sb6::shader::create_from_source(GS_SRC, gl::GEOMETRY_SHADER).unwrap();
-            let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
+            let vs = try!(sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
+            let gs = try!(sb6::shader::create_from_source(GS_SRC, gl::GEOMETRY_SHADER));
+            let fs = try!(sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
 
             gl::AttachShader(self.program, vs);
             gl::AttachShader(self.program, gs);
             gl::AttachShader(self.program, fs);
             gl::LinkProgram(self.program);
-            sb6::program::check_link_status(self.program).unwrap();
+            try!(sb6::program::check_link_status(self.program));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(gs);
             gl::DeleteShader(fs);
 
-            self.mv_location =
-                sb6::program::get_uniform_location(self.program, "mv_matrix").unwrap();
-            self.proj_location =
-                sb6::program::get_uniform_location(self.program, "proj_matrix").unwrap();
-            self.explode_factor_location =
-                sb6::program::get_uniform_location(self.program, "explode_factor").unwrap();
-
-            load_object_or_panic!(&mut self.object, "media/objects/bunny_1k.sbm");
+            self.mv_location = try!(sb6::program::get_uniform_location(
+                self.program,
+                "mv_matrix"
+            ));
+            self.proj_location = try!(sb6::program::get_uniform_location(
+                self.program,
+                "proj_matrix"
+            ));
+            self.explode_factor_location = try!(sb6::program::get_uniform_location(
+                self.program,
+                "explode_factor"
+            ));
+
+            try_load_object!(&mut self.object, &ctx.media_path("objects/bunny_1k.sbm"));
 
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LEQUAL);
         }
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         self.object.free();
         unsafe {
             gl::DeleteProgram(self.program);
@@ -175,25 +195,44 @@ impl sb6::App for SampleApp {
         self.program = 0;
     }
 
-    fn render(&mut self, time: f64) {
+    fn update(&mut self, ctx: &mut sb6::Context, time: f64) {
+        let dt = (time - self.last_time) as f32;
+        self.last_time = time;
+        if let Some(pad) = ctx.gamepads().first() {
+            if pad.pressed(GamepadButton::A) {
+                self.manual = !self.manual;
+            }
+            if self.manual {
+                // left stick turns the bunny, right trigger blows it apart
+                self.yaw += pad.axis(GamepadAxis::LeftX) * 90.0 * dt;
+                self.pitch += pad.axis(GamepadAxis::LeftY) * 90.0 * dt;
+                self.explode_factor = pad.axis(GamepadAxis::RightTrigger) * 0.8;
+            }
+        }
+    }
+
+    fn render(&mut self, ctx: &mut sb6::Context, time: f64) {
         const BLACK: [GLfloat; 4] = [0.0, 0.0, 0.0, 1.0];
         const ONE: GLfloat = 1.0;
         let time = time as f32;
 
-        let aspect = self.info.window_width as f32 / self.info.window_height as f32;
+        let aspect = ctx.aspect_ratio();
         let proj_matrix = vmath::perspective(50.0, aspect, 0.1, 1000.0);
+        let (yaw, pitch, explode_factor) = if self.manual {
+            (self.yaw, self.pitch, self.explode_factor)
+        } else {
+            (
+                time * 45.0,
+                time * 81.0,
+                (time * 8.0).sin() * (time * 6.0).cos() * 0.7 + 0.1,
+            )
+        };
         let mv_matrix = vmath::translate(0.0, 0.0, -3.0)
-            * vmath::rotate(time * 45.0, 0.0, 1.0, 0.0)
-            * vmath::rotate(time * 81.0, 1.0, 0.0, 0.0);
-        let explode_factor = (time * 8.0).sin() * (time * 6.0).cos() * 0.7 + 0.1;
+            * vmath::rotate(yaw, 0.0, 1.0, 0.0)
+            * vmath::rotate(pitch, 1.0, 0.0, 0.0);
 
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                self.info.window_width as i32,
-                self.info.window_height as i32,
-            );
+            ctx.set_viewport();
             gl::ClearBufferfv(gl::COLOR, 0, BLACK.as_ptr());
             gl::ClearBufferfv(gl::DEPTH, 0, &ONE);
 
@@ -208,6 +247,17 @@ impl sb6::App for SampleApp {
 
         self.object.render();
     }
+
+    fn on_gamepad_connected(&mut self, ctx: &mut sb6::Context, _: sb6::JoystickId) {
+        ctx.set_title("OpenGL SuperBible - Exploder (press A to take control)");
+    }
+
+    fn on_gamepad_disconnected(&mut self, ctx: &mut sb6::Context, _: sb6::JoystickId) {
+        if ctx.gamepads().is_empty() {
+            ctx.set_title(self.info.title);
+            self.manual = false;
+        }
+    }
 }
 
 fn main() {
@@ -215,6 +265,15 @@ fn main() {
     init.title = "OpenGL SuperBible - Exploder";
     init.major_version = 3;
     init.minor_version = 3;
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -27,7 +27,11 @@ extern crate gl;
 extern crate sb6;
 
 use gl::types::*;
+use sb6::camera::{Camera, OrbitCamera};
+use sb6::debug_draw;
+use sb6::debug_draw::DebugDraw;
 use sb6::vmath;
+use std::process;
 
 const VS_SRC: &str = r"
 #version 330 core
@@ -75,6 +79,10 @@ struct SampleApp {
     mv_location: GLint,
     proj_location: GLint,
     object: sb6::object::Object,
+    debug_draw: Option<DebugDraw>,
+    show_debug: bool,
+    camera: OrbitCamera,
+    last_time: f64,
 }
 
 impl SampleApp {
@@ -85,6 +93,10 @@ impl SampleApp {
             mv_location: -1,
             proj_location: -1,
             object: sb6::object::Object::new(),
+            debug_draw: None,
+            show_debug: false,
+            camera: OrbitCamera::new(vmath::vec3(0.0, 0.0, 0.0), 3.0, 0.0, 0.0),
+            last_time: 0.0,
         }
     }
 }
@@ -94,35 +106,50 @@ impl sb6::App for SampleApp {
         &self.info
     }
 
-    fn startup(&mut self) {
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
         unsafe {
             self.program = gl::CreateProgram();
 
-            let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
-            let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
+            let vs = try!(sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
+            let fs = try!(sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
 
             gl::AttachShader(self.program, vs);
             gl::AttachShader(self.program, fs);
             gl::LinkProgram(self.program);
-            sb6::program::check_link_status(self.program).unwrap();
+            try!(sb6::program::check_link_status(self.program));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(fs);
 
-            self.mv_location =
-                sb6::program::get_uniform_location(self.program, "mv_matrix").unwrap();
-            self.proj_location =
-                sb6::program::get_uniform_location(self.program, "proj_matrix").unwrap();
+            self.mv_location = try!(sb6::program::get_uniform_location(
+                self.program,
+                "mv_matrix"
+            ));
+            self.proj_location = try!(sb6::program::get_uniform_location(
+                self.program,
+                "proj_matrix"
+            ));
 
-            load_object_or_panic!(&mut self.object, "media/objects/bunny_1k.sbm");
+            try_load_object!(&mut self.object, &ctx.media_path("objects/bunny_1k.sbm"));
 
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LEQUAL);
         }
+
+        self.camera.projection_mut().aspect = ctx.aspect_ratio();
+        let (width, height) = ctx.window_size();
+        self.camera.on_resize(width as isize, height as isize);
+
+        self.debug_draw = Some(try!(DebugDraw::new()));
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         self.object.free();
+        if let Some(ref mut debug_draw) = self.debug_draw {
+            debug_draw.free();
+        }
+        self.debug_draw = None;
         unsafe {
             gl::DeleteProgram(self.program);
         }
@@ -131,24 +158,26 @@ impl sb6::App for SampleApp {
         self.program = 0;
     }
 
-    fn render(&mut self, time: f64) {
+    fn update(&mut self, ctx: &mut sb6::Context, time: f64) {
+        if let Some(pad) = ctx.gamepads().first() {
+            self.camera.on_gamepad(pad, time - self.last_time);
+        }
+        self.last_time = time;
+    }
+
+    fn render(&mut self, ctx: &mut sb6::Context, time: f64) {
         const BLACK: [GLfloat; 4] = [0.0, 0.0, 0.0, 1.0];
         const ONE: GLfloat = 1.0;
         let time = time as f32;
 
-        let aspect = self.info.window_width as f32 / self.info.window_height as f32;
-        let proj_matrix = vmath::perspective(50.0, aspect, 0.1, 1000.0);
-        let mv_matrix = vmath::translate(0.0, 0.0, -3.0)
-            * vmath::rotate(time * 45.0, 0.0, 1.0, 0.0)
-            * vmath::rotate(time * 81.0, 1.0, 0.0, 0.0);
+        let proj_matrix = self.camera.proj_matrix();
+        let view_matrix = self.camera.view_matrix();
+        let model_matrix =
+            vmath::rotate(time * 45.0, 0.0, 1.0, 0.0) * vmath::rotate(time * 81.0, 1.0, 0.0, 0.0);
+        let mv_matrix = view_matrix * model_matrix;
 
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                self.info.window_width as i32,
-                self.info.window_height as i32,
-            );
+            ctx.set_viewport();
             gl::ClearBufferfv(gl::COLOR, 0, BLACK.as_ptr());
             gl::ClearBufferfv(gl::DEPTH, 0, &ONE);
 
@@ -160,6 +189,61 @@ impl sb6::App for SampleApp {
 
             self.object.render();
         }
+
+        if let Some(ref mut debug_draw) = self.debug_draw {
+            if self.show_debug {
+                let grey = vmath::vec4(0.5, 0.5, 0.5, 1.0);
+                debug_draw.set_mode(debug_draw::Mode::DepthTested);
+                debug_draw.grid(vmath::vec3(0.0, -1.0, 0.0), 4.0, 8, grey);
+                debug_draw.aabb(
+                    vmath::vec3(-1.0, -1.0, -1.0),
+                    vmath::vec3(1.0, 1.0, 1.0),
+                    debug_draw::WHITE,
+                );
+                debug_draw.set_mode(debug_draw::Mode::Overlay);
+                debug_draw.axes(&model_matrix, 0.5);
+                debug_draw.draw(&view_matrix, &proj_matrix);
+            }
+        }
+    }
+
+    fn on_key(
+        &mut self,
+        _: &mut sb6::Context,
+        key: sb6::Key,
+        action: sb6::Action,
+        mods: sb6::Modifiers,
+    ) {
+        if action == sb6::Action::Press && key == sb6::Key::D {
+            self.show_debug = !self.show_debug;
+        }
+        self.camera.on_key(key, action, mods);
+    }
+
+    fn on_mouse_button(
+        &mut self,
+        _: &mut sb6::Context,
+        button: sb6::MouseButton,
+        action: sb6::Action,
+        mods: sb6::Modifiers,
+    ) {
+        self.camera.on_mouse_button(button, action, mods);
+    }
+
+    fn on_mouse_move(&mut self, _: &mut sb6::Context, x: f64, y: f64) {
+        self.camera.on_mouse_move(x, y);
+    }
+
+    fn on_mouse_wheel(&mut self, _: &mut sb6::Context, x: f64, y: f64) {
+        self.camera.on_mouse_wheel(x, y);
+    }
+
+    fn on_resize(&mut self, _: &mut sb6::Context, width: isize, height: isize) {
+        self.camera.on_resize(width, height);
+    }
+
+    fn on_framebuffer_resize(&mut self, _: &mut sb6::Context, width: isize, height: isize) {
+        self.camera.on_framebuffer_resize(width, height);
     }
 }
 
@@ -168,6 +252,15 @@ fn main() {
     init.title = "OpenGL SuperBible - Object Viewer";
     init.major_version = 3;
     init.minor_version = 3;
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
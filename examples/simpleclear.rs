@@ -26,6 +26,7 @@ extern crate gl;
 extern crate sb6;
 
 use gl::types::*;
+use std::process;
 
 struct SampleApp {
     info: sb6::AppInfo,
@@ -42,7 +43,7 @@ impl sb6::App for SampleApp {
         &self.info
     }
 
-    fn render(&mut self, _: f64) {
+    fn render(&mut self, _: &mut sb6::Context, _: f64) {
         const RED: [GLfloat; 4] = [1.0, 0.0, 0.0, 1.0];
         unsafe {
             gl::ClearBufferfv(gl::COLOR, 0, RED.as_ptr());
@@ -53,6 +54,15 @@ impl sb6::App for SampleApp {
 fn main() {
     let mut init = sb6::AppInfo::default();
     init.title = "OpenGL SuperBible - Single Triangle";
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -29,6 +29,7 @@ extern crate sb6;
 use gl::types::*;
 use sb6::vmath;
 use std::mem;
+use std::process;
 
 struct SampleApp {
     info: sb6::AppInfo,
@@ -53,18 +54,18 @@ impl SampleApp {
         }
     }
 
-    fn load_shaders(&mut self) {
+    fn load_shaders(&mut self, ctx: &sb6::Context) -> Result<(), sb6::Error> {
         unsafe {
             if self.render_prog != 0 {
                 gl::DeleteProgram(self.render_prog);
             }
 
-            let vs = load_shader_or_panic!(
-                "media/shaders/simpletexcoords/render.vs.glsl",
+            let vs = try_load_shader!(
+                &ctx.media_path("shaders/simpletexcoords/render.vs.glsl"),
                 gl::VERTEX_SHADER
             );
-            let fs = load_shader_or_panic!(
-                "media/shaders/simpletexcoords/render.fs.glsl",
+            let fs = try_load_shader!(
+                &ctx.media_path("shaders/simpletexcoords/render.fs.glsl"),
                 gl::FRAGMENT_SHADER
             );
 
@@ -72,15 +73,21 @@ impl SampleApp {
             gl::AttachShader(self.render_prog, vs);
             gl::AttachShader(self.render_prog, fs);
             gl::LinkProgram(self.render_prog);
-            sb6::program::check_link_status(self.render_prog).unwrap();
+            try!(sb6::program::check_link_status(self.render_prog));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(fs);
         }
 
-        self.mv_matrix = sb6::program::get_uniform_location(self.render_prog, "mv_matrix").unwrap();
-        self.proj_matrix =
-            sb6::program::get_uniform_location(self.render_prog, "proj_matrix").unwrap();
+        self.mv_matrix = try!(sb6::program::get_uniform_location(
+            self.render_prog,
+            "mv_matrix"
+        ));
+        self.proj_matrix = try!(sb6::program::get_uniform_location(
+            self.render_prog,
+            "proj_matrix"
+        ));
+        Ok(())
     }
 }
 
@@ -88,7 +95,7 @@ impl sb6::App for SampleApp {
     fn get_app_info(&self) -> &sb6::AppInfo {
         &self.info
     }
-    fn startup(&mut self) {
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
         // generate a 16 x 16 checker texture
         const TEX_DIM: usize = 16;
         let mut tex_data: [u32; (TEX_DIM * TEX_DIM)] = [0; (TEX_DIM * TEX_DIM)];
@@ -123,19 +130,23 @@ impl sb6::App for SampleApp {
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
         }
 
-        self.tex_object[1] = load_ktx_or_panic!("media/textures/pattern1.ktx");
+        self.tex_object[1] = try_load_ktx!(&ctx.media_path("textures/pattern1.ktx"));
 
-        load_object_or_panic!(&mut self.object, "media/objects/torus_nrms_tc.sbm");
+        try_load_object!(
+            &mut self.object,
+            &ctx.media_path("objects/torus_nrms_tc.sbm")
+        );
 
-        self.load_shaders();
+        try!(self.load_shaders(ctx));
 
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LEQUAL);
         }
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         unsafe {
             gl::DeleteProgram(self.render_prog);
             gl::DeleteTextures(2, self.tex_object.as_ptr());
@@ -147,11 +158,11 @@ impl sb6::App for SampleApp {
         self.proj_matrix = -1;
     }
 
-    fn render(&mut self, current_time: f64) {
+    fn render(&mut self, ctx: &mut sb6::Context, current_time: f64) {
         let gray = [0.2, 0.2, 0.2, 1.0];
         let ones = [1.0];
 
-        let aspect = self.info.window_width as f32 / self.info.window_height as f32;
+        let aspect = ctx.aspect_ratio();
         let proj_matrix = vmath::perspective(60.0, aspect, 0.1, 1000.0);
         let mv_matrix = vmath::translate(0.0, 0.0, -3.0)
             * vmath::rotate(current_time as f32 * 19.3, 0.0, 1.0, 0.0)
@@ -160,12 +171,7 @@ impl sb6::App for SampleApp {
         unsafe {
             gl::ClearBufferfv(gl::COLOR, 0, gray.as_ptr());
             gl::ClearBufferfv(gl::DEPTH, 0, ones.as_ptr());
-            gl::Viewport(
-                0,
-                0,
-                self.info.window_width as i32,
-                self.info.window_height as i32,
-            );
+            ctx.set_viewport();
             gl::BindTexture(gl::TEXTURE_2D, self.tex_object[self.tex_index as usize]);
             gl::UseProgram(self.render_prog);
             gl::UniformMatrix4fv(self.mv_matrix, 1, gl::FALSE, mv_matrix.as_ptr());
@@ -175,10 +181,20 @@ impl sb6::App for SampleApp {
         self.object.render();
     }
 
-    fn on_key(&mut self, key: sb6::Key, action: sb6::Action) {
+    fn on_key(
+        &mut self,
+        ctx: &mut sb6::Context,
+        key: sb6::Key,
+        action: sb6::Action,
+        _: sb6::Modifiers,
+    ) {
         if action == sb6::Action::Release {
             match key {
-                sb6::Key::R => self.load_shaders(),
+                sb6::Key::R => {
+                    if let Err(e) = self.load_shaders(ctx) {
+                        eprintln!("{}", e);
+                    }
+                }
                 sb6::Key::T => self.tex_index = (self.tex_index + 1) % 2,
                 _ => (),
             };
@@ -189,6 +205,15 @@ impl sb6::App for SampleApp {
 fn main() {
     let mut init = sb6::AppInfo::default();
     init.title = "OpenGL SuperBible - Texture Coordinates";
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -26,6 +26,7 @@ extern crate gl;
 extern crate sb6;
 
 use gl::types::*;
+use std::process;
 
 const VS_SRC: &str = r"
 #version 420 core
@@ -72,17 +73,17 @@ impl sb6::App for SampleApp {
         &self.info
     }
 
-    fn startup(&mut self) {
+    fn startup(&mut self, _: &mut sb6::Context) -> Result<(), sb6::Error> {
         unsafe {
             self.program = gl::CreateProgram();
 
-            let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
-            let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
+            let fs = try!(sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+            let vs = try!(sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
 
             gl::AttachShader(self.program, vs);
             gl::AttachShader(self.program, fs);
             gl::LinkProgram(self.program);
-            sb6::program::check_link_status(self.program).unwrap();
+            try!(sb6::program::check_link_status(self.program));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(fs);
@@ -93,9 +94,10 @@ impl sb6::App for SampleApp {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::BindVertexArray(self.vao);
         }
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         unsafe {
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteProgram(self.program);
@@ -104,7 +106,7 @@ impl sb6::App for SampleApp {
         self.program = 0;
     }
 
-    fn render(&mut self, time: f64) {
+    fn render(&mut self, _: &mut sb6::Context, time: f64) {
         let (sin_time, cos_time) = (time as f32).sin_cos();
         unsafe {
             gl::ClearColor(0.5 + sin_time * 0.5, 0.5 + cos_time * 0.5, 0.3, 1.0);
@@ -117,6 +119,15 @@ impl sb6::App for SampleApp {
 fn main() {
     let mut init = sb6::AppInfo::default();
     init.title = "OpenGL SuperBible - Single Triangle";
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
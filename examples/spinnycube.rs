@@ -29,6 +29,7 @@ use gl::types::*;
 use sb6::vmath;
 use sb6::vmath::Mat4;
 use std::mem;
+use std::process;
 use std::ptr;
 
 const VERTEX_POSITIONS: [GLfloat; 108] = [
@@ -101,8 +102,7 @@ impl SampleApp {
         }
     }
 
-    fn update_proj_matrix(&mut self) {
-        let aspect = self.info.window_width as f32 / self.info.window_height as f32;
+    fn update_proj_matrix(&mut self, aspect: f32) {
         self.proj_matrix = vmath::perspective(50.0, aspect, 0.1, 1000.0);
     }
 }
@@ -111,25 +111,29 @@ impl sb6::App for SampleApp {
     fn get_app_info(&self) -> &sb6::AppInfo {
         &self.info
     }
-    fn startup(&mut self) {
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
         unsafe {
             self.program = gl::CreateProgram();
 
-            let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
-            let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
+            let fs = try!(sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+            let vs = try!(sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
 
             gl::AttachShader(self.program, vs);
             gl::AttachShader(self.program, fs);
             gl::LinkProgram(self.program);
-            sb6::program::check_link_status(self.program).unwrap();
+            try!(sb6::program::check_link_status(self.program));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(fs);
 
-            self.mv_location =
-                sb6::program::get_uniform_location(self.program, "mv_matrix").unwrap();
-            self.proj_location =
-                sb6::program::get_uniform_location(self.program, "proj_matrix").unwrap();
+            self.mv_location = try!(sb6::program::get_uniform_location(
+                self.program,
+                "mv_matrix"
+            ));
+            self.proj_location = try!(sb6::program::get_uniform_location(
+                self.program,
+                "proj_matrix"
+            ));
 
             gl::GenVertexArrays(1, &mut self.vao);
             gl::BindVertexArray(self.vao);
@@ -151,10 +155,11 @@ impl sb6::App for SampleApp {
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LEQUAL);
         }
-        self.update_proj_matrix();
+        self.update_proj_matrix(ctx.aspect_ratio());
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         unsafe {
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteBuffers(1, &self.buffer);
@@ -167,23 +172,16 @@ impl sb6::App for SampleApp {
         self.program = 0;
     }
 
-    fn on_resize(&mut self, width: isize, height: isize) {
-        self.info.window_width = width as u32;
-        self.info.window_height = height as u32;
-        self.update_proj_matrix();
+    fn on_framebuffer_resize(&mut self, ctx: &mut sb6::Context, _: isize, _: isize) {
+        self.update_proj_matrix(ctx.aspect_ratio());
     }
 
-    fn render(&mut self, time: f64) {
+    fn render(&mut self, ctx: &mut sb6::Context, time: f64) {
         const GREEN: [GLfloat; 4] = [0.0, 0.25, 0.0, 1.0];
         const ONE: GLfloat = 1.0;
 
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                self.info.window_width as i32,
-                self.info.window_height as i32,
-            );
+            ctx.set_viewport();
 
             gl::ClearBufferfv(gl::COLOR, 0, GREEN.as_ptr());
             gl::ClearBufferfv(gl::DEPTH, 0, &ONE);
@@ -211,6 +209,15 @@ impl sb6::App for SampleApp {
 fn main() {
     let mut init = sb6::AppInfo::default();
     init.title = "OpenGL SuperBible - Moving Triangle";
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
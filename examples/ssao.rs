@@ -28,9 +28,12 @@ extern crate rand;
 extern crate sb6;
 
 use gl::types::*;
-use rand::Rng;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use sb6::text_overlay::TextOverlay;
 use sb6::vmath;
+use std::fmt::Write;
 use std::mem;
+use std::process;
 
 struct SamplePoints {
     point: [vmath::Vec4; 256],
@@ -40,7 +43,6 @@ struct SamplePoints {
 struct RenderUniforms {
     mv_matrix: GLint,
     proj_matrix: GLint,
-    shading_level: GLint,
 }
 
 impl RenderUniforms {
@@ -48,7 +50,6 @@ impl RenderUniforms {
         RenderUniforms {
             mv_matrix: -1,
             proj_matrix: -1,
-            shading_level: -1,
         }
     }
 }
@@ -57,7 +58,6 @@ struct SSAOUniforms {
     ssao_level: GLint,
     object_level: GLint,
     ssao_radius: GLint,
-    randomize_points: GLint,
     point_count: GLint,
 }
 
@@ -67,7 +67,6 @@ impl SSAOUniforms {
             ssao_level: -1,
             object_level: -1,
             ssao_radius: -1,
-            randomize_points: -1,
             point_count: -1,
         }
     }
@@ -75,8 +74,8 @@ impl SSAOUniforms {
 
 struct SampleApp {
     info: sb6::AppInfo,
-    render_program: GLuint,
-    ssao_program: GLuint,
+    render_program: Option<sb6::ProgramId>,
+    ssao_program: Option<sb6::ProgramId>,
     render_fbo: GLuint,
     fbo_textures: [GLuint; 3],
     quad_vao: GLuint,
@@ -94,14 +93,15 @@ struct SampleApp {
     show_ao: bool,
     //weight_by_angle: bool,
     randomize_points: bool,
+    overlay: Option<TextOverlay>,
 }
 
 impl SampleApp {
     fn new(init: sb6::AppInfo) -> SampleApp {
         SampleApp {
             info: init,
-            render_program: 0,
-            ssao_program: 0,
+            render_program: None,
+            ssao_program: None,
             render_fbo: 0,
             fbo_textures: [0; 3],
             quad_vao: 0,
@@ -119,47 +119,80 @@ impl SampleApp {
             show_ao: true,
             //weight_by_angle: true,
             randomize_points: true,
+            overlay: None,
         }
     }
 
-    fn load_shaders(&mut self) {
-        if self.render_program != 0 {
-            unsafe {
-                gl::DeleteProgram(self.render_program);
+    fn shading_level(&self) -> f32 {
+        if self.show_shading {
+            if self.show_ao {
+                0.7
+            } else {
+                1.0
             }
+        } else {
+            0.0
         }
+    }
 
-        let render_shaders = [
-            load_shader_or_panic!("media/shaders/ssao/render.vs.glsl", gl::VERTEX_SHADER),
-            load_shader_or_panic!("media/shaders/ssao/render.fs.glsl", gl::FRAGMENT_SHADER),
-        ];
-
-        self.render_program = sb6::program::link_from_shaders(&render_shaders).unwrap();
-
-        self.render.mv_matrix =
-            sb6::program::get_uniform_location(self.render_program, "mv_matrix").unwrap();
-        self.render.proj_matrix =
-            sb6::program::get_uniform_location(self.render_program, "proj_matrix").unwrap();
-        self.render.shading_level =
-            sb6::program::get_uniform_location(self.render_program, "shading_level").unwrap();
-
-        let ssao_shaders = [
-            load_shader_or_panic!("media/shaders/ssao/ssao.vs.glsl", gl::VERTEX_SHADER),
-            load_shader_or_panic!("media/shaders/ssao/ssao.fs.glsl", gl::FRAGMENT_SHADER),
-        ];
-
-        self.ssao_program = sb6::program::link_from_shaders(&ssao_shaders).unwrap();
-
-        self.ssao.ssao_radius =
-            sb6::program::get_uniform_location(self.ssao_program, "ssao_radius").unwrap();
-        self.ssao.ssao_level =
-            sb6::program::get_uniform_location(self.ssao_program, "ssao_level").unwrap();
-        self.ssao.object_level =
-            sb6::program::get_uniform_location(self.ssao_program, "object_level").unwrap();
-        self.ssao.randomize_points =
-            sb6::program::get_uniform_location(self.ssao_program, "randomize_points").unwrap();
-        self.ssao.point_count =
-            sb6::program::get_uniform_location(self.ssao_program, "point_count").unwrap();
+    /// Selects the program variants for the current settings. Each variant
+    /// is compiled the first time it's used and rebuilt by the context when
+    /// the shader files change.
+    fn select_programs(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
+        let shading_level = format!("{:.1}", self.shading_level());
+        let render_vs = ctx.media_path("shaders/ssao/render.vs.glsl");
+        let render_fs = ctx.media_path("shaders/ssao/render.fs.glsl");
+        self.render_program = Some(try!(ctx.programs_mut().load_variant(
+            &[
+                (&render_vs, gl::VERTEX_SHADER),
+                (&render_fs, gl::FRAGMENT_SHADER),
+            ],
+            &[("SHADING_LEVEL", &shading_level)],
+        )));
+
+        let randomize_points = if self.randomize_points { "1" } else { "0" };
+        let ssao_vs = ctx.media_path("shaders/ssao/ssao.vs.glsl");
+        let ssao_fs = ctx.media_path("shaders/ssao/ssao.fs.glsl");
+        self.ssao_program = Some(try!(ctx.programs_mut().load_variant(
+            &[
+                (&ssao_vs, gl::VERTEX_SHADER),
+                (&ssao_fs, gl::FRAGMENT_SHADER),
+            ],
+            &[("RANDOMIZE_POINTS", randomize_points)],
+        )));
+
+        self.get_uniforms(ctx)
+    }
+
+    fn get_uniforms(&mut self, ctx: &sb6::Context) -> Result<(), sb6::Error> {
+        let render_program = self.render_program.map_or(0, |id| ctx.programs().get(id));
+        self.render.mv_matrix = try!(sb6::program::get_uniform_location(
+            render_program,
+            "mv_matrix"
+        ));
+        self.render.proj_matrix = try!(sb6::program::get_uniform_location(
+            render_program,
+            "proj_matrix"
+        ));
+
+        let ssao_program = self.ssao_program.map_or(0, |id| ctx.programs().get(id));
+        self.ssao.ssao_radius = try!(sb6::program::get_uniform_location(
+            ssao_program,
+            "ssao_radius"
+        ));
+        self.ssao.ssao_level = try!(sb6::program::get_uniform_location(
+            ssao_program,
+            "ssao_level"
+        ));
+        self.ssao.object_level = try!(sb6::program::get_uniform_location(
+            ssao_program,
+            "object_level"
+        ));
+        self.ssao.point_count = try!(sb6::program::get_uniform_location(
+            ssao_program,
+            "point_count"
+        ));
+        Ok(())
     }
 }
 
@@ -167,8 +200,10 @@ impl sb6::App for SampleApp {
     fn get_app_info(&self) -> &sb6::AppInfo {
         &self.info
     }
-    fn startup(&mut self) {
-        self.load_shaders();
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
+        try!(self.select_programs(ctx));
+
+        self.overlay = Some(try!(TextOverlay::new(64, 16)));
 
         unsafe {
             gl::GenFramebuffers(1, &mut self.render_fbo);
@@ -232,15 +267,16 @@ impl sb6::App for SampleApp {
             gl::BindVertexArray(self.quad_vao);
         }
 
-        load_object_or_panic!(&mut self.object, "media/objects/dragon.sbm");
-        load_object_or_panic!(&mut self.cube, "media/objects/cube.sbm");
+        try_load_object!(&mut self.object, &ctx.media_path("objects/dragon.sbm"));
+        try_load_object!(&mut self.cube, &ctx.media_path("objects/cube.sbm"));
 
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
             gl::Enable(gl::CULL_FACE);
         }
 
-        let mut rng = rand::weak_rng();
+        // fixed seed so every run renders the same image
+        let mut rng = XorShiftRng::from_seed([0x193a_6754, 0xa8a7_d469, 0x9783_0e05, 0x113b_a7bb]);
         let mut point_data = SamplePoints {
             point: [vmath::Vec4::zero(); 256],
             random_vectors: [vmath::Vec4::zero(); 256],
@@ -279,30 +315,38 @@ impl sb6::App for SampleApp {
                 gl::STATIC_DRAW,
             );
         }
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, ctx: &mut sb6::Context) {
+        if let Some(id) = self.render_program.take() {
+            ctx.programs_mut().remove(id);
+        }
+        if let Some(id) = self.ssao_program.take() {
+            ctx.programs_mut().remove(id);
+        }
         unsafe {
-            gl::DeleteProgram(self.render_program);
             gl::DeleteTextures(3, self.fbo_textures.as_ptr());
         }
         self.object.free();
         self.cube.free();
-        self.render_program = 0;
-        self.ssao_program = 0;
+        if let Some(ref mut overlay) = self.overlay {
+            overlay.free();
+        }
+        self.overlay = None;
         self.fbo_textures = [0; 3];
         self.render = RenderUniforms::new();
         self.ssao = SSAOUniforms::new();
     }
 
-    fn update(&mut self, current_time: f64) {
+    fn update(&mut self, _: &mut sb6::Context, current_time: f64) {
         if !self.paused {
             self.total_time += current_time - self.last_time;
         }
         self.last_time = current_time;
     }
 
-    fn render(&mut self, _: f64) {
+    fn render(&mut self, ctx: &mut sb6::Context, _: f64) {
         const BLACK: [GLfloat; 4] = [0.0, 0.0, 0.0, 0.0];
         const ONE: GLfloat = 1.0;
 
@@ -313,19 +357,9 @@ impl sb6::App for SampleApp {
             vmath::vec3(0.0, 0.0, 0.0),
             vmath::vec3(0.0, 1.0, 0.0),
         );
-        let aspect = self.info.window_width as f32 / self.info.window_height as f32;
+        let aspect = ctx.aspect_ratio();
         let proj_matrix = vmath::perspective(50.0, aspect, 0.1, 1000.0);
 
-        let shading_level = if self.show_shading {
-            if self.show_ao {
-                0.7
-            } else {
-                1.0
-            }
-        } else {
-            0.0
-        };
-
         let ssao_level = if self.show_ao {
             if self.show_shading {
                 0.3
@@ -337,12 +371,7 @@ impl sb6::App for SampleApp {
         };
 
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                self.info.window_width as GLint,
-                self.info.window_height as GLint,
-            );
+            ctx.set_viewport();
 
             gl::BindFramebuffer(gl::FRAMEBUFFER, self.render_fbo);
             gl::Enable(gl::DEPTH_TEST);
@@ -353,7 +382,7 @@ impl sb6::App for SampleApp {
 
             gl::BindBufferBase(gl::UNIFORM_BUFFER, 0, self.points_buffer);
 
-            gl::UseProgram(self.render_program);
+            gl::UseProgram(self.render_program.map_or(0, |id| ctx.programs().get(id)));
 
             gl::UniformMatrix4fv(self.render.proj_matrix, 1, gl::FALSE, proj_matrix.as_ptr());
 
@@ -366,8 +395,6 @@ impl sb6::App for SampleApp {
                 (lookat_matrix * mv_matrix).as_ptr(),
             );
 
-            gl::Uniform1f(self.render.shading_level, shading_level);
-
             self.object.render();
         }
 
@@ -386,20 +413,18 @@ impl sb6::App for SampleApp {
         }
 
         unsafe {
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, sb6::default_framebuffer());
 
-            gl::UseProgram(self.ssao_program);
+            gl::UseProgram(self.ssao_program.map_or(0, |id| ctx.programs().get(id)));
 
             gl::Uniform1f(
                 self.ssao.ssao_radius,
-                self.ssao_radius * self.info.window_width as f32 / 1000.0,
+                self.ssao_radius * ctx.framebuffer_size().0 as f32 / 1000.0,
             );
 
             gl::Uniform1f(self.ssao.ssao_level, ssao_level);
             // let weight_by_angle = if self.weight_by_angle { 1 } else { 0 };
             // gl::Uniform1i(self.ssao.weight_by_angle, weight_by_angle);
-            let randomize_points = if self.randomize_points { 1 } else { 0 };
-            gl::Uniform1i(self.ssao.randomize_points, randomize_points);
             gl::Uniform1ui(self.ssao.point_count, self.point_count);
 
             gl::ActiveTexture(gl::TEXTURE0);
@@ -411,9 +436,38 @@ impl sb6::App for SampleApp {
             gl::BindVertexArray(self.quad_vao);
             gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
         }
+
+        if let Some(ref mut overlay) = self.overlay {
+            let on_off = |b| if b { "On" } else { "Off" };
+            overlay.clear();
+            let _ = writeln!(overlay, "Points: {} (S/X)", self.point_count);
+            let _ = writeln!(overlay, "Radius: {:.2} (A/Z)", self.ssao_radius);
+            let _ = writeln!(
+                overlay,
+                "Randomize points: {} (R)",
+                on_off(self.randomize_points)
+            );
+            let _ = writeln!(overlay, "Shading: {} (Q)", on_off(self.show_shading));
+            let _ = writeln!(overlay, "Ambient occlusion: {} (W)", on_off(self.show_ao));
+            let _ = writeln!(overlay, "Paused: {} (P)", on_off(self.paused));
+            overlay.print("Reload shaders (L)");
+            overlay.draw(ctx);
+        }
+    }
+
+    fn on_programs_reloaded(&mut self, ctx: &mut sb6::Context, _: &[sb6::ProgramId]) {
+        if let Err(e) = self.get_uniforms(ctx) {
+            eprintln!("{}", e);
+        }
     }
 
-    fn on_key(&mut self, key: sb6::Key, action: sb6::Action) {
+    fn on_key(
+        &mut self,
+        ctx: &mut sb6::Context,
+        key: sb6::Key,
+        action: sb6::Action,
+        _: sb6::Modifiers,
+    ) {
         if action == sb6::Action::Release {
             match key {
                 sb6::Key::R => self.randomize_points = !self.randomize_points,
@@ -424,9 +478,14 @@ impl sb6::App for SampleApp {
                 sb6::Key::A => self.ssao_radius += 0.01,
                 sb6::Key::Z => self.ssao_radius -= 0.01,
                 sb6::Key::P => self.paused = !self.paused,
-                sb6::Key::L => self.load_shaders(),
+                sb6::Key::L => ctx.programs_mut().reload_all(),
                 _ => (),
             };
+            if key == sb6::Key::R || key == sb6::Key::Q || key == sb6::Key::W {
+                if let Err(e) = self.select_programs(ctx) {
+                    eprintln!("{}", e);
+                }
+            }
         }
     }
 }
@@ -434,6 +493,15 @@ impl sb6::App for SampleApp {
 fn main() {
     let mut init = sb6::AppInfo::default();
     init.title = "OpenGL SuperBible - SSAO";
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -27,9 +27,10 @@ extern crate rand;
 extern crate sb6;
 
 use gl::types::*;
-use rand::Rng;
+use rand::{Rng, SeedableRng, XorShiftRng};
 use sb6::vmath;
 use std::mem;
+use std::process;
 use std::ptr;
 use std::slice;
 
@@ -117,26 +118,28 @@ impl sb6::App for SampleApp {
         &self.info
     }
 
-    fn startup(&mut self) {
-        let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
-        let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
+        let fs = try!(sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+        let vs = try!(sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
 
         unsafe {
             self.render_prog = gl::CreateProgram();
             gl::AttachShader(self.render_prog, vs);
             gl::AttachShader(self.render_prog, fs);
             gl::LinkProgram(self.render_prog);
-            sb6::program::check_link_status(self.render_prog).unwrap();
+            try!(sb6::program::check_link_status(self.render_prog));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(fs);
         }
 
-        self.uniforms.time = sb6::program::get_uniform_location(self.render_prog, "time").unwrap();
-        self.uniforms.proj_matrix =
-            sb6::program::get_uniform_location(self.render_prog, "proj_matrix").unwrap();
+        self.uniforms.time = try!(sb6::program::get_uniform_location(self.render_prog, "time"));
+        self.uniforms.proj_matrix = try!(sb6::program::get_uniform_location(
+            self.render_prog,
+            "proj_matrix"
+        ));
 
-        self.star_texture = sb6::ktx::load("media/textures/star.ktx").unwrap();
+        self.star_texture = try!(sb6::ktx::load(&ctx.media_path("textures/star.ktx")));
 
         unsafe {
             gl::GenVertexArrays(1, &mut self.star_vao);
@@ -164,7 +167,8 @@ impl sb6::App for SampleApp {
             )
         };
 
-        let mut rng = rand::weak_rng();
+        // fixed seed so every run renders the same image
+        let mut rng = XorShiftRng::from_seed([0x193a_6754, 0xa8a7_d469, 0x9783_0e05, 0x113b_a7bb]);
 
         for star in &mut stars[..] {
             star.position.x = (rng.gen::<f32>() * 2.0 - 1.0) * 100.0;
@@ -197,30 +201,21 @@ impl sb6::App for SampleApp {
             gl::EnableVertexAttribArray(0);
             gl::EnableVertexAttribArray(1);
         }
+        Ok(())
     }
 
-    fn render(&mut self, time: f64) {
+    fn render(&mut self, ctx: &mut sb6::Context, time: f64) {
         const BLACK: [GLfloat; 4] = [0.0, 0.0, 0.0, 0.0];
         const ONE: [GLfloat; 1] = [1.0];
 
-        let proj_matrix = vmath::perspective(
-            50.0,
-            self.info.window_width as f32 / self.info.window_height as f32,
-            0.1,
-            1000.0,
-        );
+        let proj_matrix = vmath::perspective(50.0, ctx.aspect_ratio(), 0.1, 1000.0);
 
         let mut t = time as f32;
         t *= 0.1;
         t -= t.floor();
 
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                self.info.window_width as GLint,
-                self.info.window_height as GLint,
-            );
+            ctx.set_viewport();
             gl::ClearBufferfv(gl::COLOR, 0, BLACK.as_ptr());
             gl::ClearBufferfv(gl::DEPTH, 0, ONE.as_ptr());
 
@@ -250,6 +245,15 @@ fn main() {
         title: "OpenGL SuperBible - Starfield",
         ..sb6::AppInfo::default()
     };
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,218 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+#[macro_use]
+extern crate sb6;
+
+use gl::types::*;
+use sb6::vmath;
+use std::process;
+
+struct Uniforms {
+    mv_matrix: GLint,
+    proj_matrix: GLint,
+}
+
+struct SampleApp {
+    info: sb6::AppInfo,
+    program: GLuint,
+    uniforms: Uniforms,
+    object: sb6::object::Object,
+    paused: bool,
+    total_time: f64,
+    last_time: f64,
+    separation: f32,
+    convergence: f32,
+}
+
+impl SampleApp {
+    fn new(init: sb6::AppInfo) -> SampleApp {
+        SampleApp {
+            info: init,
+            program: 0,
+            uniforms: Uniforms {
+                mv_matrix: -1,
+                proj_matrix: -1,
+            },
+            object: sb6::object::Object::new(),
+            paused: false,
+            total_time: 0.0,
+            last_time: 0.0,
+            separation: 0.4,
+            convergence: 15.0,
+        }
+    }
+
+    fn load_shaders(&mut self, ctx: &sb6::Context) -> Result<(), sb6::Error> {
+        if self.program != 0 {
+            unsafe {
+                gl::DeleteProgram(self.program);
+            }
+        }
+
+        let shaders = [
+            try_load_shader!(
+                &ctx.media_path("shaders/stereo/stereo-render.vs.glsl"),
+                gl::VERTEX_SHADER
+            ),
+            try_load_shader!(
+                &ctx.media_path("shaders/stereo/stereo-render.fs.glsl"),
+                gl::FRAGMENT_SHADER,
+                ctx.preprocessor()
+            ),
+        ];
+
+        self.program = try!(sb6::program::link_from_shaders(&shaders));
+
+        self.uniforms.mv_matrix = try!(sb6::program::get_uniform_location(
+            self.program,
+            "mv_matrix"
+        ));
+        self.uniforms.proj_matrix = try!(sb6::program::get_uniform_location(
+            self.program,
+            "proj_matrix"
+        ));
+        Ok(())
+    }
+}
+
+impl sb6::App for SampleApp {
+    fn get_app_info(&self) -> &sb6::AppInfo {
+        &self.info
+    }
+
+    fn startup(&mut self, ctx: &mut sb6::Context) -> Result<(), sb6::Error> {
+        try!(self.load_shaders(ctx));
+
+        try_load_object!(&mut self.object, &ctx.media_path("objects/dragon.sbm"));
+
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LEQUAL);
+        }
+        Ok(())
+    }
+
+    fn shutdown(&mut self, _: &mut sb6::Context) {
+        self.object.free();
+        unsafe {
+            gl::DeleteProgram(self.program);
+        }
+        self.program = 0;
+    }
+
+    fn update(&mut self, _: &mut sb6::Context, time: f64) {
+        if !self.paused {
+            self.total_time += time - self.last_time;
+        }
+        self.last_time = time;
+    }
+
+    fn render_eye(&mut self, ctx: &mut sb6::Context, _: f64, eye: sb6::Eye) {
+        const BLACK: [GLfloat; 4] = [0.0, 0.0, 0.0, 0.0];
+        const ONE: GLfloat = 1.0;
+        let f = self.total_time as f32;
+
+        let offset = eye.offset(self.separation);
+        let proj_matrix = vmath::stereo_frustum(
+            50.0,
+            ctx.aspect_ratio(),
+            0.1,
+            1000.0,
+            offset,
+            self.convergence,
+        );
+        let view_matrix = vmath::look_at(
+            vmath::vec3(0.0, 0.0, 15.0),
+            vmath::vec3(0.0, 0.0, 0.0),
+            vmath::vec3(0.0, 1.0, 0.0),
+        );
+        let model_matrix =
+            vmath::rotate(f * 14.5, 0.0, 1.0, 0.0) * vmath::translate(0.0, -5.0, 0.0);
+        let mv_matrix = vmath::stereo_view(&view_matrix, offset) * model_matrix;
+
+        unsafe {
+            ctx.set_viewport();
+            gl::ClearBufferfv(gl::COLOR, 0, BLACK.as_ptr());
+            gl::ClearBufferfv(gl::DEPTH, 0, &ONE);
+
+            gl::UseProgram(self.program);
+            gl::UniformMatrix4fv(
+                self.uniforms.proj_matrix,
+                1,
+                gl::FALSE,
+                proj_matrix.as_ptr(),
+            );
+            gl::UniformMatrix4fv(self.uniforms.mv_matrix, 1, gl::FALSE, mv_matrix.as_ptr());
+        }
+
+        self.object.render();
+    }
+
+    fn on_key(
+        &mut self,
+        ctx: &mut sb6::Context,
+        key: sb6::Key,
+        action: sb6::Action,
+        _: sb6::Modifiers,
+    ) {
+        if action == sb6::Action::Release {
+            return;
+        }
+        match key {
+            sb6::Key::Z => self.separation = (self.separation - 0.05).max(0.0),
+            sb6::Key::X => self.separation += 0.05,
+            sb6::Key::C => self.convergence = (self.convergence - 1.0).max(1.0),
+            sb6::Key::V => self.convergence += 1.0,
+            sb6::Key::P if action == sb6::Action::Press => self.paused = !self.paused,
+            sb6::Key::L if action == sb6::Action::Press => {
+                if let Err(e) = self.load_shaders(ctx) {
+                    eprintln!("{}", e);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn main() {
+    let mut init = sb6::AppInfo::default();
+    init.title = "OpenGL SuperBible - Stereo";
+    init.min_major_version = 4;
+    init.min_minor_version = 2;
+    init.stereo = true;
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
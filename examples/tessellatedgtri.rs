@@ -26,10 +26,13 @@ extern crate gl;
 extern crate sb6;
 
 use gl::types::*;
+use std::process;
 
-const VS_SRC: &str = r"
+const SRC: &str = r"
 #version 410 core
 
+#pragma stage vertex
+
 void main(void)
 {
     const vec4 vertices[] = vec4[](vec4( 0.25, -0.25, 0.5, 1.0),
@@ -38,10 +41,8 @@ void main(void)
 
     gl_Position = vertices[gl_VertexID];
 }
-";
 
-const TCS_SRC: &str = r"
-#version 410 core
+#pragma stage tess_control
 
 layout (vertices = 3) out;
 
@@ -56,10 +57,8 @@ void main(void)
     }
     gl_out[gl_InvocationID].gl_Position = gl_in[gl_InvocationID].gl_Position;
 }
-";
 
-const TES_SRC: &str = r"
-#version 410 core
+#pragma stage tess_evaluation
 
 layout (triangles, equal_spacing, cw) in;
 
@@ -69,10 +68,8 @@ void main(void)
         (gl_TessCoord.y * gl_in[1].gl_Position) +
         (gl_TessCoord.z * gl_in[2].gl_Position);
 }
-";
 
-const GS_SRC: &str = r"
-#version 410 core
+#pragma stage geometry
 
 layout (triangles) in;
 layout (points, max_vertices = 3) out;
@@ -87,10 +84,8 @@ void main(void)
         EmitVertex();
     }
 }
-";
 
-const FS_SRC: &str = r"
-#version 410 core
+#pragma stage fragment
 
 out vec4 color;
 
@@ -121,39 +116,19 @@ impl sb6::App for SampleApp {
         &self.info
     }
 
-    fn startup(&mut self) {
-        unsafe {
-            self.program = gl::CreateProgram();
-
-            let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
-            let tcs = sb6::shader::create_from_source(TCS_SRC, gl::TESS_CONTROL_SHADER).unwrap();
-            let tes = sb6::shader::create_from_source(TES_SRC, gl::TESS_EVALUATION_SHADER).unwrap();
-            let gs = sb6::shader::create_from_source(GS_SRC, gl::GEOMETRY_SHADER).unwrap();
-            let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
-
-            gl::AttachShader(self.program, vs);
-            gl::AttachShader(self.program, tcs);
-            gl::AttachShader(self.program, tes);
-            gl::AttachShader(self.program, gs);
-            gl::AttachShader(self.program, fs);
-
-            gl::LinkProgram(self.program);
-            sb6::program::check_link_status(self.program).unwrap();
-
-            gl::DeleteShader(vs);
-            gl::DeleteShader(tcs);
-            gl::DeleteShader(tes);
-            gl::DeleteShader(gs);
-            gl::DeleteShader(fs);
+    fn startup(&mut self, _: &mut sb6::Context) -> Result<(), sb6::Error> {
+        self.program = try!(sb6::program::create_from_source(SRC));
 
+        unsafe {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::BindVertexArray(self.vao);
 
             gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
         }
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         unsafe {
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteProgram(self.program);
@@ -162,7 +137,7 @@ impl sb6::App for SampleApp {
         self.program = 0;
     }
 
-    fn render(&mut self, _: f64) {
+    fn render(&mut self, _: &mut sb6::Context, _: f64) {
         const GREEN: [GLfloat; 4] = [0.0, 0.25, 0.0, 1.0];
         unsafe {
             gl::ClearBufferfv(gl::COLOR, 0, GREEN.as_ptr());
@@ -176,6 +151,15 @@ impl sb6::App for SampleApp {
 fn main() {
     let mut init = sb6::AppInfo::default();
     init.title = "OpenGL SuperBible - Tessellation and Geometry Shaders";
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -26,6 +26,7 @@ extern crate gl;
 extern crate sb6;
 
 use gl::types::*;
+use std::process;
 
 const VS_SRC: &str = r"
 #version 410 core
@@ -103,14 +104,20 @@ impl sb6::App for SampleApp {
         &self.info
     }
 
-    fn startup(&mut self) {
+    fn startup(&mut self, _: &mut sb6::Context) -> Result<(), sb6::Error> {
         unsafe {
             self.program = gl::CreateProgram();
 
-            let vs = sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER).unwrap();
-            let tcs = sb6::shader::create_from_source(TCS_SRC, gl::TESS_CONTROL_SHADER).unwrap();
-            let tes = sb6::shader::create_from_source(TES_SRC, gl::TESS_EVALUATION_SHADER).unwrap();
-            let fs = sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER).unwrap();
+            let vs = try!(sb6::shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
+            let tcs = try!(sb6::shader::create_from_source(
+                TCS_SRC,
+                gl::TESS_CONTROL_SHADER
+            ));
+            let tes = try!(sb6::shader::create_from_source(
+                TES_SRC,
+                gl::TESS_EVALUATION_SHADER
+            ));
+            let fs = try!(sb6::shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
 
             gl::AttachShader(self.program, vs);
             gl::AttachShader(self.program, tcs);
@@ -118,7 +125,7 @@ impl sb6::App for SampleApp {
             gl::AttachShader(self.program, fs);
 
             gl::LinkProgram(self.program);
-            sb6::program::check_link_status(self.program).unwrap();
+            try!(sb6::program::check_link_status(self.program));
 
             gl::DeleteShader(vs);
             gl::DeleteShader(tcs);
@@ -130,9 +137,10 @@ impl sb6::App for SampleApp {
 
             gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
         }
+        Ok(())
     }
 
-    fn shutdown(&mut self) {
+    fn shutdown(&mut self, _: &mut sb6::Context) {
         unsafe {
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteProgram(self.program);
@@ -141,7 +149,7 @@ impl sb6::App for SampleApp {
         self.program = 0;
     }
 
-    fn render(&mut self, _: f64) {
+    fn render(&mut self, _: &mut sb6::Context, _: f64) {
         const GREEN: [GLfloat; 4] = [0.0, 0.25, 0.0, 1.0];
         unsafe {
             gl::ClearBufferfv(gl::COLOR, 0, GREEN.as_ptr());
@@ -154,6 +162,15 @@ impl sb6::App for SampleApp {
 fn main() {
     let mut init = sb6::AppInfo::default();
     init.title = "OpenGL SuperBible - Tessellated Triangle";
-    let mut app = SampleApp::new(init);
-    sb6::run(&mut app);
+    let mut app = match sb6::AppInfo::from_args(init) {
+        Ok(init) => SampleApp::new(init),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = sb6::run(&mut app) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,15 @@
+// Phong lighting terms shared by the sample shaders. N, L and V are the
+// normalized surface normal, light vector and view vector.
+#pragma once
+
+vec3 phong_diffuse(vec3 N, vec3 L, vec3 diffuse_albedo)
+{
+    return max(dot(N, L), 0.0) * diffuse_albedo;
+}
+
+vec3 phong_specular(vec3 N, vec3 L, vec3 V, vec3 specular_albedo, float specular_power)
+{
+    // Calculate R locally
+    vec3 R = reflect(-L, N);
+    return pow(max(dot(R, V), 0.0), specular_power) * specular_albedo;
+}
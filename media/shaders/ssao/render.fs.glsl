@@ -1,5 +1,7 @@
 #version 410 core
 
+#include "common/phong.glsl"
+
 // Output
 layout (location = 0) out vec4 color;
 layout (location = 1) out vec4 normal_depth;
@@ -16,7 +18,11 @@ in VS_OUT
 uniform vec3 diffuse_albedo = vec3(0.8, 0.8, 0.9);
 uniform vec3 specular_albedo = vec3(0.01);
 uniform float specular_power = 128.0;
-uniform float shading_level = 1.0;
+
+// Amount of shading, defined by the app for each variant
+#ifndef SHADING_LEVEL
+#define SHADING_LEVEL 1.0
+#endif
 
 void main(void)
 {
@@ -25,15 +31,12 @@ void main(void)
     vec3 L = normalize(fs_in.L);
     vec3 V = normalize(fs_in.V);
 
-    // Calculate R locally
-    vec3 R = reflect(-L, N);
-
     // Compute the diffuse and specular components for each fragment
-    vec3 diffuse = max(dot(N, L), 0.0) * diffuse_albedo;
+    vec3 diffuse = phong_diffuse(N, L, diffuse_albedo);
     diffuse *= diffuse;
-    vec3 specular = pow(max(dot(R, V), 0.0), specular_power) * specular_albedo;
+    vec3 specular = phong_specular(N, L, V, specular_albedo, specular_power);
 
     // Write final color to the framebuffer
-    color = mix(vec4(0.0), vec4(diffuse + specular, 1.0), shading_level);
+    color = mix(vec4(0.0), vec4(diffuse + specular, 1.0), SHADING_LEVEL);
     normal_depth = vec4(N, fs_in.V.z);
 }
@@ -13,7 +13,11 @@ uniform float object_level = 1.0;
 uniform float ssao_radius = 5.0;
 uniform bool weight_by_angle = true;
 uniform uint point_count = 8;
-uniform bool randomize_points = true;
+
+// Set to 0 by the app for the variant that doesn't randomize points
+#ifndef RANDOMIZE_POINTS
+#define RANDOMIZE_POINTS 1
+#endif
 
 // Uniform block containing up to 256 random directions (x,y,z,0)
 // and 256 more completely random vectors
@@ -51,8 +55,9 @@ void main(void)
 
     // r is our 'radius randomizer'
     float r = (v.r + 3.0) * 0.1;
-    if (!randomize_points)
-        r = 0.5;
+#if !RANDOMIZE_POINTS
+    r = 0.5;
+#endif
 
     // For each random point (or direction)...
     for (i = 0; i < point_count; i++)
@@ -1,5 +1,7 @@
 #version 420 core
 
+#include "common/phong.glsl"
+
 layout (location = 0) out vec4 color;
 
 in VS_OUT
@@ -22,12 +24,9 @@ void main(void)
     vec3 L = normalize(fs_in.L);
     vec3 V = normalize(fs_in.V);
 
-    // Calculate R locally
-    vec3 R = reflect(-L, N);
-
     // Compute the diffuse and specular components for each fragment
-    vec3 diffuse = max(dot(N, L), 0.0) * diffuse_albedo;
-    vec3 specular = pow(max(dot(R, V), 0.0), specular_power) * specular_albedo;
+    vec3 diffuse = phong_diffuse(N, L, diffuse_albedo);
+    vec3 specular = phong_specular(N, L, V, specular_albedo, specular_power);
 
     // Write final color to the framebuffer
     color = vec4(diffuse + specular, 1.0);
@@ -25,11 +25,40 @@
 extern crate gl;
 extern crate glfw;
 
-use glfw::Context;
+use capture;
+use clock::{Clock, FixedClock, RealClock, ScriptedClock};
+use context::{Context, WindowRequest};
+use egl;
+use error::Error;
+use gamepad;
+use gamepad::Connection;
+use gl::types::*;
+use gl_debug;
+use gl_debug::DebugConfig;
+use gl_info::GlInfo;
+use glfw::Context as GlfwContext;
+use offscreen::{FramebufferError, Offscreen};
+use options;
+use replay::{Recorder, Recording};
+use stats_overlay::StatsOverlay;
+use std::cmp;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::Receiver;
+use stereo::StereoRenderer;
+use window;
+use window::SharedWindow;
+
+pub use hot_reload::ProgramId;
+pub use stereo::{Eye, StereoMode};
 
 // Re-export some glfw enums required for event handling
+pub use gamepad::{GamepadAxis, GamepadButton, JoystickId};
 pub use glfw::Action;
 pub use glfw::Key;
+pub use glfw::Modifiers;
+pub use glfw::MouseButton;
 
 #[derive(Clone)]
 pub struct AppInfo {
@@ -38,103 +67,1148 @@ pub struct AppInfo {
     pub window_height: u32,
     pub major_version: u32,
     pub minor_version: u32,
+    /// The oldest OpenGL version to fall back to if a context with
+    /// `major_version.minor_version` can't be created.
+    pub min_major_version: u32,
+    pub min_minor_version: u32,
     pub samples: usize,
     pub fullscreen: bool,
     pub vsync: bool,
     pub cursor: bool,
+    /// Render each eye separately, see `sb6::stereo`.
     pub stereo: bool,
+    /// How to show the eyes when the framebuffer isn't quad buffered. Must
+    /// not be `QuadBuffer` when running headless.
+    pub stereo_fallback: StereoMode,
     pub debug: bool,
+    /// Message filtering and error handling used when `debug` is set.
+    pub debug_config: DebugConfig,
+    /// Render into an offscreen framebuffer with an EGL context instead of
+    /// opening a window. GLFW isn't used, so no display server is needed, see
+    /// `sb6::egl`.
+    pub headless: bool,
+    /// Stop running after this many frames. Headless runs default to one
+    /// frame if this is not set.
+    pub max_frames: Option<u32>,
+    /// Reset the viewport to cover the framebuffer before every `render`.
+    pub auto_viewport: bool,
+    /// Advance time by a fixed step per frame instead of using the wall
+    /// clock, so frame N always sees the same time.
+    pub time_step: Option<f64>,
+    /// Call `update` at this fixed interval in seconds, possibly several times
+    /// per frame, and `render_interpolated` once per frame.
+    pub fixed_update_step: Option<f64>,
+    /// Pressing this key closes the window. The key is not passed to
+    /// `on_key`.
+    pub exit_key: Option<Key>,
+    /// Pressing this key saves a PNG screenshot of the next frame to the
+    /// working directory.
+    pub screenshot_key: Option<Key>,
+    /// Show the frame time graph from the start.
+    pub stats_overlay: bool,
+    /// Pressing this key toggles the frame time graph.
+    pub stats_key: Option<Key>,
+    /// Measure GPU render times with `GL_TIME_ELAPSED` queries. Disable this
+    /// if the app issues its own `GL_TIME_ELAPSED` queries in `render`, as
+    /// they can't be nested.
+    pub gpu_timer: bool,
+    /// Save frame N (counting from 0) to `capture_path` after it is rendered.
+    pub capture_frame: Option<u32>,
+    /// Destination for `capture_frame`, either a `.png` or `.ppm` file.
+    pub capture_path: String,
+    /// Directory that `Context::media_path` resolves media files against.
+    pub media_root: String,
+    /// Record frame times and window events to this file, see
+    /// `sb6::replay`.
+    pub record_path: Option<String>,
+    /// Replay frame times and window events from this file instead of using
+    /// the clock and live input. The app exits after the last recorded frame.
+    pub replay_path: Option<String>,
+    /// Stick and trigger values within this distance of rest read as 0.
+    pub gamepad_deadzone: f32,
+    /// A file of extra gamepad mappings in SDL's `gamecontrollerdb.txt`
+    /// format, for controllers GLFW doesn't recognise.
+    pub gamepad_mappings: Option<String>,
 }
 
 impl AppInfo {
-    #[cfg(use_gl_3_3)]
-    fn version() -> (u32, u32) {
-        (3, 3)
-    }
-    #[cfg(not(use_gl_3_3))]
-    fn version() -> (u32, u32) {
-        (4, 3)
-    }
     pub fn default() -> AppInfo {
-        let (major, minor) = AppInfo::version();
         AppInfo {
             title: "SuperBible6 Example",
             window_width: 800,
             window_height: 600,
-            major_version: major,
-            minor_version: minor,
+            major_version: 4,
+            minor_version: 3,
+            min_major_version: 3,
+            min_minor_version: 3,
             samples: 0,
             fullscreen: false,
             vsync: false,
             cursor: true,
             stereo: false,
+            stereo_fallback: StereoMode::Anaglyph,
             debug: false,
+            debug_config: DebugConfig::default(),
+            headless: false,
+            max_frames: None,
+            auto_viewport: false,
+            time_step: None,
+            fixed_update_step: None,
+            exit_key: Some(Key::Escape),
+            screenshot_key: Some(Key::F12),
+            stats_overlay: false,
+            stats_key: Some(Key::F3),
+            gpu_timer: true,
+            capture_frame: None,
+            capture_path: String::from("capture.png"),
+            media_root: String::from("media"),
+            record_path: None,
+            replay_path: None,
+            gamepad_deadzone: 0.15,
+            gamepad_mappings: None,
         }
     }
+
+    /// Returns `defaults` overridden by any `SB6_*` environment variables and
+    /// then the process's command line flags, see `sb6::options`. Invalid
+    /// flags and `--help` are returned as `Error::Options`, which displays
+    /// the usage.
+    pub fn from_args(defaults: AppInfo) -> Result<AppInfo, Error> {
+        let mut info = defaults;
+        try!(options::apply_overrides(&mut info));
+        Ok(info)
+    }
+}
+
+static DEFAULT_FRAMEBUFFER: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the framebuffer apps should bind to draw to the screen. This is 0
+/// for a normal window and the offscreen target when running headless.
+pub fn default_framebuffer() -> GLuint {
+    DEFAULT_FRAMEBUFFER.load(Ordering::Relaxed)
 }
 
 pub trait App {
     fn get_app_info(&self) -> &AppInfo;
-    fn startup(&mut self) {}
-    fn update(&mut self, _: f64) {}
-    fn render(&mut self, _: f64) {}
-    fn shutdown(&mut self) {}
-    fn on_resize(&mut self, _: isize, _: isize) {}
-    fn on_key(&mut self, _: Key, _: Action) {}
+    /// Called once after the window and context are created. Returning an
+    /// error stops the app without calling `shutdown` and `run` returns it.
+    fn startup(&mut self, _: &mut Context) -> Result<(), Error> {
+        Ok(())
+    }
+    fn update(&mut self, _: &mut Context, _: f64) {}
+    fn render(&mut self, _: &mut Context, _: f64) {}
+    /// Called for each eye instead of `render` when running in stereo. The
+    /// framebuffer size and `default_framebuffer` are the eye's, so apps can
+    /// render as usual with per eye matrices.
+    fn render_eye(&mut self, ctx: &mut Context, time: f64, _eye: Eye) {
+        self.render(ctx, time)
+    }
+    /// Called instead of `render` when `AppInfo::fixed_update_step` is set.
+    /// `alpha` is how far between the last and the next update the frame
+    /// time falls, in the range [0, 1).
+    fn render_interpolated(&mut self, ctx: &mut Context, time: f64, _alpha: f64) {
+        self.render(ctx, time)
+    }
+    fn shutdown(&mut self, _: &mut Context) {}
+    /// The window was resized, in screen coordinates.
+    fn on_resize(&mut self, _: &mut Context, _: isize, _: isize) {}
+    /// The framebuffer was resized, in pixels. This differs from the window
+    /// size on HiDPI displays.
+    fn on_framebuffer_resize(&mut self, _: &mut Context, _: isize, _: isize) {}
+    fn on_key(&mut self, _: &mut Context, _: Key, _: Action, _: Modifiers) {}
+    /// Unicode text input, after keyboard layout and modifiers are applied.
+    fn on_char(&mut self, _: &mut Context, _: char) {}
+    fn on_mouse_button(&mut self, _: &mut Context, _: MouseButton, _: Action, _: Modifiers) {}
+    /// The cursor moved, in screen coordinates relative to the top left of
+    /// the window.
+    fn on_mouse_move(&mut self, _: &mut Context, _: f64, _: f64) {}
+    fn on_mouse_wheel(&mut self, _: &mut Context, _: f64, _: f64) {}
+    fn on_focus(&mut self, _: &mut Context, _: bool) {}
+    fn on_file_drop(&mut self, _: &mut Context, _: &[PathBuf]) {}
+    /// A gamepad was connected. Its state is available from
+    /// `Context::gamepads` from now on.
+    fn on_gamepad_connected(&mut self, _: &mut Context, _: JoystickId) {}
+    fn on_gamepad_disconnected(&mut self, _: &mut Context, _: JoystickId) {}
+    /// Programs in `Context::programs` were rebuilt after their shader files
+    /// changed. Uniform locations looked up before are no longer valid.
+    fn on_programs_reloaded(&mut self, _: &mut Context, _: &[ProgramId]) {}
 }
 
-fn handle_window_event<T: App>(app: &mut T, window: &mut glfw::Window, event: &glfw::WindowEvent) {
+fn save_capture(path: &str, width: u32, height: u32) {
+    match capture::read_default_framebuffer(width, height).save(path) {
+        Ok(_) => info!("Saved capture to '{}'", path),
+        Err(e) => error!("Error saving capture '{}': {}", path, e),
+    }
+}
+
+/// Passes `event` to the app. Returns true if it asked to close the window,
+/// by pressing `AppInfo::exit_key`.
+pub(crate) fn handle_window_event<T: App + ?Sized>(
+    app: &mut T,
+    ctx: &mut Context,
+    event: &glfw::WindowEvent,
+) -> bool {
     match *event {
-        glfw::WindowEvent::Key(glfw::Key::Escape, _, glfw::Action::Press, _) => {
-            window.set_should_close(true)
+        // repeats and the release of the exit key are swallowed too, so the
+        // app never sees the key
+        glfw::WindowEvent::Key(key, _, action, _) if ctx.info().exit_key == Some(key) => {
+            return action == glfw::Action::Press
+        }
+        glfw::WindowEvent::Key(key, _, action, mods) => app.on_key(ctx, key, action, mods),
+        glfw::WindowEvent::Size(w, h) => {
+            ctx.set_window_size(w as u32, h as u32);
+            app.on_resize(ctx, w as isize, h as isize)
         }
-        glfw::WindowEvent::Key(key, _, action, _) => app.on_key(key, action),
-        glfw::WindowEvent::Size(w, h) => app.on_resize(w as isize, h as isize),
+        glfw::WindowEvent::FramebufferSize(w, h) => {
+            ctx.set_framebuffer_size(w as u32, h as u32);
+            app.on_framebuffer_resize(ctx, w as isize, h as isize)
+        }
+        glfw::WindowEvent::Char(c) => app.on_char(ctx, c),
+        glfw::WindowEvent::MouseButton(button, action, mods) => {
+            app.on_mouse_button(ctx, button, action, mods)
+        }
+        glfw::WindowEvent::CursorPos(x, y) => app.on_mouse_move(ctx, x, y),
+        glfw::WindowEvent::Scroll(x, y) => app.on_mouse_wheel(ctx, x, y),
+        glfw::WindowEvent::Focus(focused) => app.on_focus(ctx, focused),
+        glfw::WindowEvent::FileDrop(ref paths) => app.on_file_drop(ctx, paths),
         _ => (),
     }
+    false
 }
 
-pub fn run<T: App>(app: &mut T) {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-    let (mut window, events) = {
-        let info = app.get_app_info();
-        glfw.window_hint(glfw::WindowHint::ContextVersion(
-            info.major_version,
-            info.minor_version,
-        ));
-        glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-            glfw::OpenGlProfileHint::Core,
-        ));
-        glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
-        glfw.create_window(
-            info.window_width,
-            info.window_height,
-            info.title,
+fn create_window(
+    glfw: &mut glfw::Glfw,
+    info: &AppInfo,
+) -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
+    glfw.with_primary_monitor(|glfw, monitor| {
+        let mode = match monitor {
+            Some(monitor) if info.fullscreen => glfw::WindowMode::FullScreen(monitor),
+            _ => glfw::WindowMode::Windowed,
+        };
+        glfw.create_window(info.window_width, info.window_height, info.title, mode)
+    })
+}
+
+/// The window position and size saved when switching to fullscreen, so they
+/// can be restored when switching back.
+pub(crate) struct WindowedRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn set_fullscreen(
+    glfw: &mut glfw::Glfw,
+    window: &mut glfw::Window,
+    fullscreen: bool,
+    windowed: &mut WindowedRect,
+) -> bool {
+    if !fullscreen {
+        window.set_monitor(
             glfw::WindowMode::Windowed,
-        ).expect("Failed to create GLFW window.")
+            windowed.x,
+            windowed.y,
+            windowed.width,
+            windowed.height,
+            None,
+        );
+        return false;
+    }
+    let (x, y) = window.get_pos();
+    let (width, height) = window.get_size();
+    glfw.with_primary_monitor_mut(|_, monitor| match monitor {
+        Some(monitor) => match monitor.get_video_mode() {
+            Some(mode) => {
+                *windowed = WindowedRect {
+                    x,
+                    y,
+                    width: width as u32,
+                    height: height as u32,
+                };
+                window.set_monitor(
+                    glfw::WindowMode::FullScreen(monitor),
+                    0,
+                    0,
+                    mode.width,
+                    mode.height,
+                    Some(mode.refresh_rate),
+                );
+                true
+            }
+            None => {
+                warn!("Failed to get the primary monitor video mode");
+                false
+            }
+        },
+        None => {
+            warn!("No primary monitor found, staying windowed");
+            false
+        }
+    })
+}
+
+pub(crate) fn cursor_mode(info: &AppInfo, captured: bool) -> glfw::CursorMode {
+    if captured {
+        glfw::CursorMode::Disabled
+    } else if info.cursor {
+        glfw::CursorMode::Normal
+    } else {
+        glfw::CursorMode::Hidden
+    }
+}
+
+/// Applies the window changes the app requested through its `Context`.
+/// Windows to open are added to `opened`.
+pub(crate) fn apply_window_requests(
+    glfw: &mut glfw::Glfw,
+    window: &mut glfw::Window,
+    ctx: &mut Context,
+    windowed: &mut WindowedRect,
+    opened: &mut Vec<Box<dyn App>>,
+) {
+    for request in ctx.take_requests() {
+        match request {
+            WindowRequest::Close => window.set_should_close(true),
+            WindowRequest::Open(app) => opened.push(app),
+            WindowRequest::Title(ref title) => window.set_title(title),
+            WindowRequest::Fullscreen(fullscreen) => {
+                let fullscreen = set_fullscreen(glfw, window, fullscreen, windowed);
+                ctx.set_fullscreen_state(fullscreen);
+            }
+            WindowRequest::CursorCaptured(captured) => {
+                window.set_cursor_mode(cursor_mode(ctx.info(), captured))
+            }
+            WindowRequest::SwapInterval(interval) => {
+                glfw.set_swap_interval(glfw::SwapInterval::Sync(interval))
+            }
+        }
+    }
+}
+
+/// Applies the requests that make sense without a window when running
+/// headless. Returns true if the app asked to close.
+fn apply_headless_requests(ctx: &mut Context) -> bool {
+    let mut close = false;
+    for request in ctx.take_requests() {
+        match request {
+            WindowRequest::Close => close = true,
+            WindowRequest::Open(_) => {
+                warn!("Extra windows are not supported when running headless")
+            }
+            WindowRequest::Fullscreen(_) => ctx.set_fullscreen_state(false),
+            WindowRequest::Title(_)
+            | WindowRequest::CursorCaptured(_)
+            | WindowRequest::SwapInterval(_) => (),
+        }
+    }
+    close
+}
+
+/// Core profile versions tried, newest first, when the requested version is
+/// not available.
+const GL_VERSIONS: [(u32, u32); 9] = [
+    (4, 6),
+    (4, 5),
+    (4, 4),
+    (4, 3),
+    (4, 2),
+    (4, 1),
+    (4, 0),
+    (3, 3),
+    (3, 2),
+];
+
+/// The context versions to try, the requested version in `info` followed by
+/// the older ones down to the minimum.
+fn context_versions(info: &AppInfo) -> Vec<(u32, u32)> {
+    let requested = (info.major_version, info.minor_version);
+    let min = (info.min_major_version, info.min_minor_version);
+    let fallbacks = GL_VERSIONS
+        .iter()
+        .cloned()
+        .filter(|&version| version < requested && version >= min);
+    Some(requested).into_iter().chain(fallbacks).collect()
+}
+
+/// Creates a window with the newest context version between the requested
+/// and minimum versions in `info`.
+fn create_window_with_version(
+    glfw: &mut glfw::Glfw,
+    info: &AppInfo,
+) -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
+    for (major, minor) in context_versions(info) {
+        glfw.window_hint(glfw::WindowHint::ContextVersion(major, minor));
+        if let Some(window) = create_window(glfw, info) {
+            return Some(window);
+        }
+        info!("OpenGL {}.{} is not available", major, minor);
+    }
+    None
+}
+
+/// Logs a warning for each capability requested in `info` that the window or
+/// context could not provide. `fullscreen` is whether the window is.
+fn report_unsatisfied(info: &AppInfo, fullscreen: bool) {
+    if info.samples > 0 {
+        let mut samples = 0;
+        unsafe {
+            gl::GetIntegerv(gl::SAMPLES, &mut samples);
+        }
+        if info.headless {
+            warn!("Multisampling is not supported when running headless");
+        } else if (samples as usize) < info.samples {
+            warn!(
+                "Requested {} samples but the framebuffer has {}",
+                info.samples, samples
+            );
+        }
+    }
+
+    if info.fullscreen {
+        if info.headless {
+            warn!("Fullscreen is not supported when running headless");
+        } else if !fullscreen {
+            warn!("No primary monitor found, running windowed");
+        }
+    }
+
+    if info.stereo {
+        let mut stereo = gl::FALSE;
+        unsafe {
+            gl::GetBooleanv(gl::STEREO, &mut stereo);
+        }
+        if stereo != gl::TRUE {
+            warn!(
+                "Requested stereo but the framebuffer is not stereo, using {:?}",
+                info.stereo_fallback
+            );
+        }
+    }
+
+    let mut flags = 0;
+    unsafe {
+        gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
+    }
+    if info.debug && flags as GLuint & gl::CONTEXT_FLAG_DEBUG_BIT == 0 {
+        warn!("Requested a debug context but did not get one");
+    }
+
+    if info.headless && (info.vsync || !info.cursor) {
+        warn!("Vsync and cursor settings are ignored when running headless");
+    }
+}
+
+/// The most updates run in one frame when using a fixed update step. If the
+/// app falls further behind than this the remaining time is dropped.
+const MAX_UPDATES_PER_FRAME: u32 = 8;
+
+/// Runs the app, using a `FixedClock` if `AppInfo::time_step` is set and the
+/// wall clock otherwise.
+pub fn run<T: App>(app: &mut T) -> Result<(), Error> {
+    let info = app.get_app_info().clone();
+    match info.time_step {
+        Some(step) => run_app(app, info, &mut FixedClock::new(step)),
+        None => run_app(app, info, &mut RealClock::new()),
+    }
+}
+
+/// Runs the app with time supplied by `clock`.
+pub fn run_with_clock<T: App, C: Clock>(app: &mut T, clock: &mut C) -> Result<(), Error> {
+    let info = app.get_app_info().clone();
+    run_app(app, info, clock)
+}
+
+/// Rebuilds the programs whose files changed and tells the app about them.
+pub(crate) fn reload_programs<T: App + ?Sized>(app: &mut T, ctx: &mut Context) {
+    let reloaded = ctx.programs_mut().poll();
+    if !reloaded.is_empty() {
+        app.on_programs_reloaded(ctx, &reloaded);
+    }
+}
+
+/// Renders both eyes with `render_eye` and composes them into the default
+/// framebuffer.
+fn render_stereo<T: App>(
+    app: &mut T,
+    ctx: &mut Context,
+    stereo: &mut StereoRenderer,
+    time: f64,
+) -> Result<(), FramebufferError> {
+    let framebuffer = default_framebuffer();
+    let size = ctx.framebuffer_size();
+    let eye_size = try!(stereo.prepare(size));
+    ctx.set_framebuffer_size(eye_size.0, eye_size.1);
+    for &eye in [Eye::Left, Eye::Right].iter() {
+        let target = stereo.begin_eye(eye, framebuffer);
+        DEFAULT_FRAMEBUFFER.store(target, Ordering::Relaxed);
+        if ctx.auto_viewport() {
+            ctx.set_viewport();
+        }
+        app.render_eye(ctx, time, eye);
+    }
+    DEFAULT_FRAMEBUFFER.store(framebuffer, Ordering::Relaxed);
+    ctx.set_framebuffer_size(size.0, size.1);
+    stereo.finish(framebuffer, size);
+    Ok(())
+}
+
+fn free_offscreen(offscreen: &mut Option<Offscreen>) {
+    if let Some(ref mut offscreen) = *offscreen {
+        offscreen.free();
+        DEFAULT_FRAMEBUFFER.store(0, Ordering::Relaxed);
+    }
+}
+
+/// The main window when not running headless.
+struct MainWindow {
+    glfw: glfw::Glfw,
+    window: glfw::Window,
+    events: Receiver<(f64, glfw::WindowEvent)>,
+    windowed: WindowedRect,
+}
+
+/// Initializes GLFW and creates the main window, with its context current.
+fn open_main_window(info: &AppInfo) -> Result<MainWindow, Error> {
+    // errors are logged rather than fatal so that failed window creation can
+    // fall back to fewer capabilities
+    let mut glfw = try!(glfw::init(glfw::LOG_ERRORS));
+    if let Some(ref path) = info.gamepad_mappings {
+        match fs::read_to_string(path) {
+            Ok(ref mappings) if gamepad::update_mappings(mappings) => (),
+            Ok(_) => warn!("Failed to parse gamepad mappings '{}'", path),
+            Err(e) => warn!("Error loading gamepad mappings '{}': {}", path, e),
+        }
+    }
+    glfw.window_hint(glfw::WindowHint::OpenGlProfile(
+        glfw::OpenGlProfileHint::Core,
+    ));
+    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+    glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(info.debug));
+    glfw.window_hint(glfw::WindowHint::Stereo(info.stereo));
+    if info.samples > 0 {
+        glfw.window_hint(glfw::WindowHint::Samples(Some(info.samples as u32)));
+    }
+
+    let window = match create_window_with_version(&mut glfw, info) {
+        None if info.stereo => {
+            // quad buffered stereo is rarely available, so try again without
+            warn!("Failed to create a stereo window, falling back to mono");
+            glfw.window_hint(glfw::WindowHint::Stereo(false));
+            create_window_with_version(&mut glfw, info)
+        }
+        window => window,
     };
+    let (mut window, events) = try!(window.ok_or(Error::ContextCreation(
+        (info.major_version, info.minor_version),
+        (info.min_major_version, info.min_minor_version),
+    )));
 
     window.set_key_polling(true);
+    window.set_char_polling(true);
     window.set_size_polling(true);
+    window.set_framebuffer_size_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_scroll_polling(true);
+    window.set_focus_polling(true);
+    window.set_drag_and_drop_polling(true);
     window.make_current();
 
-    // Load the OpenGL function pointers
-    gl::load_with(|s| window.get_proc_address(s) as *const _);
+    // a fullscreen window has no windowed position to restore
+    let fullscreen = window.with_window_mode(|mode| match mode {
+        glfw::WindowMode::FullScreen(_) => true,
+        glfw::WindowMode::Windowed => false,
+    });
+    let (x, y) = if fullscreen { (0, 0) } else { window.get_pos() };
+    let windowed = WindowedRect {
+        x,
+        y,
+        width: info.window_width,
+        height: info.window_height,
+    };
+    Ok(MainWindow {
+        glfw,
+        window,
+        events,
+        windowed,
+    })
+}
 
-    app.startup();
+/// Creates the EGL context used when running headless and makes it current.
+/// GLFW isn't initialized, so no display connection is needed.
+fn create_headless_context(info: &AppInfo) -> Result<egl::Context, Error> {
+    match try!(egl::Context::new(&context_versions(info), info.debug)) {
+        Some(context) => Ok(context),
+        None => Err(Error::ContextCreation(
+            (info.major_version, info.minor_version),
+            (info.min_major_version, info.min_minor_version),
+        )),
+    }
+}
 
-    while !window.should_close() {
-        let time = glfw.get_time();
-        app.update(time);
-        app.render(time);
+/// Checks that a time step is positive, since zero, negative or NaN steps
+/// would stall or never end the loops that consume them.
+fn validate_time_step(field: &'static str, step: Option<f64>) -> Result<(), Error> {
+    match step {
+        Some(step) if !(step > 0.0 && step.is_finite()) => Err(Error::TimeStep(field, step)),
+        _ => Ok(()),
+    }
+}
 
-        window.swap_buffers();
+/// Checks that the stereo mode can draw to the target, since the offscreen
+/// target used headless has no back buffers for quad buffered stereo.
+fn validate_stereo(info: &AppInfo) -> Result<(), Error> {
+    if info.stereo && info.headless && info.stereo_fallback == StereoMode::QuadBuffer {
+        Err(Error::StereoOffscreen)
+    } else {
+        Ok(())
+    }
+}
+
+fn run_app<T: App, C: Clock>(app: &mut T, info: AppInfo, clock: &mut C) -> Result<(), Error> {
+    try!(validate_time_step("time_step", info.time_step));
+    try!(validate_time_step(
+        "fixed_update_step",
+        info.fixed_update_step
+    ));
+    try!(validate_stereo(&info));
+    let recording = match info.replay_path {
+        Some(ref path) => Some(try!(Recording::load(path).map_err(|e| Error::file(path, e)))),
+        None => None,
+    };
+    let mut recorder = match info.record_path {
+        Some(ref path) => Some(try!(
+            Recorder::create(path).map_err(|e| Error::file(path, e))
+        )),
+        None => None,
+    };
+    // a replay supplies its own frame times
+    let mut replay_clock;
+    let clock: &mut dyn Clock = match recording {
+        Some(ref recording) => {
+            replay_clock = ScriptedClock::new(recording.times());
+            &mut replay_clock
+        }
+        None => clock,
+    };
+
+    // the headless context is released when this returns, after the GL
+    // objects created in it
+    let (mut main_window, _headless_context) = if info.headless {
+        let context = try!(create_headless_context(&info));
+        gl::load_with(|s| context.get_proc_address(s));
+        (None, Some(context))
+    } else {
+        let mut main = try!(open_main_window(&info));
+        gl::load_with(|s| main.window.get_proc_address(s) as *const _);
+        (Some(main), None)
+    };
+
+    let gl_info = GlInfo::query();
+    gl_info.report();
+    gl_info.make_current();
+    let mut info = info;
+    info.major_version = gl_info.major_version;
+    info.minor_version = gl_info.minor_version;
+
+    let mut fullscreen = false;
+    if let Some(ref mut main) = main_window {
+        main.glfw.set_swap_interval(if info.vsync {
+            glfw::SwapInterval::Sync(1)
+        } else {
+            glfw::SwapInterval::None
+        });
+        main.window.set_cursor_mode(cursor_mode(&info, false));
+        fullscreen = main.window.with_window_mode(|mode| match mode {
+            glfw::WindowMode::FullScreen(_) => true,
+            glfw::WindowMode::Windowed => false,
+        });
+    }
+
+    report_unsatisfied(&info, fullscreen);
+
+    if info.debug {
+        if !gl_debug::install(&info.debug_config) {
+            warn!("KHR_debug is not available, checking glGetError instead");
+        }
+    } else if info.debug_config.check_errors {
+        gl_debug::enable_error_checks(info.debug_config.panic_on_error);
+    }
+
+    let mut offscreen = None;
+    let mut ctx = match main_window {
+        Some(ref main) => {
+            let (width, height) = main.window.get_size();
+            let (fb_width, fb_height) = main.window.get_framebuffer_size();
+            let mut info = info.clone();
+            info.window_width = width as u32;
+            info.window_height = height as u32;
+            Context::new(info, gl_info, (fb_width as u32, fb_height as u32))
+        }
+        None => {
+            let target = try!(Offscreen::new(info.window_width, info.window_height));
+            DEFAULT_FRAMEBUFFER.store(target.framebuffer(), Ordering::Relaxed);
+            let size = (target.width(), target.height());
+            offscreen = Some(target);
+            let ctx = Context::new(info.clone(), gl_info, size);
+            // a context without a window starts with an empty viewport
+            ctx.set_viewport();
+            ctx
+        }
+    };
+    ctx.set_fullscreen_state(fullscreen);
+
+    let max_frames = match recording {
+        // a replay stops at the end of the recording
+        Some(ref recording) => {
+            let len = recording.len() as u32;
+            Some(info.max_frames.map_or(len, |n| cmp::min(n, len)))
+        }
+        None => match info.max_frames {
+            Some(n) => Some(n),
+            None if info.headless => Some(1),
+            None => None,
+        },
+    };
+
+    if info.gpu_timer {
+        ctx.stats_mut().enable_gpu_timer();
+    }
+    let mut stats_overlay: Option<StatsOverlay> = None;
+
+    let mut stereo = if info.stereo {
+        let mut quad_buffered = gl::FALSE;
+        unsafe {
+            gl::GetBooleanv(gl::STEREO, &mut quad_buffered);
+        }
+        let mode = if quad_buffered == gl::TRUE && !info.headless {
+            StereoMode::QuadBuffer
+        } else {
+            info.stereo_fallback
+        };
+        match StereoRenderer::new(mode) {
+            Ok(stereo) => Some(stereo),
+            Err(e) => {
+                ctx.stats_mut().free();
+                free_offscreen(&mut offscreen);
+                return Err(e);
+            }
+        }
+    } else {
+        None
+    };
+    ctx.set_stereo_mode(stereo.as_ref().map(|stereo| stereo.mode()));
+
+    if let Err(e) = app.startup(&mut ctx) {
+        if let Some(ref mut stereo) = stereo {
+            stereo.free();
+        }
+        ctx.programs_mut().free();
+        ctx.stats_mut().free();
+        free_offscreen(&mut offscreen);
+        return Err(e);
+    }
+    let mut shared_windows: Vec<SharedWindow> = Vec::new();
+    let mut opened = Vec::new();
+    let mut close_requested = match main_window {
+        Some(ref mut main) => {
+            apply_window_requests(
+                &mut main.glfw,
+                &mut main.window,
+                &mut ctx,
+                &mut main.windowed,
+                &mut opened,
+            );
+            window::open_windows(
+                &mut main.glfw,
+                &main.window,
+                ctx.gl_info(),
+                &mut opened,
+                &mut shared_windows,
+            );
+            main.window.make_current();
+            main.window.should_close()
+        }
+        None => apply_headless_requests(&mut ctx),
+    };
+
+    let mut frame = 0;
+    let mut update_time = 0.0;
+    let mut screenshot_requested = false;
+    let mut screenshot_count = 0;
+    let frames_done = |frame| match max_frames {
+        Some(n) => frame >= n,
+        None => false,
+    };
+
+    while !close_requested && !frames_done(frame) {
+        let time = clock.time();
+        if let Some(ref mut recorder) = recorder {
+            recorder.frame(time);
+        }
+        ctx.stats_mut().begin_frame(frame);
+        reload_programs(app, &mut ctx);
+
+        let alpha = match info.fixed_update_step {
+            Some(step) => {
+                let mut updates = 0;
+                while update_time + step <= time && updates < MAX_UPDATES_PER_FRAME {
+                    update_time += step;
+                    app.update(&mut ctx, update_time);
+                    updates += 1;
+                }
+                if update_time + step <= time {
+                    warn!("Dropping {:.3}s of updates", time - update_time);
+                    update_time = time - (time - update_time) % step;
+                }
+                Some((time - update_time) / step)
+            }
+            None => {
+                app.update(&mut ctx, time);
+                None
+            }
+        };
+        ctx.stats_mut().end_update();
+
+        ctx.stats_mut().begin_render();
+        let stereo_rendered = match stereo {
+            Some(ref mut stereo) => match render_stereo(app, &mut ctx, stereo, time) {
+                Ok(_) => true,
+                Err(e) => {
+                    error!("Failed to create the stereo targets, rendering mono: {}", e);
+                    false
+                }
+            },
+            None => false,
+        };
+        if !stereo_rendered {
+            if let Some(mut failed) = stereo.take() {
+                failed.free();
+                ctx.set_stereo_mode(None);
+            }
+            if let Some(ref offscreen) = offscreen {
+                offscreen.bind();
+            }
+            if ctx.auto_viewport() {
+                ctx.set_viewport();
+            }
+            match alpha {
+                Some(alpha) => app.render_interpolated(&mut ctx, time, alpha),
+                None => app.render(&mut ctx, time),
+            }
+        }
+        ctx.stats_mut().end_render();
+        gl_debug::check_errors("App::render");
+
+        if ctx.stats_overlay() {
+            if stats_overlay.is_none() {
+                match StatsOverlay::new() {
+                    Ok(overlay) => stats_overlay = Some(overlay),
+                    Err(e) => {
+                        error!("Failed to create the stats overlay: {}", e);
+                        ctx.set_stats_overlay(false);
+                    }
+                }
+            }
+            if let Some(ref mut overlay) = stats_overlay {
+                overlay.draw(ctx.stats(), default_framebuffer(), ctx.framebuffer_size());
+            }
+        }
+
+        if screenshot_requested || info.capture_frame == Some(frame) {
+            let (width, height) = ctx.framebuffer_size();
+            if info.capture_frame == Some(frame) {
+                save_capture(&info.capture_path, width, height);
+            }
+            if screenshot_requested {
+                save_capture(
+                    &format!("screenshot_{:04}.png", screenshot_count),
+                    width,
+                    height,
+                );
+                screenshot_count += 1;
+                screenshot_requested = false;
+            }
+        }
 
-        glfw.poll_events();
-        for (_, event) in glfw::flush_messages(&events) {
-            handle_window_event::<T>(app, &mut window, &event);
+        let live_events: Vec<glfw::WindowEvent> = match main_window {
+            Some(ref mut main) => {
+                main.window.swap_buffers();
+                if !shared_windows.is_empty() {
+                    for shared in shared_windows.iter_mut() {
+                        shared.frame(time);
+                    }
+                    main.window.make_current();
+                }
+                main.glfw.poll_events();
+                glfw::flush_messages(&main.events)
+                    .map(|(_, event)| event)
+                    .collect()
+            }
+            None => {
+                unsafe {
+                    gl::Finish();
+                }
+                Vec::new()
+            }
+        };
+        let frame_events = match recording {
+            // live input is dropped so it can't change the replay
+            Some(ref recording) => recording.events(frame).to_vec(),
+            None => live_events,
+        };
+        for event in frame_events {
+            if let Some(ref mut recorder) = recorder {
+                recorder.event(&event);
+            }
+            if let glfw::WindowEvent::Size(w, h) = event {
+                match main_window {
+                    Some(ref mut main) if recording.is_some() => main.window.set_size(w, h),
+                    _ => (),
+                }
+            }
+            if let glfw::WindowEvent::FramebufferSize(w, h) = event {
+                // the offscreen target stands in for the window's framebuffer,
+                // so it follows replayed resizes
+                if let Some(ref mut offscreen) = offscreen {
+                    if w <= 0 || h <= 0 {
+                        continue;
+                    }
+                    if let Err(e) = offscreen.resize(w as u32, h as u32) {
+                        error!(
+                            "Failed to resize the offscreen target to {}x{}: {}",
+                            w, h, e
+                        );
+                        continue;
+                    }
+                }
+            }
+            if let glfw::WindowEvent::Key(key, _, glfw::Action::Press, _) = event {
+                if info.screenshot_key == Some(key) {
+                    screenshot_requested = true;
+                }
+                if info.stats_key == Some(key) {
+                    let visible = ctx.stats_overlay();
+                    ctx.set_stats_overlay(!visible);
+                }
+            }
+            if handle_window_event::<T>(app, &mut ctx, &event) {
+                close_requested = true;
+            }
+        }
+
+        // gamepads aren't recorded, so they're ignored during a replay
+        if recording.is_none() && !info.headless {
+            for connection in ctx.gamepads_mut().poll() {
+                match connection {
+                    Connection::Connected(id) => {
+                        if let Some(pad) = ctx.gamepads().get(id) {
+                            info!("Gamepad connected: {}", pad.name());
+                        }
+                        if recorder.is_some() {
+                            warn!("Gamepad input isn't recorded and won't be replayed");
+                        }
+                        app.on_gamepad_connected(&mut ctx, id);
+                    }
+                    Connection::Disconnected(id) => {
+                        info!("Gamepad disconnected");
+                        app.on_gamepad_disconnected(&mut ctx, id);
+                    }
+                }
+            }
+        }
+        match main_window {
+            Some(ref mut main) => {
+                apply_window_requests(
+                    &mut main.glfw,
+                    &mut main.window,
+                    &mut ctx,
+                    &mut main.windowed,
+                    &mut opened,
+                );
+                if !shared_windows.is_empty() || !opened.is_empty() {
+                    for shared in shared_windows.iter_mut() {
+                        shared.handle_events(&mut main.glfw, &mut opened);
+                    }
+                    let (closed, open): (Vec<_>, Vec<_>) = shared_windows
+                        .into_iter()
+                        .partition(|shared| shared.should_close());
+                    shared_windows = open;
+                    for shared in closed {
+                        shared.close(&mut main.glfw, &main.window);
+                    }
+                    window::open_windows(
+                        &mut main.glfw,
+                        &main.window,
+                        ctx.gl_info(),
+                        &mut opened,
+                        &mut shared_windows,
+                    );
+                    main.window.make_current();
+                }
+                if main.window.should_close() {
+                    close_requested = true;
+                }
+            }
+            None => {
+                if apply_headless_requests(&mut ctx) {
+                    close_requested = true;
+                }
+            }
+        }
+
+        ctx.stats_mut().end_frame();
+        frame += 1;
+    }
+
+    if let Some(ref mut main) = main_window {
+        for shared in shared_windows {
+            shared.close(&mut main.glfw, &main.window);
+        }
+    }
+    app.shutdown(&mut ctx);
+    ctx.programs_mut().free();
+
+    if let Some(recorder) = recorder {
+        let path = recorder.path().to_string();
+        match recorder.finish() {
+            Ok(_) => info!("Saved recording of {} frames to '{}'", frame, path),
+            Err(e) => error!("Error saving recording '{}': {}", path, e),
         }
     }
 
-    app.shutdown();
+    if frame > 0 {
+        info!(
+            "Frame times over the last {} frames:",
+            ctx.stats().history().len()
+        );
+        info!("  CPU {}", ctx.stats().cpu_summary());
+        if ctx.stats().has_gpu_times() {
+            info!("  GPU {}", ctx.stats().gpu_summary());
+        }
+    }
+    if let Some(ref mut stereo) = stereo {
+        stereo.free();
+    }
+    if let Some(ref mut overlay) = stats_overlay {
+        overlay.free();
+    }
+    ctx.stats_mut().free();
+    free_offscreen(&mut offscreen);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64;
+
+    struct KeyApp {
+        info: AppInfo,
+        keys: Vec<(Key, Action)>,
+    }
+
+    impl App for KeyApp {
+        fn get_app_info(&self) -> &AppInfo {
+            &self.info
+        }
+
+        fn on_key(&mut self, _: &mut Context, key: Key, action: Action, _: Modifiers) {
+            self.keys.push((key, action));
+        }
+    }
+
+    fn context(info: &AppInfo) -> Context {
+        let gl_info = GlInfo {
+            major_version: 4,
+            minor_version: 5,
+            version: String::new(),
+            shading_language_version: String::new(),
+            vendor: String::new(),
+            renderer: String::new(),
+            extensions: Vec::new(),
+        };
+        Context::new(info.clone(), gl_info, (800, 600))
+    }
+
+    #[test]
+    fn exit_key_is_not_passed_to_the_app() {
+        let mut app = KeyApp {
+            info: AppInfo::default(),
+            keys: Vec::new(),
+        };
+        let mut ctx = context(&app.info);
+        let key = |key, action| glfw::WindowEvent::Key(key, 0, action, Modifiers::empty());
+
+        assert!(handle_window_event(
+            &mut app,
+            &mut ctx,
+            &key(Key::Escape, Action::Press)
+        ));
+        for &action in [Action::Repeat, Action::Release].iter() {
+            assert!(!handle_window_event(
+                &mut app,
+                &mut ctx,
+                &key(Key::Escape, action)
+            ));
+        }
+        assert!(app.keys.is_empty());
+
+        assert!(!handle_window_event(
+            &mut app,
+            &mut ctx,
+            &key(Key::A, Action::Press)
+        ));
+        assert_eq!(app.keys, [(Key::A, Action::Press)]);
+    }
+
+    #[test]
+    fn context_versions_fall_back_to_the_minimum() {
+        let mut info = AppInfo::default();
+        info.major_version = 4;
+        info.minor_version = 1;
+        info.min_major_version = 3;
+        info.min_minor_version = 2;
+        assert_eq!(context_versions(&info), [(4, 1), (4, 0), (3, 3), (3, 2)]);
+
+        info.min_minor_version = 3;
+        assert_eq!(context_versions(&info), [(4, 1), (4, 0), (3, 3)]);
+
+        // a request for the minimum has nothing to fall back to
+        info.major_version = 3;
+        info.minor_version = 2;
+        info.min_minor_version = 2;
+        assert_eq!(context_versions(&info), [(3, 2)]);
+    }
+
+    #[test]
+    fn time_steps_must_be_positive() {
+        assert!(validate_time_step("time_step", None).is_ok());
+        assert!(validate_time_step("time_step", Some(1.0 / 60.0)).is_ok());
+        for &step in [0.0, -0.5, f64::NAN, f64::INFINITY].iter() {
+            match validate_time_step("time_step", Some(step)) {
+                Err(Error::TimeStep("time_step", _)) => (),
+                _ => panic!("time step {} was accepted", step),
+            }
+        }
+    }
+
+    #[test]
+    fn quad_buffered_stereo_is_rejected_headless() {
+        let mut info = AppInfo::default();
+        info.stereo = true;
+        info.stereo_fallback = StereoMode::QuadBuffer;
+        assert!(validate_stereo(&info).is_ok());
+
+        info.headless = true;
+        match validate_stereo(&info) {
+            Err(Error::StereoOffscreen) => (),
+            _ => panic!("quad buffered stereo was accepted headless"),
+        }
+
+        info.stereo_fallback = StereoMode::Anaglyph;
+        assert!(validate_stereo(&info).is_ok());
+    }
 }
@@ -0,0 +1,590 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Orbit and fly camera controllers. Forward input events from the `App`
+//! callbacks to the camera and read `view_matrix` and `proj_matrix` when
+//! rendering.
+//!
+//! ```ignore
+//! fn on_mouse_move(&mut self, _: &mut sb6::Context, x: f64, y: f64) {
+//!     self.camera.on_mouse_move(x, y);
+//! }
+//! fn on_resize(&mut self, _: &mut sb6::Context, w: isize, h: isize) {
+//!     self.camera.on_resize(w, h);
+//! }
+//! fn on_framebuffer_resize(&mut self, _: &mut sb6::Context, w: isize, h: isize) {
+//!     self.camera.on_framebuffer_resize(w, h);
+//! }
+//! ```
+
+use app::{Action, Key, Modifiers, MouseButton};
+use gamepad::{Gamepad, GamepadAxis, GamepadButton};
+use vmath;
+use vmath::{vec3, Mat4, Vec3};
+
+/// The key that returns a camera to where it started, unless changed with
+/// `set_reset_key`.
+pub const DEFAULT_RESET_KEY: Key = Key::Home;
+
+/// The gamepad button that resets a camera.
+pub const RESET_BUTTON: GamepadButton = GamepadButton::Back;
+
+/// Degrees per second turned with a stick pushed all the way.
+const STICK_TURN_RATE: f32 = 120.0;
+
+/// The viewport height assumed until `on_resize` is called, the default
+/// window height.
+const DEFAULT_VIEWPORT_HEIGHT: f32 = 600.0;
+
+/// A perspective projection that tracks the framebuffer aspect ratio.
+#[derive(Clone, Copy, Debug)]
+pub struct Projection {
+    /// Vertical field of view in degrees.
+    pub fovy: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Projection {
+    pub fn new(fovy: f32, near: f32, far: f32) -> Projection {
+        Projection {
+            fovy,
+            aspect: 1.0,
+            near,
+            far,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        vmath::perspective(self.fovy, self.aspect, self.near, self.far)
+    }
+
+    pub fn set_framebuffer_size(&mut self, width: isize, height: isize) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Projection {
+        Projection::new(50.0, 0.1, 1000.0)
+    }
+}
+
+/// Input handling shared by the camera controllers, with the same arguments
+/// as the matching `App` callbacks.
+pub trait Camera {
+    fn view_matrix(&self) -> Mat4;
+    fn projection(&self) -> &Projection;
+    fn projection_mut(&mut self) -> &mut Projection;
+    /// Returns the camera to its initial position and orientation.
+    fn reset(&mut self);
+
+    fn proj_matrix(&self) -> Mat4 {
+        self.projection().matrix()
+    }
+    /// Moves the camera for keys held down, `dt` seconds since the last
+    /// update.
+    fn update(&mut self, _: f64) {}
+    fn on_key(&mut self, _: Key, _: Action, _: Modifiers) {}
+    fn on_mouse_button(&mut self, _: MouseButton, _: Action, _: Modifiers) {}
+    fn on_mouse_move(&mut self, _: f64, _: f64) {}
+    fn on_mouse_wheel(&mut self, _: f64, _: f64) {}
+    /// Applies gamepad input for a frame `dt` seconds long, e.g. with the
+    /// pad from `Context::gamepads().first()`.
+    fn on_gamepad(&mut self, _: &Gamepad, _: f64) {}
+    /// The window was resized, in screen coordinates like the cursor.
+    fn on_resize(&mut self, _: isize, _: isize) {}
+    fn on_framebuffer_resize(&mut self, width: isize, height: isize) {
+        self.projection_mut().set_framebuffer_size(width, height);
+    }
+}
+
+/// Tracks the cursor so controllers can turn positions into deltas.
+#[derive(Clone, Copy, Debug, Default)]
+struct CursorTracker {
+    last: Option<(f64, f64)>,
+}
+
+impl CursorTracker {
+    fn delta(&mut self, x: f64, y: f64) -> (f32, f32) {
+        let delta = match self.last {
+            Some((last_x, last_y)) => ((x - last_x) as f32, (y - last_y) as f32),
+            None => (0.0, 0.0),
+        };
+        self.last = Some((x, y));
+        delta
+    }
+}
+
+fn clamp_pitch(pitch: f32) -> f32 {
+    pitch.clamp(-89.0, 89.0)
+}
+
+/// Direction from yaw and pitch in degrees. Yaw 0 faces down -z and
+/// increases to the right, pitch increases upwards.
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    let (sin_yaw, cos_yaw) = yaw.to_radians().sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.to_radians().sin_cos();
+    vec3(sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct OrbitState {
+    target: Vec3,
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+/// Circles a target point. Drag with the left button to rotate, with the
+/// middle button or shift and left to pan, and use the wheel to zoom.
+pub struct OrbitCamera {
+    state: OrbitState,
+    initial: OrbitState,
+    projection: Projection,
+    reset_key: Option<Key>,
+    cursor: CursorTracker,
+    rotating: bool,
+    panning: bool,
+    viewport_height: f32,
+    /// Degrees per pixel dragged.
+    pub rotate_speed: f32,
+    /// Fraction of the distance moved per wheel step.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+}
+
+impl OrbitCamera {
+    /// Creates a camera `distance` units from `target`, rotated `yaw` degrees
+    /// around the y axis and raised `pitch` degrees. With yaw and pitch at 0
+    /// the camera looks down the -z axis.
+    pub fn new(target: Vec3, distance: f32, yaw: f32, pitch: f32) -> OrbitCamera {
+        let state = OrbitState {
+            target,
+            distance,
+            yaw,
+            pitch: clamp_pitch(pitch),
+        };
+        OrbitCamera {
+            state,
+            initial: state,
+            projection: Projection::default(),
+            reset_key: Some(DEFAULT_RESET_KEY),
+            cursor: CursorTracker::default(),
+            rotating: false,
+            panning: false,
+            viewport_height: DEFAULT_VIEWPORT_HEIGHT,
+            rotate_speed: 0.3,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+        }
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> OrbitCamera {
+        self.projection = projection;
+        self
+    }
+
+    pub fn set_reset_key(&mut self, key: Option<Key>) {
+        self.reset_key = key;
+    }
+
+    pub fn target(&self) -> Vec3 {
+        self.state.target
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.state.distance
+    }
+
+    /// The camera position.
+    pub fn eye(&self) -> Vec3 {
+        self.state.target - direction(self.state.yaw, self.state.pitch) * self.state.distance
+    }
+}
+
+impl Camera for OrbitCamera {
+    fn view_matrix(&self) -> Mat4 {
+        vmath::look_at(self.eye(), self.state.target, vec3(0.0, 1.0, 0.0))
+    }
+
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
+    fn reset(&mut self) {
+        self.state = self.initial;
+    }
+
+    fn on_key(&mut self, key: Key, action: Action, _: Modifiers) {
+        if action == Action::Press && self.reset_key == Some(key) {
+            self.reset();
+        }
+    }
+
+    fn on_mouse_button(&mut self, button: MouseButton, action: Action, mods: Modifiers) {
+        let pressed = action != Action::Release;
+        match button {
+            MouseButton::Button1 if mods.contains(Modifiers::Shift) || self.panning => {
+                self.panning = pressed
+            }
+            MouseButton::Button1 => self.rotating = pressed,
+            MouseButton::Button3 => self.panning = pressed,
+            _ => (),
+        }
+    }
+
+    fn on_mouse_move(&mut self, x: f64, y: f64) {
+        let (dx, dy) = self.cursor.delta(x, y);
+        if self.rotating {
+            self.state.yaw -= dx * self.rotate_speed;
+            self.state.pitch = clamp_pitch(self.state.pitch + dy * self.rotate_speed);
+        } else if self.panning {
+            let forward = direction(self.state.yaw, self.state.pitch);
+            let right = forward.cross(&vec3(0.0, 1.0, 0.0)).normalize();
+            let up = right.cross(&forward);
+            // move the target so it follows the cursor at the target distance
+            let scale = 2.0 * self.state.distance * (0.5 * self.projection.fovy).to_radians().tan()
+                / self.viewport_height;
+            self.state.target = self.state.target - right * (dx * scale) + up * (dy * scale);
+        }
+    }
+
+    fn on_resize(&mut self, _: isize, height: isize) {
+        if height > 0 {
+            self.viewport_height = height as f32;
+        }
+    }
+
+    fn on_mouse_wheel(&mut self, _: f64, y: f64) {
+        let factor = (1.0 - self.zoom_speed).powf(y as f32);
+        self.state.distance = (self.state.distance * factor).max(self.min_distance);
+    }
+
+    /// The right stick rotates, the right and left triggers zoom in and out.
+    fn on_gamepad(&mut self, pad: &Gamepad, dt: f64) {
+        if pad.pressed(RESET_BUTTON) {
+            self.reset();
+        }
+        let dt = dt as f32;
+        let turn = STICK_TURN_RATE * dt;
+        self.state.yaw -= pad.axis(GamepadAxis::RightX) * turn;
+        self.state.pitch = clamp_pitch(self.state.pitch + pad.axis(GamepadAxis::RightY) * turn);
+        let zoom = pad.axis(GamepadAxis::RightTrigger) - pad.axis(GamepadAxis::LeftTrigger);
+        if zoom != 0.0 {
+            // about a wheel step every tenth of a second
+            self.on_mouse_wheel(0.0, f64::from(zoom * dt * 10.0));
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct FlyState {
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+}
+
+/// A first person camera. WASD moves, Q and E move down and up, shift
+/// moves faster, and dragging with the right button looks around.
+pub struct FlyCamera {
+    state: FlyState,
+    initial: FlyState,
+    projection: Projection,
+    reset_key: Option<Key>,
+    cursor: CursorTracker,
+    looking: bool,
+    // forward, back, left, right, down, up
+    moving: [bool; 6],
+    fast: bool,
+    /// Units per second.
+    pub move_speed: f32,
+    /// Speed multiplier while shift is held.
+    pub fast_multiplier: f32,
+    /// Degrees per pixel dragged.
+    pub look_speed: f32,
+}
+
+impl FlyCamera {
+    /// Creates a camera at `position` facing `yaw` degrees right of the -z
+    /// axis and `pitch` degrees up.
+    pub fn new(position: Vec3, yaw: f32, pitch: f32) -> FlyCamera {
+        let state = FlyState {
+            position,
+            yaw,
+            pitch: clamp_pitch(pitch),
+        };
+        FlyCamera {
+            state,
+            initial: state,
+            projection: Projection::default(),
+            reset_key: Some(DEFAULT_RESET_KEY),
+            cursor: CursorTracker::default(),
+            looking: false,
+            moving: [false; 6],
+            fast: false,
+            move_speed: 5.0,
+            fast_multiplier: 4.0,
+            look_speed: 0.2,
+        }
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> FlyCamera {
+        self.projection = projection;
+        self
+    }
+
+    pub fn set_reset_key(&mut self, key: Option<Key>) {
+        self.reset_key = key;
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.state.position
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        direction(self.state.yaw, self.state.pitch)
+    }
+}
+
+impl Camera for FlyCamera {
+    fn view_matrix(&self) -> Mat4 {
+        let position = self.state.position;
+        vmath::look_at(position, position + self.forward(), vec3(0.0, 1.0, 0.0))
+    }
+
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
+    fn reset(&mut self) {
+        self.state = self.initial;
+    }
+
+    fn update(&mut self, dt: f64) {
+        let forward = self.forward();
+        let right = forward.cross(&vec3(0.0, 1.0, 0.0)).normalize();
+        let up = vec3(0.0, 1.0, 0.0);
+        let directions = [forward, forward * -1.0, right * -1.0, right, up * -1.0, up];
+        let mut velocity = Vec3::zero();
+        for (&moving, &direction) in self.moving.iter().zip(directions.iter()) {
+            if moving {
+                velocity = velocity + direction;
+            }
+        }
+        // keep diagonal movement as fast as straight movement
+        let length = velocity.length();
+        if length > 0.0 {
+            velocity = velocity * (1.0 / length);
+        }
+        let speed = if self.fast {
+            self.move_speed * self.fast_multiplier
+        } else {
+            self.move_speed
+        };
+        self.state.position = self.state.position + velocity * (speed * dt as f32);
+    }
+
+    fn on_key(&mut self, key: Key, action: Action, _: Modifiers) {
+        if action == Action::Repeat {
+            return;
+        }
+        let pressed = action == Action::Press;
+        match key {
+            Key::W => self.moving[0] = pressed,
+            Key::S => self.moving[1] = pressed,
+            Key::A => self.moving[2] = pressed,
+            Key::D => self.moving[3] = pressed,
+            Key::Q => self.moving[4] = pressed,
+            Key::E => self.moving[5] = pressed,
+            Key::LeftShift | Key::RightShift => self.fast = pressed,
+            _ if pressed && self.reset_key == Some(key) => self.reset(),
+            _ => (),
+        }
+    }
+
+    fn on_mouse_button(&mut self, button: MouseButton, action: Action, _: Modifiers) {
+        if button == MouseButton::Button2 {
+            self.looking = action != Action::Release;
+        }
+    }
+
+    /// The left stick moves, the right stick looks, the bumpers move down
+    /// and up and holding the left stick in moves faster.
+    fn on_gamepad(&mut self, pad: &Gamepad, dt: f64) {
+        if pad.pressed(RESET_BUTTON) {
+            self.reset();
+        }
+        let dt = dt as f32;
+        let turn = STICK_TURN_RATE * dt;
+        self.state.yaw += pad.axis(GamepadAxis::RightX) * turn;
+        self.state.pitch = clamp_pitch(self.state.pitch - pad.axis(GamepadAxis::RightY) * turn);
+
+        let forward = self.forward();
+        let right = forward.cross(&vec3(0.0, 1.0, 0.0)).normalize();
+        let mut up = 0.0;
+        if pad.is_down(GamepadButton::LeftBumper) {
+            up -= 1.0;
+        }
+        if pad.is_down(GamepadButton::RightBumper) {
+            up += 1.0;
+        }
+        let mut velocity = right * pad.axis(GamepadAxis::LeftX)
+            - forward * pad.axis(GamepadAxis::LeftY)
+            + vec3(0.0, up, 0.0);
+        // sticks can reach past 1 on the diagonals
+        let length = velocity.length();
+        if length > 1.0 {
+            velocity = velocity * (1.0 / length);
+        }
+        let speed = if pad.is_down(GamepadButton::LeftThumb) {
+            self.move_speed * self.fast_multiplier
+        } else {
+            self.move_speed
+        };
+        self.state.position = self.state.position + velocity * (speed * dt);
+    }
+
+    fn on_mouse_move(&mut self, x: f64, y: f64) {
+        let (dx, dy) = self.cursor.delta(x, y);
+        if self.looking {
+            self.state.yaw += dx * self.look_speed;
+            self.state.pitch = clamp_pitch(self.state.pitch - dy * self.look_speed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Camera, FlyCamera, OrbitCamera};
+    use app::{Action, Key, Modifiers, MouseButton};
+    use vmath::{vec3, Vec3};
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn orbit_eye_position() {
+        let target = vec3(1.0, 2.0, 3.0);
+        // yaw and pitch 0 look down -z, so the eye is behind the target on +z
+        assert_near(
+            OrbitCamera::new(target, 5.0, 0.0, 0.0).eye(),
+            vec3(1.0, 2.0, 8.0),
+        );
+        // yawing right puts the eye on the left
+        assert_near(
+            OrbitCamera::new(target, 5.0, 90.0, 0.0).eye(),
+            vec3(-4.0, 2.0, 3.0),
+        );
+        // pitching up puts the eye below
+        assert_near(
+            OrbitCamera::new(target, 5.0, 0.0, 30.0).eye(),
+            vec3(1.0, 2.0 - 2.5, 3.0 + 5.0 * 30f32.to_radians().cos()),
+        );
+    }
+
+    #[test]
+    fn orbit_pitch_is_clamped() {
+        let camera = OrbitCamera::new(Vec3::zero(), 1.0, 0.0, 120.0);
+        assert_near(
+            camera.eye(),
+            OrbitCamera::new(Vec3::zero(), 1.0, 0.0, 89.0).eye(),
+        );
+
+        let mut camera = OrbitCamera::new(Vec3::zero(), 1.0, 0.0, 0.0);
+        camera.rotate_speed = 1.0;
+        camera.on_mouse_button(MouseButton::Button1, Action::Press, Modifiers::empty());
+        camera.on_mouse_move(0.0, 0.0);
+        camera.on_mouse_move(0.0, -500.0);
+        assert_near(
+            camera.eye(),
+            OrbitCamera::new(Vec3::zero(), 1.0, 0.0, -89.0).eye(),
+        );
+    }
+
+    #[test]
+    fn orbit_zoom_limits() {
+        let mut camera = OrbitCamera::new(Vec3::zero(), 2.0, 0.0, 0.0);
+        camera.min_distance = 0.5;
+        camera.on_mouse_wheel(0.0, 1.0);
+        assert!((camera.distance() - 1.8).abs() < 1e-5);
+        camera.on_mouse_wheel(0.0, 100.0);
+        assert_eq!(camera.distance(), 0.5);
+        camera.on_mouse_wheel(0.0, -1.0);
+        assert!(camera.distance() > 0.5);
+    }
+
+    #[test]
+    fn orbit_pan_follows_the_cursor() {
+        let mut camera = OrbitCamera::new(Vec3::zero(), 1.0, 0.0, 0.0);
+        camera.projection_mut().fovy = 90.0;
+        camera.on_resize(800, 400);
+        camera.on_mouse_button(MouseButton::Button3, Action::Press, Modifiers::empty());
+        camera.on_mouse_move(0.0, 0.0);
+        // the view is 2 units tall at the target, so half the height is 1
+        camera.on_mouse_move(0.0, 200.0);
+        assert_near(camera.target(), vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn orbit_reset() {
+        let mut camera = OrbitCamera::new(vec3(1.0, 0.0, 0.0), 2.0, 10.0, 20.0);
+        let eye = camera.eye();
+        camera.on_mouse_wheel(0.0, 3.0);
+        camera.on_mouse_button(MouseButton::Button1, Action::Press, Modifiers::empty());
+        camera.on_mouse_move(0.0, 0.0);
+        camera.on_mouse_move(50.0, 50.0);
+        camera.on_key(Key::Home, Action::Press, Modifiers::empty());
+        assert_near(camera.eye(), eye);
+        assert_near(camera.target(), vec3(1.0, 0.0, 0.0));
+        assert_eq!(camera.distance(), 2.0);
+    }
+
+    #[test]
+    fn fly_diagonals_are_not_faster() {
+        let mut camera = FlyCamera::new(Vec3::zero(), 0.0, 0.0);
+        camera.on_key(Key::W, Action::Press, Modifiers::empty());
+        camera.on_key(Key::D, Action::Press, Modifiers::empty());
+        camera.update(1.0);
+        assert!((camera.position().length() - camera.move_speed).abs() < 1e-4);
+
+        camera.reset();
+        camera.on_key(Key::D, Action::Release, Modifiers::empty());
+        camera.on_key(Key::S, Action::Press, Modifiers::empty());
+        camera.update(1.0);
+        assert_near(camera.position(), Vec3::zero());
+    }
+}
@@ -0,0 +1,612 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+
+use app::default_framebuffer;
+use gl::types::*;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    UnknownExtension(String),
+    FormatError(String),
+    IoError(io::Error),
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> CaptureError {
+        CaptureError::IoError(e)
+    }
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CaptureError::UnknownExtension(ref path) => {
+                write!(fmt, "Unknown image file extension for '{}'", path)
+            }
+            CaptureError::FormatError(ref e) => write!(fmt, "Invalid image: {}", e),
+            CaptureError::IoError(ref e) => e.fmt(fmt),
+        }
+    }
+}
+
+/// An 8-bit RGBA image with rows stored from top to bottom.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// A 32-bit float RGBA image with rows stored from top to bottom.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FloatImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<f32>,
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl FloatImage {
+    /// Converts to 8 bits per channel, clamping to [0, 1]. If `encode_srgb` is
+    /// set the color channels are treated as linear and sRGB encoded.
+    pub fn to_image(&self, encode_srgb: bool) -> Image {
+        let pixels = self
+            .pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| {
+                let c = c.clamp(0.0, 1.0);
+                let c = if encode_srgb && i % 4 != 3 {
+                    linear_to_srgb(c)
+                } else {
+                    c
+                };
+                (c * 255.0 + 0.5) as u8
+            })
+            .collect();
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+impl Image {
+    /// Saves the image, choosing PNG or PPM from the file extension.
+    pub fn save(&self, filename: &str) -> Result<(), CaptureError> {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension.as_deref() {
+            Some("png") => self.save_png(filename),
+            Some("ppm") => self.save_ppm(filename),
+            _ => Err(CaptureError::UnknownExtension(String::from(filename))),
+        }
+    }
+
+    /// Loads a binary (P6) PPM with 8 bits per channel, such as those written
+    /// by `save_ppm`. Alpha is set to 255.
+    pub fn load_ppm(filename: &str) -> Result<Image, CaptureError> {
+        let mut bytes = Vec::new();
+        try!(try!(File::open(filename)).read_to_end(&mut bytes));
+        Image::parse_ppm(&bytes)
+    }
+
+    fn parse_ppm(bytes: &[u8]) -> Result<Image, CaptureError> {
+        // the header is four whitespace separated tokens, with '#' comments
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        while tokens.len() < 4 {
+            while pos < bytes.len() && (bytes[pos] as char).is_whitespace() {
+                pos += 1;
+            }
+            if pos < bytes.len() && bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            let start = pos;
+            while pos < bytes.len() && !(bytes[pos] as char).is_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(CaptureError::FormatError(String::from("truncated header")));
+            }
+            tokens.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+        }
+        // a single whitespace character separates the header from the data
+        pos += 1;
+
+        if tokens[0] != "P6" || tokens[3] != "255" {
+            return Err(CaptureError::FormatError(String::from(
+                "only 8-bit binary PPM is supported",
+            )));
+        }
+        let (width, height) = match (tokens[1].parse::<u32>(), tokens[2].parse::<u32>()) {
+            (Ok(w), Ok(h)) => (w, h),
+            _ => return Err(CaptureError::FormatError(String::from("bad dimensions"))),
+        };
+
+        let rgb_len = width as usize * height as usize * 3;
+        if pos > bytes.len() || bytes.len() - pos < rgb_len {
+            return Err(CaptureError::FormatError(String::from("truncated data")));
+        }
+        let pixels = bytes[pos..pos + rgb_len]
+            .chunks(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255])
+            .collect();
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn save_png(&self, filename: &str) -> Result<(), CaptureError> {
+        let mut file = BufWriter::new(try!(File::create(filename)));
+        try!(self.write_png(&mut file));
+        Ok(())
+    }
+
+    pub fn save_ppm(&self, filename: &str) -> Result<(), CaptureError> {
+        let mut file = BufWriter::new(try!(File::create(filename)));
+        try!(self.write_ppm(&mut file));
+        Ok(())
+    }
+
+    /// Writes a binary (P6) PPM. PPM has no alpha channel so it is dropped.
+    pub fn write_ppm<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write!(w, "P6\n{} {}\n255\n", self.width, self.height));
+        let rgb: Vec<u8> = self
+            .pixels
+            .chunks(4)
+            .flat_map(|p| p[..3].iter().cloned())
+            .collect();
+        w.write_all(&rgb)
+    }
+
+    /// Writes an uncompressed RGBA PNG. The image data is stored in zlib
+    /// "stored" blocks, which keeps the encoder trivial at the cost of size.
+    pub fn write_png<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+        try!(w.write_all(&SIGNATURE));
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&be_u32(self.width));
+        ihdr.extend_from_slice(&be_u32(self.height));
+        // 8 bit depth, color type 6 (RGBA), deflate, adaptive filtering, no interlace
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        try!(write_png_chunk(w, b"IHDR", &ihdr));
+
+        // each scanline is prefixed with filter type 0 (none)
+        let stride = self.width as usize * 4;
+        let mut raw = Vec::with_capacity((stride + 1) * self.height as usize);
+        for row in self.pixels.chunks(stride) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut zlib = Vec::with_capacity(raw.len() + raw.len() / MAX_STORED_BLOCK * 5 + 16);
+        zlib.extend_from_slice(&[0x78, 0x01]);
+        let mut blocks = raw.chunks(MAX_STORED_BLOCK).peekable();
+        if blocks.peek().is_none() {
+            zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+        }
+        while let Some(block) = blocks.next() {
+            let last = if blocks.peek().is_none() { 1 } else { 0 };
+            let len = block.len() as u16;
+            zlib.push(last);
+            zlib.extend_from_slice(&[len as u8, (len >> 8) as u8]);
+            zlib.extend_from_slice(&[!len as u8, (!len >> 8) as u8]);
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&be_u32(adler32(&raw)));
+        try!(write_png_chunk(w, b"IDAT", &zlib));
+
+        write_png_chunk(w, b"IEND", &[])
+    }
+}
+
+fn be_u32(v: u32) -> [u8; 4] {
+    [(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a = 1u32;
+    let mut b = 0u32;
+    // 5552 is the largest block that can be summed without overflowing u32
+    for block in data.chunks(5552) {
+        for &byte in block {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for chunk in chunks {
+        for &byte in chunk.iter() {
+            crc ^= u32::from(byte);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+    }
+    !crc
+}
+
+fn write_png_chunk<W: Write>(w: &mut W, chunk_type: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    try!(w.write_all(&be_u32(data.len() as u32)));
+    try!(w.write_all(chunk_type));
+    try!(w.write_all(data));
+    w.write_all(&be_u32(crc32(&[chunk_type, data])))
+}
+
+/// Saved pixel pack and read state, restored when dropped so a capture does
+/// not disturb the app's own GL state.
+struct ReadState {
+    read_framebuffer: GLint,
+    read_buffer: GLint,
+    pack_buffer: GLint,
+    pack_alignment: GLint,
+    pack_row_length: GLint,
+    framebuffer_srgb: GLboolean,
+}
+
+impl ReadState {
+    fn save() -> ReadState {
+        let mut state = ReadState {
+            read_framebuffer: 0,
+            read_buffer: 0,
+            pack_buffer: 0,
+            pack_alignment: 4,
+            pack_row_length: 0,
+            framebuffer_srgb: gl::FALSE,
+        };
+        unsafe {
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut state.read_framebuffer);
+            gl::GetIntegerv(gl::READ_BUFFER, &mut state.read_buffer);
+            gl::GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut state.pack_buffer);
+            gl::GetIntegerv(gl::PACK_ALIGNMENT, &mut state.pack_alignment);
+            gl::GetIntegerv(gl::PACK_ROW_LENGTH, &mut state.pack_row_length);
+            state.framebuffer_srgb = gl::IsEnabled(gl::FRAMEBUFFER_SRGB);
+        }
+        state
+    }
+}
+
+impl Drop for ReadState {
+    fn drop(&mut self) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.read_framebuffer as GLuint);
+            gl::ReadBuffer(self.read_buffer as GLenum);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pack_buffer as GLuint);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, self.pack_alignment);
+            gl::PixelStorei(gl::PACK_ROW_LENGTH, self.pack_row_length);
+            if self.framebuffer_srgb == gl::TRUE {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
+    }
+}
+
+/// Binds `framebuffer` for reading from `attachment` and returns whether the
+/// attachment stores floating point values.
+fn bind_for_read(framebuffer: GLuint, attachment: GLenum) -> bool {
+    // the default framebuffer names its color buffers differently when
+    // querying attachment parameters
+    let query_attachment = match attachment {
+        gl::BACK => gl::BACK_LEFT,
+        gl::FRONT => gl::FRONT_LEFT,
+        a => a,
+    };
+    let mut component_type = 0;
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+        gl::ReadBuffer(attachment);
+        gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::PixelStorei(gl::PACK_ROW_LENGTH, 0);
+        // read back sRGB attachments as stored rather than linearized
+        gl::Disable(gl::FRAMEBUFFER_SRGB);
+        gl::GetFramebufferAttachmentParameteriv(
+            gl::READ_FRAMEBUFFER,
+            query_attachment,
+            gl::FRAMEBUFFER_ATTACHMENT_COMPONENT_TYPE,
+            &mut component_type,
+        );
+    }
+    component_type as GLenum == gl::FLOAT
+}
+
+fn flip_rows<T: Copy>(pixels: &mut Vec<T>, stride: usize) {
+    let rows: Vec<&[T]> = pixels.chunks(stride).rev().collect();
+    *pixels = rows.concat();
+}
+
+/// Reads back a float RGBA copy of `attachment` of `framebuffer`. Use `gl::BACK`
+/// as the attachment for the window's default framebuffer.
+pub fn read_framebuffer_float(
+    framebuffer: GLuint,
+    attachment: GLenum,
+    width: u32,
+    height: u32,
+) -> FloatImage {
+    let _state = ReadState::save();
+    bind_for_read(framebuffer, attachment);
+    let stride = width as usize * 4;
+    let mut pixels = vec![0.0f32; stride * height as usize];
+    unsafe {
+        gl::ReadPixels(
+            0,
+            0,
+            width as GLsizei,
+            height as GLsizei,
+            gl::RGBA,
+            gl::FLOAT,
+            pixels.as_mut_ptr() as *mut _,
+        );
+    }
+    flip_rows(&mut pixels, stride);
+    FloatImage {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// Reads back an 8-bit RGBA copy of `attachment` of `framebuffer`. Float
+/// attachments are clamped to [0, 1] and sRGB attachments are returned as
+/// stored. Use `gl::BACK` as the attachment for the window's default
+/// framebuffer.
+pub fn read_framebuffer(framebuffer: GLuint, attachment: GLenum, width: u32, height: u32) -> Image {
+    let _state = ReadState::save();
+    if bind_for_read(framebuffer, attachment) {
+        return read_framebuffer_float(framebuffer, attachment, width, height).to_image(false);
+    }
+    let stride = width as usize * 4;
+    let mut pixels = vec![0u8; stride * height as usize];
+    unsafe {
+        gl::ReadPixels(
+            0,
+            0,
+            width as GLsizei,
+            height as GLsizei,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut _,
+        );
+    }
+    flip_rows(&mut pixels, stride);
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// Reads back whatever was rendered to `sb6::default_framebuffer()`, which is
+/// the window's back buffer or the offscreen target when running headless.
+pub fn read_default_framebuffer(width: u32, height: u32) -> Image {
+    let framebuffer = default_framebuffer();
+    let attachment = if framebuffer == 0 {
+        gl::BACK
+    } else {
+        gl::COLOR_ATTACHMENT0
+    };
+    read_framebuffer(framebuffer, attachment, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image(width: u32, height: u32) -> Image {
+        let pixels = (0..width * height * 4)
+            .map(|i| if i % 4 == 3 { 255 } else { (i * 37) as u8 })
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn be_u32_at(bytes: &[u8], pos: usize) -> u32 {
+        (u32::from(bytes[pos]) << 24)
+            | (u32::from(bytes[pos + 1]) << 16)
+            | (u32::from(bytes[pos + 2]) << 8)
+            | u32::from(bytes[pos + 3])
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let image = test_image(5, 3);
+        let mut bytes = Vec::new();
+        image.write_ppm(&mut bytes).unwrap();
+        assert_eq!(&bytes[..11], b"P6\n5 3\n255\n");
+        assert_eq!(Image::parse_ppm(&bytes).unwrap(), image);
+    }
+
+    #[test]
+    fn ppm_round_trip_file() {
+        let image = test_image(2, 2);
+        let path = std::env::temp_dir().join(format!("sb6_capture_{}.ppm", std::process::id()));
+        let path = path.to_str().unwrap();
+        image.save(path).unwrap();
+        let loaded = Image::load_ppm(path);
+        let _ = std::fs::remove_file(path);
+        assert_eq!(loaded.unwrap(), image);
+    }
+
+    #[test]
+    fn ppm_header_comments() {
+        let bytes = b"P6\n# a comment\n1 1\n255\n\x01\x02\x03";
+        assert_eq!(Image::parse_ppm(bytes).unwrap().pixels, vec![1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn ppm_rejects_bad_input() {
+        let bad: [&[u8]; 4] = [
+            b"P6\n2 2\n255\n\x00\x00\x00",
+            b"P3\n1 1\n255\n1 2 3",
+            b"P6\n1 1\n65535\n\x00\x00\x00\x00\x00\x00",
+            b"P6\n1",
+        ];
+        for bytes in bad.iter() {
+            match Image::parse_ppm(bytes) {
+                Err(CaptureError::FormatError(_)) => (),
+                r => panic!("expected a format error, got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(&[b"IEND"]), 0xAE42_6082);
+        assert_eq!(crc32(&[b"IE", b"ND"]), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(&[]), 1);
+        // longer than the 5552 byte block summed before taking the modulus
+        let data: Vec<u8> = (0..20000u32).map(|i| (i * 7) as u8).collect();
+        assert_eq!(adler32(&data), 0x37D1_E8CB);
+    }
+
+    #[test]
+    fn png_chunks() {
+        let image = Image {
+            width: 1,
+            height: 1,
+            pixels: vec![10, 20, 30, 255],
+        };
+        let mut bytes = Vec::new();
+        image.write_png(&mut bytes).unwrap();
+
+        assert_eq!(
+            &bytes[..8],
+            &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]
+        );
+        assert_eq!(be_u32_at(&bytes, 8), 13);
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert_eq!(&bytes[16..29], &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        assert_eq!(be_u32_at(&bytes, 29), 0x1F15_C489);
+        assert_eq!(
+            &bytes[bytes.len() - 12..],
+            &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+    }
+
+    /// Inflates the stored blocks written by `write_png`, checking the zlib
+    /// header, block lengths and trailing Adler-32.
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(&zlib[..2], &[0x78, 0x01]);
+        assert_eq!((u32::from(zlib[0]) << 8 | u32::from(zlib[1])) % 31, 0);
+        let mut pos = 2;
+        let mut raw = Vec::new();
+        loop {
+            let last = zlib[pos] & 1 == 1;
+            assert_eq!(zlib[pos] >> 1, 0, "not a stored block");
+            let len = usize::from(zlib[pos + 1]) | usize::from(zlib[pos + 2]) << 8;
+            let nlen = usize::from(zlib[pos + 3]) | usize::from(zlib[pos + 4]) << 8;
+            assert_eq!(len, !nlen & 0xFFFF);
+            raw.extend_from_slice(&zlib[pos + 5..pos + 5 + len]);
+            pos += 5 + len;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(be_u32_at(zlib, pos), adler32(&raw));
+        assert_eq!(pos + 4, zlib.len());
+        raw
+    }
+
+    #[test]
+    fn png_image_data() {
+        // large enough to need several stored blocks
+        let image = test_image(211, 173);
+        let mut bytes = Vec::new();
+        image.write_png(&mut bytes).unwrap();
+
+        let idat_len = be_u32_at(&bytes, 33) as usize;
+        assert_eq!(&bytes[37..41], b"IDAT");
+        let idat = &bytes[41..41 + idat_len];
+        assert_eq!(be_u32_at(&bytes, 41 + idat_len), crc32(&[b"IDAT", idat]));
+
+        let raw = inflate_stored(idat);
+        let stride = image.width as usize * 4;
+        assert_eq!(raw.len(), (stride + 1) * image.height as usize);
+        for (y, row) in raw.chunks(stride + 1).enumerate() {
+            assert_eq!(row[0], 0);
+            assert_eq!(&row[1..], &image.pixels[y * stride..(y + 1) * stride]);
+        }
+    }
+
+    #[test]
+    fn flip_odd_rows() {
+        // reads set a pack alignment of 1, so rows of an odd width are
+        // tightly packed with no padding between them
+        let stride = 3 * 4;
+        let mut pixels: Vec<u8> = (0..stride as u8 * 3).collect();
+        flip_rows(&mut pixels, stride);
+        let expected: Vec<u8> = (24..36).chain(12..24).chain(0..12).collect();
+        assert_eq!(pixels, expected);
+
+        let mut floats: Vec<f32> = (0..stride * 2).map(|i| i as f32).collect();
+        flip_rows(&mut floats, stride);
+        assert_eq!(floats[0], stride as f32);
+        assert_eq!(floats[stride], 0.0);
+
+        let mut single = vec![1u8, 2, 3, 4];
+        flip_rows(&mut single, 4);
+        assert_eq!(single, vec![1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use std::time::Instant;
+
+/// A source of time for the run loop. `time` is called once per frame and
+/// returns the number of seconds since the app started.
+pub trait Clock {
+    fn time(&mut self) -> f64;
+}
+
+/// Wall clock time.
+pub struct RealClock {
+    start: Instant,
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RealClock {
+    pub fn new() -> RealClock {
+        RealClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for RealClock {
+    fn time(&mut self) -> f64 {
+        let elapsed = self.start.elapsed();
+        elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9
+    }
+}
+
+/// Advances by a fixed step every frame regardless of how long frames take,
+/// so frame N always sees time `N * step`.
+pub struct FixedClock {
+    step: f64,
+    frame: u64,
+}
+
+impl FixedClock {
+    pub fn new(step: f64) -> FixedClock {
+        FixedClock { step, frame: 0 }
+    }
+}
+
+impl Clock for FixedClock {
+    fn time(&mut self) -> f64 {
+        let time = self.frame as f64 * self.step;
+        self.frame += 1;
+        time
+    }
+}
+
+/// Plays back a recorded list of frame times. Once the list is exhausted the
+/// last time is repeated.
+pub struct ScriptedClock {
+    times: Vec<f64>,
+    index: usize,
+}
+
+impl ScriptedClock {
+    pub fn new(times: Vec<f64>) -> ScriptedClock {
+        ScriptedClock { times, index: 0 }
+    }
+}
+
+impl Clock for ScriptedClock {
+    fn time(&mut self) -> f64 {
+        let time = match self.times.get(self.index) {
+            Some(&t) => t,
+            None => self.times.last().cloned().unwrap_or(0.0),
+        };
+        self.index += 1;
+        time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_clock_steps() {
+        let mut clock = FixedClock::new(0.25);
+        let times: Vec<f64> = (0..4).map(|_| clock.time()).collect();
+        assert_eq!(times, [0.0, 0.25, 0.5, 0.75]);
+    }
+
+    #[test]
+    fn fixed_clock_does_not_accumulate_error() {
+        let mut clock = FixedClock::new(1.0 / 60.0);
+        for _ in 0..600 {
+            clock.time();
+        }
+        assert_eq!(clock.time(), 600.0 / 60.0);
+    }
+
+    #[test]
+    fn scripted_clock_plays_back_then_repeats() {
+        let mut clock = ScriptedClock::new(vec![0.0, 0.1, 0.3]);
+        let times: Vec<f64> = (0..5).map(|_| clock.time()).collect();
+        assert_eq!(times, [0.0, 0.1, 0.3, 0.3, 0.3]);
+    }
+
+    #[test]
+    fn empty_scripted_clock() {
+        let mut clock = ScriptedClock::new(Vec::new());
+        assert_eq!(clock.time(), 0.0);
+        assert_eq!(clock.time(), 0.0);
+    }
+}
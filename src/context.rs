@@ -0,0 +1,318 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+
+use app::{App, AppInfo};
+use gamepad::Gamepads;
+use gl::types::*;
+use gl_info::{Feature, GlInfo};
+use hot_reload::ProgramRegistry;
+use preprocessor::Preprocessor;
+use stats::FrameStats;
+use std::path::Path;
+use stereo::StereoMode;
+use window::RenderCallback;
+
+/// Changes to the window requested by the app, applied by the run loop once
+/// the current callback returns.
+pub(crate) enum WindowRequest {
+    Close,
+    Fullscreen(bool),
+    Title(String),
+    CursorCaptured(bool),
+    SwapInterval(u32),
+    Open(Box<dyn App>),
+}
+
+/// Per-window state owned by the run loop and passed to every `App` callback.
+pub struct Context {
+    info: AppInfo,
+    gl_info: GlInfo,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    auto_viewport: bool,
+    fullscreen: bool,
+    cursor_captured: bool,
+    requests: Vec<WindowRequest>,
+    stats: FrameStats,
+    stats_overlay: bool,
+    gamepads: Gamepads,
+    stereo_mode: Option<StereoMode>,
+    programs: ProgramRegistry,
+}
+
+impl Context {
+    pub(crate) fn new(info: AppInfo, gl_info: GlInfo, framebuffer_size: (u32, u32)) -> Context {
+        let auto_viewport = info.auto_viewport;
+        let fullscreen = info.fullscreen;
+        let stats_overlay = info.stats_overlay;
+        let gamepads = Gamepads::new(info.gamepad_deadzone);
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.add_include_path(Path::new(&info.media_root).join("shaders"));
+        preprocessor.set_max_version(Some(gl_info.glsl_version()));
+        Context {
+            info,
+            gl_info,
+            framebuffer_width: framebuffer_size.0,
+            framebuffer_height: framebuffer_size.1,
+            auto_viewport,
+            fullscreen,
+            cursor_captured: false,
+            requests: Vec::new(),
+            stats: FrameStats::new(),
+            stats_overlay,
+            gamepads,
+            stereo_mode: None,
+            programs: ProgramRegistry::with_preprocessor(preprocessor),
+        }
+    }
+
+    /// The `AppInfo` the app is running with, after any overrides, with
+    /// `window_width` and `window_height` tracking the current window size.
+    pub fn info(&self) -> &AppInfo {
+        &self.info
+    }
+
+    /// The version, vendor, renderer and extensions of the OpenGL context.
+    /// `info().major_version` and `minor_version` are also updated to the
+    /// version that was created.
+    pub fn gl_info(&self) -> &GlInfo {
+        &self.gl_info
+    }
+
+    /// Returns true if the OpenGL context supports `feature`.
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.gl_info.supports(feature)
+    }
+
+    /// The window size in screen coordinates.
+    pub fn window_size(&self) -> (u32, u32) {
+        (self.info.window_width, self.info.window_height)
+    }
+
+    /// The framebuffer size in pixels. Use this for viewports and render
+    /// targets, it is larger than the window size on HiDPI displays.
+    pub fn framebuffer_size(&self) -> (u32, u32) {
+        (self.framebuffer_width, self.framebuffer_height)
+    }
+
+    /// The ratio of framebuffer pixels to screen coordinates.
+    pub fn content_scale(&self) -> (f32, f32) {
+        if self.info.window_width == 0 || self.info.window_height == 0 {
+            return (1.0, 1.0);
+        }
+        (
+            self.framebuffer_width as f32 / self.info.window_width as f32,
+            self.framebuffer_height as f32 / self.info.window_height as f32,
+        )
+    }
+
+    /// The framebuffer width divided by its height, for projection matrices.
+    pub fn aspect_ratio(&self) -> f32 {
+        if self.framebuffer_height == 0 {
+            return 1.0;
+        }
+        self.framebuffer_width as f32 / self.framebuffer_height as f32
+    }
+
+    /// Sets the viewport to cover the whole framebuffer.
+    pub fn set_viewport(&self) {
+        unsafe {
+            gl::Viewport(
+                0,
+                0,
+                self.framebuffer_width as GLsizei,
+                self.framebuffer_height as GLsizei,
+            );
+        }
+    }
+
+    /// When enabled the viewport is reset to cover the framebuffer before
+    /// every `render`.
+    pub fn set_auto_viewport(&mut self, enabled: bool) {
+        self.auto_viewport = enabled;
+    }
+
+    pub fn auto_viewport(&self) -> bool {
+        self.auto_viewport
+    }
+
+    /// Resolves a path relative to the media root, e.g.
+    /// `ctx.media_path("textures/Tree.ktx")`.
+    pub fn media_path(&self, path: &str) -> String {
+        Path::new(&self.info.media_root)
+            .join(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Closes the window at the end of the current frame, after which
+    /// `shutdown` is called and `run` returns.
+    pub fn request_close(&mut self) {
+        self.requests.push(WindowRequest::Close);
+    }
+
+    /// Switches between fullscreen on the primary monitor and a window. The
+    /// windowed position and size are restored when leaving fullscreen.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        if fullscreen != self.fullscreen {
+            self.fullscreen = fullscreen;
+            self.requests.push(WindowRequest::Fullscreen(fullscreen));
+        }
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen = !self.fullscreen;
+        self.set_fullscreen(fullscreen);
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    /// Sets the window title, e.g. to display the frame rate.
+    pub fn set_title(&mut self, title: &str) {
+        self.requests.push(WindowRequest::Title(title.to_string()));
+    }
+
+    /// A captured cursor is hidden and locked to the window, and mouse move
+    /// events report unbounded virtual positions, which suits mouse look.
+    pub fn set_cursor_captured(&mut self, captured: bool) {
+        if captured != self.cursor_captured {
+            self.cursor_captured = captured;
+            self.requests.push(WindowRequest::CursorCaptured(captured));
+        }
+    }
+
+    pub fn is_cursor_captured(&self) -> bool {
+        self.cursor_captured
+    }
+
+    /// Sets the number of vertical blanks to wait for before swapping
+    /// buffers, 0 disables vsync.
+    pub fn set_swap_interval(&mut self, interval: u32) {
+        self.requests.push(WindowRequest::SwapInterval(interval));
+    }
+
+    /// Opens another window driven by `app`, once the current callback
+    /// returns. Its OpenGL context shares textures, buffers, shaders and
+    /// programs with this one, so objects can be created once and used in
+    /// both. The window's `Context` and callbacks are separate, and it
+    /// closes independently or when the main window closes.
+    ///
+    /// Only the main window waits for vsync and is recorded, and extra
+    /// windows are not opened when running headless.
+    pub fn open_window<A: App + 'static>(&mut self, app: A) {
+        self.requests.push(WindowRequest::Open(Box::new(app)));
+    }
+
+    /// Opens another window that calls `render` each frame. See
+    /// `open_window`.
+    pub fn open_render_window<F>(&mut self, info: AppInfo, render: F)
+    where
+        F: FnMut(&mut Context, f64) + 'static,
+    {
+        self.open_window(RenderCallback::new(info, render));
+    }
+
+    /// Frame timings for the recent frames.
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Shows or hides the frame time graph. It can also be toggled with
+    /// `AppInfo::stats_key`.
+    pub fn set_stats_overlay(&mut self, visible: bool) {
+        self.stats_overlay = visible;
+    }
+
+    pub fn stats_overlay(&self) -> bool {
+        self.stats_overlay
+    }
+
+    /// The connected gamepads, updated once per frame before `update`.
+    pub fn gamepads(&self) -> &Gamepads {
+        &self.gamepads
+    }
+
+    /// Changes the gamepad deadzone set by `AppInfo::gamepad_deadzone`.
+    pub fn set_gamepad_deadzone(&mut self, deadzone: f32) {
+        self.gamepads.set_deadzone(deadzone);
+    }
+
+    /// How the eyes are shown when running in stereo, or `None` for mono.
+    pub fn stereo_mode(&self) -> Option<StereoMode> {
+        self.stereo_mode
+    }
+
+    /// Programs rebuilt when their shader files change, see
+    /// `sb6::hot_reload`.
+    pub fn programs(&self) -> &ProgramRegistry {
+        &self.programs
+    }
+
+    pub fn programs_mut(&mut self) -> &mut ProgramRegistry {
+        &mut self.programs
+    }
+
+    /// The preprocessor used by `programs`, which also finds includes in the
+    /// `shaders` directory under `AppInfo::media_root` and lowers `#version`
+    /// directives to the context's GLSL version. Pass it to
+    /// `shader::load_with` or `shader::load_with_defines` to load shaders that
+    /// include shared files.
+    pub fn preprocessor(&self) -> &Preprocessor {
+        self.programs.preprocessor()
+    }
+
+    pub(crate) fn set_stereo_mode(&mut self, mode: Option<StereoMode>) {
+        self.stereo_mode = mode;
+    }
+
+    pub(crate) fn gamepads_mut(&mut self) -> &mut Gamepads {
+        &mut self.gamepads
+    }
+
+    pub(crate) fn stats_mut(&mut self) -> &mut FrameStats {
+        &mut self.stats
+    }
+
+    pub(crate) fn take_requests(&mut self) -> Vec<WindowRequest> {
+        ::std::mem::take(&mut self.requests)
+    }
+
+    pub(crate) fn set_fullscreen_state(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+    }
+
+    pub(crate) fn set_window_size(&mut self, width: u32, height: u32) {
+        self.info.window_width = width;
+        self.info.window_height = height;
+    }
+
+    pub(crate) fn set_framebuffer_size(&mut self, width: u32, height: u32) {
+        self.framebuffer_width = width;
+        self.framebuffer_height = height;
+    }
+}
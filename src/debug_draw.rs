@@ -0,0 +1,391 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Immediate mode line drawing for visualizing bounds, lights and cameras
+//! while developing samples. Shapes are queued during the frame and drawn
+//! with one upload and at most two draw calls by `DebugDraw::draw`.
+//!
+//! ```ignore
+//! self.debug.grid(vmath::vec3(0.0, 0.0, 0.0), 10.0, 10, GREY);
+//! self.debug.sphere(light_pos, 0.25, YELLOW);
+//! self.debug.set_mode(debug_draw::Mode::Overlay);
+//! self.debug.frustum(&(light_proj * light_view), WHITE);
+//! self.debug.draw(&view_matrix, &proj_matrix);
+//! ```
+
+extern crate gl;
+
+use error::Error;
+use gl::types::*;
+use gl_state::SavedState;
+use program;
+use shader;
+use std::f32;
+use std::mem;
+use std::ptr;
+use vmath::{vec3, Mat4, Vec3, Vec4};
+
+const VS_SRC: &str = r"
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec4 color;
+
+uniform mat4 view_proj_matrix;
+
+out vec4 vs_color;
+
+void main(void)
+{
+    gl_Position = view_proj_matrix * vec4(position, 1.0);
+    vs_color = color;
+}
+";
+
+const FS_SRC: &str = r"
+#version 330 core
+
+in vec4 vs_color;
+
+out vec4 color;
+
+void main(void)
+{
+    color = vs_color;
+}
+";
+
+pub const RED: Vec4 = Vec4 {
+    x: 1.0,
+    y: 0.0,
+    z: 0.0,
+    w: 1.0,
+};
+pub const GREEN: Vec4 = Vec4 {
+    x: 0.0,
+    y: 1.0,
+    z: 0.0,
+    w: 1.0,
+};
+pub const BLUE: Vec4 = Vec4 {
+    x: 0.0,
+    y: 0.0,
+    z: 1.0,
+    w: 1.0,
+};
+pub const WHITE: Vec4 = Vec4 {
+    x: 1.0,
+    y: 1.0,
+    z: 1.0,
+    w: 1.0,
+};
+
+/// Position and color, 7 floats per vertex.
+const VERTEX_SIZE: usize = 7;
+
+/// Segments used to draw circles and spheres.
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    /// Lines are hidden by the geometry already in the depth buffer.
+    DepthTested,
+    /// Lines are drawn over everything.
+    Overlay,
+}
+
+pub struct DebugDraw {
+    mode: Mode,
+    depth_tested: Vec<f32>,
+    overlay: Vec<f32>,
+    program: GLuint,
+    vao: GLuint,
+    buffer: GLuint,
+    view_proj_location: GLint,
+}
+
+impl DebugDraw {
+    pub fn new() -> Result<DebugDraw, Error> {
+        let vs = try!(shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
+        let fs = try!(shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+        let program = try!(program::link_from_shaders(&[vs, fs]));
+        let view_proj_location = try!(program::get_uniform_location(program, "view_proj_matrix"));
+
+        let mut debug_draw = DebugDraw {
+            mode: Mode::DepthTested,
+            depth_tested: Vec::new(),
+            overlay: Vec::new(),
+            program,
+            vao: 0,
+            buffer: 0,
+            view_proj_location,
+        };
+        let stride = (VERTEX_SIZE * mem::size_of::<f32>()) as GLsizei;
+        unsafe {
+            gl::GenVertexArrays(1, &mut debug_draw.vao);
+            gl::BindVertexArray(debug_draw.vao);
+            gl::GenBuffers(1, &mut debug_draw.buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, debug_draw.buffer);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(
+                1,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (3 * mem::size_of::<f32>()) as *const _,
+            );
+            gl::EnableVertexAttribArray(1);
+            gl::BindVertexArray(0);
+        }
+        Ok(debug_draw)
+    }
+
+    /// Sets the mode used by shapes added after this call.
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Vec4) {
+        let vertices = match self.mode {
+            Mode::DepthTested => &mut self.depth_tested,
+            Mode::Overlay => &mut self.overlay,
+        };
+        for p in &[a, b] {
+            vertices.extend_from_slice(&[p.x, p.y, p.z, color.x, color.y, color.z, color.w]);
+        }
+    }
+
+    /// Draws the 12 edges between 8 corners, ordered as the corners of a
+    /// cube with bit 0 selecting x, bit 1 y and bit 2 z.
+    fn box_edges(&mut self, corners: &[Vec3; 8], color: Vec4) {
+        for i in 0..8 {
+            for &bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    /// An axis aligned box.
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        let mut corners = [min; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = vec3(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+        }
+        self.box_edges(&corners, color);
+    }
+
+    /// The cube from -1 to 1 transformed by `transform`, e.g. an oriented
+    /// bounding box.
+    pub fn transformed_box(&mut self, transform: &Mat4, color: Vec4) {
+        let mut corners = [Vec3::zero(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = transform.transform_point(vec3(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            ));
+        }
+        self.box_edges(&corners, color);
+    }
+
+    /// The view volume of the camera with view-projection matrix
+    /// `view_proj`, by transforming the clip space cube back to world space.
+    pub fn frustum(&mut self, view_proj: &Mat4, color: Vec4) {
+        match view_proj.inverse() {
+            Some(inverse) => self.transformed_box(&inverse, color),
+            None => warn!("Can't draw the frustum of a singular matrix"),
+        }
+    }
+
+    /// A circle around `center` in the plane spanned by `u` and `v`, which
+    /// should be perpendicular and of length `radius`.
+    fn circle(&mut self, center: Vec3, u: Vec3, v: Vec3, color: Vec4) {
+        let point = |i: usize| {
+            let angle = 2.0 * f32::consts::PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            let (sin, cos) = angle.sin_cos();
+            center + u * cos + v * sin
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    /// A sphere drawn as three circles around the axes.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        let x = vec3(radius, 0.0, 0.0);
+        let y = vec3(0.0, radius, 0.0);
+        let z = vec3(0.0, 0.0, radius);
+        self.circle(center, x, y, color);
+        self.circle(center, y, z, color);
+        self.circle(center, z, x, color);
+    }
+
+    /// The x, y and z axes of `transform` in red, green and blue, `size`
+    /// units long.
+    pub fn axes(&mut self, transform: &Mat4, size: f32) {
+        let origin = transform.transform_point(Vec3::zero());
+        self.line(origin, transform.transform_point(vec3(size, 0.0, 0.0)), RED);
+        self.line(
+            origin,
+            transform.transform_point(vec3(0.0, size, 0.0)),
+            GREEN,
+        );
+        self.line(
+            origin,
+            transform.transform_point(vec3(0.0, 0.0, size)),
+            BLUE,
+        );
+    }
+
+    /// A grid on the y = `center.y` plane, `size` units across with
+    /// `divisions` cells along each side.
+    pub fn grid(&mut self, center: Vec3, size: f32, divisions: u32, color: Vec4) {
+        let divisions = divisions.max(1);
+        let half = size * 0.5;
+        for i in 0..=divisions {
+            let t = -half + size * i as f32 / divisions as f32;
+            self.line(
+                center + vec3(t, 0.0, -half),
+                center + vec3(t, 0.0, half),
+                color,
+            );
+            self.line(
+                center + vec3(-half, 0.0, t),
+                center + vec3(half, 0.0, t),
+                color,
+            );
+        }
+    }
+
+    /// A small 3D cross, e.g. for a light position.
+    pub fn cross(&mut self, center: Vec3, size: f32, color: Vec4) {
+        let half = size * 0.5;
+        self.line(
+            center - vec3(half, 0.0, 0.0),
+            center + vec3(half, 0.0, 0.0),
+            color,
+        );
+        self.line(
+            center - vec3(0.0, half, 0.0),
+            center + vec3(0.0, half, 0.0),
+            color,
+        );
+        self.line(
+            center - vec3(0.0, 0.0, half),
+            center + vec3(0.0, 0.0, half),
+            color,
+        );
+    }
+
+    /// Discards the queued shapes without drawing them.
+    pub fn clear(&mut self) {
+        self.depth_tested.clear();
+        self.overlay.clear();
+    }
+
+    /// Draws and clears the queued shapes into the currently bound
+    /// framebuffer and viewport. GL state changed while drawing is restored
+    /// afterwards.
+    pub fn draw(&mut self, view: &Mat4, proj: &Mat4) {
+        let depth_tested_count = self.depth_tested.len() / VERTEX_SIZE;
+        let overlay_count = self.overlay.len() / VERTEX_SIZE;
+        if depth_tested_count + overlay_count == 0 {
+            return;
+        }
+
+        let view_proj = *proj * *view;
+        let saved = SavedState::save();
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::UniformMatrix4fv(self.view_proj_location, 1, gl::FALSE, view_proj.as_ptr());
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer);
+
+            // one buffer with the depth tested lines followed by the overlay
+            let size = (self.depth_tested.len() + self.overlay.len()) * mem::size_of::<f32>();
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                size as GLsizeiptr,
+                ptr::null(),
+                gl::STREAM_DRAW,
+            );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (self.depth_tested.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                self.depth_tested.as_ptr() as *const _,
+            );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (self.depth_tested.len() * mem::size_of::<f32>()) as GLintptr,
+                (self.overlay.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                self.overlay.as_ptr() as *const _,
+            );
+
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::DepthMask(gl::FALSE);
+
+            if depth_tested_count > 0 {
+                gl::Enable(gl::DEPTH_TEST);
+                gl::DepthFunc(gl::LEQUAL);
+                gl::DrawArrays(gl::LINES, 0, depth_tested_count as GLsizei);
+            }
+            if overlay_count > 0 {
+                gl::Disable(gl::DEPTH_TEST);
+                gl::DrawArrays(
+                    gl::LINES,
+                    depth_tested_count as GLint,
+                    overlay_count as GLsizei,
+                );
+            }
+        }
+        saved.restore();
+        self.clear();
+    }
+
+    pub fn free(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+        self.program = 0;
+        self.vao = 0;
+        self.buffer = 0;
+    }
+}
@@ -0,0 +1,431 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Parsing of shader compiler info logs into structured diagnostics.
+//!
+//! The drivers all report a source string number and line, but each in its
+//! own format:
+//!
+//! ```text
+//! Mesa:              0:12(5): error: syntax error, unexpected IDENTIFIER
+//! NVIDIA:            0(12) : error C0000: syntax error, unexpected identifier
+//! AMD, Intel, Apple: ERROR: 0:12: 'foo' : undeclared identifier
+//! ```
+//!
+//! Lines in none of these formats are kept as part of the previous
+//! diagnostic's message, or as a diagnostic without a location.
+
+use std::fmt;
+use std::path::Path;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        })
+    }
+}
+
+/// One message from a compiler info log. Lines and columns count from 1.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The source string the message refers to, which maps to a file through
+    /// `preprocessor::Source::file`.
+    pub source_string: Option<u32>,
+    pub line: Option<u32>,
+    /// Only reported by Mesa.
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+/// A line that isn't in the format being tried.
+struct NoMatch;
+
+fn parse_severity(s: &str) -> Result<Severity, NoMatch> {
+    match s {
+        "error" | "fatal error" | "ERROR" => Ok(Severity::Error),
+        "warning" | "WARNING" => Ok(Severity::Warning),
+        "info" | "INFO" => Ok(Severity::Info),
+        _ => Err(NoMatch),
+    }
+}
+
+/// Splits a leading decimal number from `s`.
+fn split_number(s: &str) -> Result<(u32, &str), NoMatch> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if end == 0 {
+        return Err(NoMatch);
+    }
+    s[..end]
+        .parse()
+        .map(|n| (n, &s[end..]))
+        .map_err(|_| NoMatch)
+}
+
+fn strip<'a>(s: &'a str, prefix: &str) -> Result<&'a str, NoMatch> {
+    s.strip_prefix(prefix).ok_or(NoMatch)
+}
+
+/// Splits `s` at the first `": "`.
+fn split_colon(s: &str) -> Result<(&str, &str), NoMatch> {
+    match s.find(": ") {
+        Some(colon) => Ok((&s[..colon], &s[colon + 2..])),
+        None => Err(NoMatch),
+    }
+}
+
+/// `0:12(5): error: message`
+fn parse_mesa(line: &str) -> Result<Diagnostic, NoMatch> {
+    let (source_string, rest) = try!(split_number(line));
+    let (line_number, rest) = try!(split_number(try!(strip(rest, ":"))));
+    let (column, rest) = try!(split_number(try!(strip(rest, "("))));
+    let (severity, message) = try!(split_colon(try!(strip(rest, "): "))));
+    Ok(Diagnostic {
+        severity: try!(parse_severity(severity)),
+        source_string: Some(source_string),
+        line: Some(line_number),
+        column: Some(column),
+        message: message.to_string(),
+    })
+}
+
+/// `0(12) : error C0000: message`
+fn parse_nvidia(line: &str) -> Result<Diagnostic, NoMatch> {
+    let (source_string, rest) = try!(split_number(line));
+    let (line_number, rest) = try!(split_number(try!(strip(rest, "("))));
+    let (severity, message) = try!(split_colon(try!(strip(rest, ") : "))));
+    // the severity is followed by an error code such as C0000
+    let severity = match severity.rfind(' ') {
+        Some(space) => &severity[..space],
+        None => severity,
+    };
+    Ok(Diagnostic {
+        severity: try!(parse_severity(severity)),
+        source_string: Some(source_string),
+        line: Some(line_number),
+        column: None,
+        message: message.to_string(),
+    })
+}
+
+/// `ERROR: 0:12: message`
+fn parse_amd(line: &str) -> Result<Diagnostic, NoMatch> {
+    let (severity, rest) = try!(split_colon(line));
+    let severity = try!(parse_severity(severity));
+    let (source_string, rest) = try!(split_number(rest));
+    let (line_number, rest) = try!(split_number(try!(strip(rest, ":"))));
+    let message = try!(strip(rest, ":"));
+    Ok(Diagnostic {
+        severity,
+        source_string: Some(source_string),
+        line: Some(line_number),
+        column: None,
+        message: message.trim().to_string(),
+    })
+}
+
+/// Parses every message in `log`.
+pub fn parse_info_log(log: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in log.lines() {
+        let line = line.trim_end_matches('\0').trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(diagnostic) = parse_mesa(line)
+            .or_else(|_| parse_nvidia(line))
+            .or_else(|_| parse_amd(line))
+        {
+            diagnostics.push(diagnostic);
+            continue;
+        }
+        // AMD ends its log with a summary that repeats the error count
+        if line.ends_with("No code generated.") {
+            continue;
+        }
+        match diagnostics.last_mut() {
+            Some(last) => {
+                last.message.push('\n');
+                last.message.push_str(line.trim());
+            }
+            None => diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                source_string: None,
+                line: None,
+                column: None,
+                message: line.trim().to_string(),
+            }),
+        }
+    }
+    diagnostics
+}
+
+impl Diagnostic {
+    /// Writes the diagnostic in the style of rustc, with the offending line
+    /// of `source` and a caret under the reported column or an underline
+    /// under the whole line.
+    pub fn render(
+        &self,
+        fmt: &mut fmt::Formatter,
+        file: Option<&Path>,
+        source: Option<&str>,
+    ) -> fmt::Result {
+        try!(writeln!(fmt, "{}: {}", self.severity, self.message));
+        let line_number = match self.line {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        let name = match file {
+            Some(file) => file.display().to_string(),
+            None => format!("<source string {}>", self.source_string.unwrap_or(0)),
+        };
+        let gutter = " ".repeat(line_number.to_string().len());
+        match self.column {
+            Some(column) => try!(writeln!(
+                fmt,
+                "{}--> {}:{}:{}",
+                gutter, name, line_number, column
+            )),
+            None => try!(writeln!(fmt, "{}--> {}:{}", gutter, name, line_number)),
+        }
+        let text = match source.and_then(|s| s.lines().nth((line_number as usize).wrapping_sub(1)))
+        {
+            Some(text) => text.trim_end(),
+            None => return Ok(()),
+        };
+        try!(writeln!(fmt, "{} |", gutter));
+        try!(writeln!(fmt, "{} | {}", line_number, text));
+        // keep tabs in the marker's indent so it lines up with the text
+        let marker = match self.column {
+            Some(column) if column > 0 => {
+                let indent: String = text
+                    .chars()
+                    .take(column as usize - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                format!("{}^", indent)
+            }
+            _ => {
+                let indent = &text[..text.len() - text.trim_start().len()];
+                format!(
+                    "{}{}",
+                    indent,
+                    "^".repeat(text.trim_start().chars().count().max(1))
+                )
+            }
+        };
+        writeln!(fmt, "{} | {}", gutter, marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        severity: Severity,
+        location: Option<(u32, u32, Option<u32>)>,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity,
+            source_string: location.map(|l| l.0),
+            line: location.map(|l| l.1),
+            column: location.and_then(|l| l.2),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn mesa() {
+        let log = "0:3(1): error: syntax error, unexpected IDENTIFIER, expecting ',' or ';'\n\
+                   1:12(17): error: `colour' undeclared\n\
+                   0:7(2): warning: `x' used uninitialized\n";
+        assert_eq!(
+            parse_info_log(log),
+            [
+                diagnostic(
+                    Severity::Error,
+                    Some((0, 3, Some(1))),
+                    "syntax error, unexpected IDENTIFIER, expecting ',' or ';'"
+                ),
+                diagnostic(
+                    Severity::Error,
+                    Some((1, 12, Some(17))),
+                    "`colour' undeclared"
+                ),
+                diagnostic(
+                    Severity::Warning,
+                    Some((0, 7, Some(2))),
+                    "`x' used uninitialized"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nvidia() {
+        let log = "0(12) : error C1008: undefined variable \"colour\"\n\
+                   0(5) : warning C7533: global variable gl_FragColor is deprecated after version 120\n\
+                   2(30) : error C0000: syntax error, unexpected '}' at token \"}\"\n";
+        assert_eq!(
+            parse_info_log(log),
+            [
+                diagnostic(
+                    Severity::Error,
+                    Some((0, 12, None)),
+                    "undefined variable \"colour\""
+                ),
+                diagnostic(
+                    Severity::Warning,
+                    Some((0, 5, None)),
+                    "global variable gl_FragColor is deprecated after version 120"
+                ),
+                diagnostic(
+                    Severity::Error,
+                    Some((2, 30, None)),
+                    "syntax error, unexpected '}' at token \"}\""
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn amd() {
+        // the log is null terminated and ends with a summary line
+        let log = "ERROR: 0:12: 'colour' : undeclared identifier \n\
+                   WARNING: 1:4: 'x' : variable is not used\n\
+                   ERROR: 1 compilation errors.  No code generated.\n\n\0";
+        assert_eq!(
+            parse_info_log(log),
+            [
+                diagnostic(
+                    Severity::Error,
+                    Some((0, 12, None)),
+                    "'colour' : undeclared identifier"
+                ),
+                diagnostic(
+                    Severity::Warning,
+                    Some((1, 4, None)),
+                    "'x' : variable is not used"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unrecognised_lines() {
+        assert_eq!(
+            parse_info_log("Internal compiler error\nout of registers\n"),
+            [diagnostic(
+                Severity::Error,
+                None,
+                "Internal compiler error\nout of registers"
+            )]
+        );
+        assert_eq!(
+            parse_info_log("0(3) : error C0000: syntax error\n    (near \"vec4\")\n"),
+            [diagnostic(
+                Severity::Error,
+                Some((0, 3, None)),
+                "syntax error\n(near \"vec4\")"
+            )]
+        );
+        assert!(parse_info_log("").is_empty());
+    }
+
+    struct Render<'a>(&'a Diagnostic, Option<&'a Path>, Option<&'a str>);
+
+    impl<'a> fmt::Display for Render<'a> {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            self.0.render(fmt, self.1, self.2)
+        }
+    }
+
+    const SOURCE: &str = "void main()\n{\n    colour = 1.0;\n\tx = 2;\n}\n";
+
+    fn render(diagnostic: &Diagnostic, source: Option<&str>) -> String {
+        Render(diagnostic, Some(Path::new("test.glsl")), source).to_string()
+    }
+
+    #[test]
+    fn render_caret_at_column() {
+        let d = diagnostic(
+            Severity::Error,
+            Some((0, 3, Some(5))),
+            "`colour' undeclared",
+        );
+        assert_eq!(
+            render(&d, Some(SOURCE)),
+            "error: `colour' undeclared\n \
+             --> test.glsl:3:5\n  \
+             |\n\
+             3 |     colour = 1.0;\n  \
+             |     ^\n"
+        );
+    }
+
+    #[test]
+    fn render_caret_after_tab() {
+        let d = diagnostic(Severity::Error, Some((0, 4, Some(2))), "`x' undeclared");
+        assert!(render(&d, Some(SOURCE)).ends_with("4 | \tx = 2;\n  | \t^\n"));
+    }
+
+    #[test]
+    fn render_underline_without_column() {
+        let d = diagnostic(Severity::Warning, Some((0, 3, None)), "unused");
+        assert!(
+            render(&d, Some(SOURCE)).ends_with("3 |     colour = 1.0;\n  |     ^^^^^^^^^^^^^\n")
+        );
+    }
+
+    #[test]
+    fn render_without_source_or_location() {
+        let d = diagnostic(Severity::Error, Some((0, 3, Some(5))), "oops");
+        assert_eq!(render(&d, None), "error: oops\n --> test.glsl:3:5\n");
+        // a line past the end of the source
+        let d = diagnostic(Severity::Error, Some((0, 12, None)), "oops");
+        assert_eq!(
+            render(&d, Some(SOURCE)),
+            "error: oops\n  --> test.glsl:12\n"
+        );
+        let d = diagnostic(Severity::Error, Some((2, 0, None)), "oops");
+        assert_eq!(
+            Render(&d, None, Some(SOURCE)).to_string(),
+            "error: oops\n --> <source string 2>:0\n"
+        );
+        let d = diagnostic(Severity::Info, None, "oops");
+        assert_eq!(render(&d, Some(SOURCE)), "info: oops\n");
+    }
+}
@@ -0,0 +1,357 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! A GL context with no window system connection, used when running
+//! headless.
+//!
+//! libEGL is loaded at run time so apps that never run headless don't need
+//! it. The display is Mesa's surfaceless platform
+//! (`EGL_MESA_platform_surfaceless`) when the driver has it, otherwise the
+//! default EGL display, which drivers such as NVIDIA's open without a
+//! display server. The context is made current without a surface if the
+//! display supports `EGL_KHR_surfaceless_context`, and with a 1x1 pbuffer
+//! otherwise. Either way apps render to an `Offscreen` target.
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+type EGLint = i32;
+type EGLBoolean = u32;
+type EGLenum = u32;
+type EGLDisplay = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLSurface = *mut c_void;
+
+const EGL_NONE: EGLint = 0x3038;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_SURFACE_TYPE: EGLint = 0x3033;
+const EGL_PBUFFER_BIT: EGLint = 0x0001;
+const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+const EGL_OPENGL_BIT: EGLint = 0x0008;
+const EGL_WIDTH: EGLint = 0x3057;
+const EGL_HEIGHT: EGLint = 0x3056;
+const EGL_OPENGL_API: EGLenum = 0x30A2;
+const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x0001;
+const EGL_CONTEXT_OPENGL_FORWARD_COMPATIBLE: EGLint = 0x31B1;
+const EGL_CONTEXT_OPENGL_DEBUG: EGLint = 0x31B0;
+const EGL_TRUE: EGLint = 1;
+const EGL_FALSE: EGLint = 0;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum EglError {
+    /// libEGL could not be loaded or is missing an entry point.
+    Library(String),
+    /// An EGL call failed with the given `eglGetError` code.
+    Call(&'static str, i32),
+    /// The display has no config for desktop OpenGL.
+    NoConfig,
+}
+
+impl fmt::Display for EglError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EglError::Library(ref message) => write!(fmt, "Failed to load libEGL: {}", message),
+            EglError::Call(function, code) => {
+                write!(fmt, "{} failed (EGL error 0x{:04X})", function, code)
+            }
+            EglError::NoConfig => write!(fmt, "No EGL config supports desktop OpenGL"),
+        }
+    }
+}
+
+#[cfg(unix)]
+mod dl {
+    use std::os::raw::{c_char, c_int, c_void};
+
+    pub const RTLD_NOW: c_int = 2;
+
+    extern "C" {
+        pub fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+        pub fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+}
+
+/// Looks up `name` in the library `handle`. `F` must be the function
+/// pointer type of the symbol.
+#[cfg(unix)]
+unsafe fn symbol<F: Copy>(handle: *mut c_void, name: &str) -> Result<F, EglError> {
+    let cname = CString::new(name).unwrap();
+    let address = dl::dlsym(handle, cname.as_ptr());
+    if address.is_null() {
+        Err(EglError::Library(format!("{} not found", name)))
+    } else {
+        Ok(mem::transmute_copy(&address))
+    }
+}
+
+type GetPlatformDisplayFn = unsafe extern "C" fn(EGLenum, *mut c_void, *const EGLint) -> EGLDisplay;
+
+/// The EGL entry points used, loaded from libEGL.
+struct Egl {
+    get_error: unsafe extern "C" fn() -> EGLint,
+    get_proc_address: unsafe extern "C" fn(*const c_char) -> *mut c_void,
+    query_string: unsafe extern "C" fn(EGLDisplay, EGLint) -> *const c_char,
+    get_display: unsafe extern "C" fn(*mut c_void) -> EGLDisplay,
+    initialize: unsafe extern "C" fn(EGLDisplay, *mut EGLint, *mut EGLint) -> EGLBoolean,
+    terminate: unsafe extern "C" fn(EGLDisplay) -> EGLBoolean,
+    bind_api: unsafe extern "C" fn(EGLenum) -> EGLBoolean,
+    choose_config: unsafe extern "C" fn(
+        EGLDisplay,
+        *const EGLint,
+        *mut EGLConfig,
+        EGLint,
+        *mut EGLint,
+    ) -> EGLBoolean,
+    create_context:
+        unsafe extern "C" fn(EGLDisplay, EGLConfig, EGLContext, *const EGLint) -> EGLContext,
+    destroy_context: unsafe extern "C" fn(EGLDisplay, EGLContext) -> EGLBoolean,
+    create_pbuffer_surface:
+        unsafe extern "C" fn(EGLDisplay, EGLConfig, *const EGLint) -> EGLSurface,
+    destroy_surface: unsafe extern "C" fn(EGLDisplay, EGLSurface) -> EGLBoolean,
+    make_current:
+        unsafe extern "C" fn(EGLDisplay, EGLSurface, EGLSurface, EGLContext) -> EGLBoolean,
+}
+
+impl Egl {
+    /// Loads libEGL, which stays loaded until the process exits.
+    #[cfg(unix)]
+    fn load() -> Result<Egl, EglError> {
+        let handle = ["libEGL.so.1", "libEGL.so"]
+            .iter()
+            .map(|name| {
+                let name = CString::new(*name).unwrap();
+                unsafe { dl::dlopen(name.as_ptr(), dl::RTLD_NOW) }
+            })
+            .find(|handle| !handle.is_null());
+        let handle = match handle {
+            Some(handle) => handle,
+            None => return Err(EglError::Library(String::from("libEGL.so.1 not found"))),
+        };
+        unsafe {
+            Ok(Egl {
+                get_error: try!(symbol(handle, "eglGetError")),
+                get_proc_address: try!(symbol(handle, "eglGetProcAddress")),
+                query_string: try!(symbol(handle, "eglQueryString")),
+                get_display: try!(symbol(handle, "eglGetDisplay")),
+                initialize: try!(symbol(handle, "eglInitialize")),
+                terminate: try!(symbol(handle, "eglTerminate")),
+                bind_api: try!(symbol(handle, "eglBindAPI")),
+                choose_config: try!(symbol(handle, "eglChooseConfig")),
+                create_context: try!(symbol(handle, "eglCreateContext")),
+                destroy_context: try!(symbol(handle, "eglDestroyContext")),
+                create_pbuffer_surface: try!(symbol(handle, "eglCreatePbufferSurface")),
+                destroy_surface: try!(symbol(handle, "eglDestroySurface")),
+                make_current: try!(symbol(handle, "eglMakeCurrent")),
+            })
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn load() -> Result<Egl, EglError> {
+        Err(EglError::Library(String::from(
+            "headless rendering is only supported on unix platforms",
+        )))
+    }
+
+    fn error(&self, function: &'static str) -> EglError {
+        EglError::Call(function, unsafe { (self.get_error)() })
+    }
+
+    /// The extensions of `display`, or the client extensions for a null
+    /// display.
+    fn extensions(&self, display: EGLDisplay) -> String {
+        unsafe {
+            let extensions = (self.query_string)(display, EGL_EXTENSIONS);
+            if extensions.is_null() {
+                // clear the error left by drivers without client extensions
+                (self.get_error)();
+                return String::new();
+            }
+            CStr::from_ptr(extensions).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Mesa's surfaceless display if available, otherwise the default one.
+    fn open_display(&self) -> Result<EGLDisplay, EglError> {
+        if has_extension(
+            &self.extensions(ptr::null_mut()),
+            "EGL_MESA_platform_surfaceless",
+        ) {
+            let name = b"eglGetPlatformDisplayEXT\0".as_ptr() as *const c_char;
+            let address = unsafe { (self.get_proc_address)(name) };
+            if !address.is_null() {
+                let display = unsafe {
+                    let get_platform_display: GetPlatformDisplayFn = mem::transmute_copy(&address);
+                    get_platform_display(
+                        EGL_PLATFORM_SURFACELESS_MESA,
+                        ptr::null_mut(),
+                        ptr::null(),
+                    )
+                };
+                if !display.is_null() && self.initialize(display) {
+                    return Ok(display);
+                }
+            }
+        }
+        let display = unsafe { (self.get_display)(ptr::null_mut()) };
+        if display.is_null() {
+            return Err(self.error("eglGetDisplay"));
+        }
+        if !self.initialize(display) {
+            return Err(self.error("eglInitialize"));
+        }
+        Ok(display)
+    }
+
+    fn initialize(&self, display: EGLDisplay) -> bool {
+        let (mut major, mut minor) = (0, 0);
+        unsafe { (self.initialize)(display, &mut major, &mut minor) != 0 }
+    }
+}
+
+fn has_extension(extensions: &str, name: &str) -> bool {
+    extensions.split(' ').any(|extension| extension == name)
+}
+
+/// A current OpenGL context with no window or window system connection.
+/// Dropping it releases the context and the display.
+pub(crate) struct Context {
+    egl: Egl,
+    display: EGLDisplay,
+    context: EGLContext,
+    surface: EGLSurface,
+}
+
+impl Context {
+    /// Creates a core profile context with the first of `versions` the driver
+    /// supports and makes it current. Returns `None` if none are supported.
+    pub(crate) fn new(versions: &[(u32, u32)], debug: bool) -> Result<Option<Context>, EglError> {
+        let egl = try!(Egl::load());
+        let display = try!(egl.open_display());
+        // from here on dropping `context` cleans up what was created
+        let mut context = Context {
+            egl,
+            display,
+            context: ptr::null_mut(),
+            surface: ptr::null_mut(),
+        };
+        let egl = &context.egl;
+        if unsafe { (egl.bind_api)(EGL_OPENGL_API) } == 0 {
+            return Err(egl.error("eglBindAPI"));
+        }
+
+        let config_attribs = [
+            EGL_SURFACE_TYPE,
+            EGL_PBUFFER_BIT,
+            EGL_RENDERABLE_TYPE,
+            EGL_OPENGL_BIT,
+            EGL_NONE,
+        ];
+        let mut config = ptr::null_mut();
+        let mut count = 0;
+        let chosen = unsafe {
+            (egl.choose_config)(display, config_attribs.as_ptr(), &mut config, 1, &mut count)
+        };
+        if chosen == 0 {
+            return Err(egl.error("eglChooseConfig"));
+        }
+        if count == 0 {
+            return Err(EglError::NoConfig);
+        }
+
+        for &(major, minor) in versions {
+            let context_attribs = [
+                EGL_CONTEXT_MAJOR_VERSION,
+                major as EGLint,
+                EGL_CONTEXT_MINOR_VERSION,
+                minor as EGLint,
+                EGL_CONTEXT_OPENGL_PROFILE_MASK,
+                EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                EGL_CONTEXT_OPENGL_FORWARD_COMPATIBLE,
+                EGL_TRUE,
+                EGL_CONTEXT_OPENGL_DEBUG,
+                if debug { EGL_TRUE } else { EGL_FALSE },
+                EGL_NONE,
+            ];
+            context.context = unsafe {
+                (egl.create_context)(display, config, ptr::null_mut(), context_attribs.as_ptr())
+            };
+            if !context.context.is_null() {
+                break;
+            }
+            info!("OpenGL {}.{} is not available", major, minor);
+        }
+        if context.context.is_null() {
+            return Ok(None);
+        }
+
+        if !has_extension(&egl.extensions(display), "EGL_KHR_surfaceless_context") {
+            let pbuffer_attribs = [EGL_WIDTH, 1, EGL_HEIGHT, 1, EGL_NONE];
+            context.surface =
+                unsafe { (egl.create_pbuffer_surface)(display, config, pbuffer_attribs.as_ptr()) };
+            if context.surface.is_null() {
+                return Err(egl.error("eglCreatePbufferSurface"));
+            }
+        }
+        let surface = context.surface;
+        if unsafe { (egl.make_current)(display, surface, surface, context.context) } == 0 {
+            return Err(egl.error("eglMakeCurrent"));
+        }
+        Ok(Some(context))
+    }
+
+    pub(crate) fn get_proc_address(&self, name: &str) -> *const c_void {
+        let name = CString::new(name).unwrap();
+        unsafe { (self.egl.get_proc_address)(name.as_ptr()) }
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        let egl = &self.egl;
+        unsafe {
+            (egl.make_current)(
+                self.display,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            if !self.surface.is_null() {
+                (egl.destroy_surface)(self.display, self.surface);
+            }
+            if !self.context.is_null() {
+                (egl.destroy_context)(self.display, self.context);
+            }
+            (egl.terminate)(self.display);
+        }
+    }
+}
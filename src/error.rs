@@ -0,0 +1,205 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate glfw;
+
+use capture::CaptureError;
+use egl::EglError;
+use ktx;
+use object;
+use offscreen::FramebufferError;
+use options;
+use options::OptionsError;
+use program::{ProgramError, UniformError};
+use replay::ReplayError;
+use shader;
+use shader::ShaderError;
+use std::error;
+use std::fmt;
+
+/// Any error that can stop an app from running, returned by `run` and by
+/// `App::startup`.
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid command line flags, or `--help`, from `AppInfo::from_args`.
+    Options(OptionsError),
+    /// GLFW failed to initialize.
+    Glfw(glfw::InitError),
+    /// No context between the requested and minimum OpenGL versions could be
+    /// created, for the window or when running headless.
+    ContextCreation((u32, u32), (u32, u32)),
+    /// The EGL context used when running headless could not be created.
+    Egl(EglError),
+    /// A time step that isn't a positive number of seconds, named by the
+    /// `AppInfo` field it was set in.
+    TimeStep(&'static str, f64),
+    /// Quad buffered stereo was chosen as the stereo fallback for a headless
+    /// run, whose offscreen target has no left and right back buffers.
+    StereoOffscreen,
+    Framebuffer(FramebufferError),
+    Shader(ShaderError),
+    ShaderLoad(shader::LoadError),
+    Program(ProgramError),
+    Uniform(UniformError),
+    Object(object::LoadError),
+    Ktx(ktx::LoadError),
+    Capture(CaptureError),
+    Replay(ReplayError),
+    /// An error loading the named file.
+    File(String, Box<Error>),
+    /// An error reported by the app.
+    App(String),
+}
+
+impl Error {
+    /// Wraps `e` with the path of the file that failed to load, e.g.
+    /// `ktx::load(&path).map_err(|e| Error::file(&path, e))`.
+    pub fn file<E: Into<Error>>(path: &str, e: E) -> Error {
+        Error::File(path.to_string(), Box::new(e.into()))
+    }
+}
+
+impl From<OptionsError> for Error {
+    fn from(e: OptionsError) -> Error {
+        Error::Options(e)
+    }
+}
+
+impl From<glfw::InitError> for Error {
+    fn from(e: glfw::InitError) -> Error {
+        Error::Glfw(e)
+    }
+}
+
+impl From<EglError> for Error {
+    fn from(e: EglError) -> Error {
+        Error::Egl(e)
+    }
+}
+
+impl From<FramebufferError> for Error {
+    fn from(e: FramebufferError) -> Error {
+        Error::Framebuffer(e)
+    }
+}
+
+impl From<ShaderError> for Error {
+    fn from(e: ShaderError) -> Error {
+        Error::Shader(e)
+    }
+}
+
+impl From<shader::LoadError> for Error {
+    fn from(e: shader::LoadError) -> Error {
+        Error::ShaderLoad(e)
+    }
+}
+
+impl From<ProgramError> for Error {
+    fn from(e: ProgramError) -> Error {
+        Error::Program(e)
+    }
+}
+
+impl From<UniformError> for Error {
+    fn from(e: UniformError) -> Error {
+        Error::Uniform(e)
+    }
+}
+
+impl From<object::LoadError> for Error {
+    fn from(e: object::LoadError) -> Error {
+        Error::Object(e)
+    }
+}
+
+impl From<ktx::LoadError> for Error {
+    fn from(e: ktx::LoadError) -> Error {
+        Error::Ktx(e)
+    }
+}
+
+impl From<CaptureError> for Error {
+    fn from(e: CaptureError) -> Error {
+        Error::Capture(e)
+    }
+}
+
+impl From<ReplayError> for Error {
+    fn from(e: ReplayError) -> Error {
+        Error::Replay(e)
+    }
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Error {
+        Error::App(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Options(OptionsError::Help) => write!(fmt, "{}", options::USAGE),
+            Error::Options(ref e) => write!(fmt, "{}\n\n{}", e, options::USAGE),
+            Error::Glfw(ref e) => write!(fmt, "Failed to initialize GLFW: {}", e),
+            Error::ContextCreation((major, minor), (min_major, min_minor)) => write!(
+                fmt,
+                "Failed to create an OpenGL {}.{} to {}.{} context",
+                min_major, min_minor, major, minor
+            ),
+            Error::Egl(ref e) => write!(fmt, "Failed to create a headless context: {}", e),
+            Error::TimeStep(field, step) => write!(
+                fmt,
+                "Invalid {} {}, it must be a positive number of seconds",
+                field, step
+            ),
+            Error::StereoOffscreen => write!(
+                fmt,
+                "Quad buffered stereo can't render to the offscreen target used when \
+                 running headless, use a side by side or anaglyph fallback"
+            ),
+            Error::Framebuffer(ref e) => e.fmt(fmt),
+            Error::Shader(ref e) => e.fmt(fmt),
+            Error::ShaderLoad(ref e) => e.fmt(fmt),
+            Error::Program(ref e) => e.fmt(fmt),
+            Error::Uniform(ref e) => e.fmt(fmt),
+            Error::Object(ref e) => e.fmt(fmt),
+            Error::Ktx(ref e) => e.fmt(fmt),
+            Error::Capture(ref e) => e.fmt(fmt),
+            Error::Replay(ref e) => e.fmt(fmt),
+            Error::File(ref path, ref e) => write!(fmt, "Error loading '{}': {}", path, e),
+            Error::App(ref e) => write!(fmt, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::File(_, ref e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,413 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Gamepad input. Joysticks that GLFW's gamepad database recognises are
+//! polled once per frame and exposed through `Context::gamepads` with a
+//! standard Xbox style layout. Extra mappings in SDL's
+//! `gamecontrollerdb.txt` format can be loaded with `update_mappings` or
+//! `AppInfo::gamepad_mappings`.
+
+extern crate glfw;
+
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::os::raw::{c_int, c_uchar};
+
+pub use glfw::JoystickId;
+
+/// The GLFW 3.3 gamepad API, which glfw-rs doesn't wrap yet.
+mod ffi {
+    use std::os::raw::{c_char, c_float, c_int, c_uchar};
+
+    #[repr(C)]
+    pub struct GLFWgamepadstate {
+        pub buttons: [c_uchar; 15],
+        pub axes: [c_float; 6],
+    }
+
+    extern "C" {
+        pub fn glfwJoystickIsGamepad(jid: c_int) -> c_int;
+        pub fn glfwGetGamepadName(jid: c_int) -> *const c_char;
+        pub fn glfwGetGamepadState(jid: c_int, state: *mut GLFWgamepadstate) -> c_int;
+        pub fn glfwUpdateGamepadMappings(string: *const c_char) -> c_int;
+    }
+}
+
+const BUTTON_COUNT: usize = 15;
+const AXIS_COUNT: usize = 6;
+
+/// Gamepad buttons, named after their position on an Xbox controller.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    LeftBumper,
+    RightBumper,
+    Back,
+    Start,
+    Guide,
+    LeftThumb,
+    RightThumb,
+    DpadUp,
+    DpadRight,
+    DpadDown,
+    DpadLeft,
+}
+
+/// Stick axes range from -1 to 1 with y increasing downwards, trigger axes
+/// range from 0 when released to 1.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+const JOYSTICKS: [JoystickId; 16] = [
+    JoystickId::Joystick1,
+    JoystickId::Joystick2,
+    JoystickId::Joystick3,
+    JoystickId::Joystick4,
+    JoystickId::Joystick5,
+    JoystickId::Joystick6,
+    JoystickId::Joystick7,
+    JoystickId::Joystick8,
+    JoystickId::Joystick9,
+    JoystickId::Joystick10,
+    JoystickId::Joystick11,
+    JoystickId::Joystick12,
+    JoystickId::Joystick13,
+    JoystickId::Joystick14,
+    JoystickId::Joystick15,
+    JoystickId::Joystick16,
+];
+
+/// Adds mappings in SDL's `gamecontrollerdb.txt` format to GLFW's gamepad
+/// database. Returns false if any mapping failed to parse.
+pub fn update_mappings(mappings: &str) -> bool {
+    match CString::new(mappings) {
+        Ok(mappings) => unsafe { ffi::glfwUpdateGamepadMappings(mappings.as_ptr()) != 0 },
+        Err(_) => false,
+    }
+}
+
+/// Scales a stick so values inside the deadzone are 0 and the rest of the
+/// range still reaches 1.
+fn apply_deadzone(x: f32, y: f32, deadzone: f32) -> (f32, f32) {
+    let length = (x * x + y * y).sqrt();
+    if length <= deadzone {
+        return (0.0, 0.0);
+    }
+    let scale = ((length - deadzone) / (1.0 - deadzone)).min(1.0) / length;
+    (x * scale, y * scale)
+}
+
+/// Remaps a trigger from [-1, 1] to [0, 1] with values inside the deadzone
+/// read as 0.
+fn apply_trigger_deadzone(t: f32, deadzone: f32) -> f32 {
+    let t = (t + 1.0) * 0.5;
+    if t <= deadzone {
+        0.0
+    } else {
+        ((t - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+/// A connected gamepad and its state this frame and last frame.
+#[derive(Clone, Debug)]
+pub struct Gamepad {
+    id: JoystickId,
+    name: String,
+    buttons: [bool; BUTTON_COUNT],
+    previous: [bool; BUTTON_COUNT],
+    axes: [f32; AXIS_COUNT],
+}
+
+impl Gamepad {
+    fn new(id: JoystickId) -> Gamepad {
+        let name = unsafe {
+            let name = ffi::glfwGetGamepadName(id as c_int);
+            if name.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(name).to_string_lossy().into_owned()
+            }
+        };
+        Gamepad {
+            id,
+            name,
+            buttons: [false; BUTTON_COUNT],
+            previous: [false; BUTTON_COUNT],
+            axes: [0.0; AXIS_COUNT],
+        }
+    }
+
+    /// Reads the current state, returning false if the gamepad has gone.
+    fn poll(&mut self, deadzone: f32) -> bool {
+        let mut state = ffi::GLFWgamepadstate {
+            buttons: [0 as c_uchar; BUTTON_COUNT],
+            axes: [0.0; AXIS_COUNT],
+        };
+        if unsafe { ffi::glfwGetGamepadState(self.id as c_int, &mut state) } == 0 {
+            return false;
+        }
+        self.update(&state, deadzone);
+        true
+    }
+
+    /// Moves the current buttons to the previous frame and stores `state`.
+    fn update(&mut self, state: &ffi::GLFWgamepadstate, deadzone: f32) {
+        self.previous = self.buttons;
+        for (button, &state) in self.buttons.iter_mut().zip(state.buttons.iter()) {
+            *button = state != 0;
+        }
+        let (lx, ly) = apply_deadzone(state.axes[0], state.axes[1], deadzone);
+        let (rx, ry) = apply_deadzone(state.axes[2], state.axes[3], deadzone);
+        // triggers rest at -1
+        self.axes = [
+            lx,
+            ly,
+            rx,
+            ry,
+            apply_trigger_deadzone(state.axes[4], deadzone),
+            apply_trigger_deadzone(state.axes[5], deadzone),
+        ];
+    }
+
+    pub fn id(&self) -> JoystickId {
+        self.id
+    }
+
+    /// The name from the gamepad mapping.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true while `button` is held down.
+    pub fn is_down(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize]
+    }
+
+    /// Returns true on the frame `button` went down.
+    pub fn pressed(&self, button: GamepadButton) -> bool {
+        self.buttons[button as usize] && !self.previous[button as usize]
+    }
+
+    /// Returns true on the frame `button` was let go.
+    pub fn released(&self, button: GamepadButton) -> bool {
+        !self.buttons[button as usize] && self.previous[button as usize]
+    }
+
+    /// The value of `axis` after the deadzone is applied.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+}
+
+/// A gamepad being connected or disconnected, reported by `Gamepads::poll`.
+pub(crate) enum Connection {
+    Connected(JoystickId),
+    Disconnected(JoystickId),
+}
+
+/// All connected gamepads, in the order they were connected.
+pub struct Gamepads {
+    pads: Vec<Gamepad>,
+    deadzone: f32,
+}
+
+impl Gamepads {
+    pub(crate) fn new(deadzone: f32) -> Gamepads {
+        let mut gamepads = Gamepads {
+            pads: Vec::new(),
+            deadzone: 0.0,
+        };
+        gamepads.set_deadzone(deadzone);
+        gamepads
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<'_, Gamepad> {
+        self.pads.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pads.is_empty()
+    }
+
+    /// The first connected gamepad, which suits single player samples.
+    pub fn first(&self) -> Option<&Gamepad> {
+        self.pads.first()
+    }
+
+    pub fn get(&self, id: JoystickId) -> Option<&Gamepad> {
+        self.pads.iter().find(|pad| pad.id == id)
+    }
+
+    /// Stick and trigger values within `deadzone` of rest read as 0. The
+    /// deadzone is clamped to [0, 0.99] so the rest of the range can still be
+    /// scaled up to 1.
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+    }
+
+    pub fn deadzone(&self) -> f32 {
+        self.deadzone
+    }
+
+    /// Updates every gamepad, returning those connected or disconnected since
+    /// the last poll. Joysticks without a gamepad mapping are ignored.
+    pub(crate) fn poll(&mut self) -> Vec<Connection> {
+        let mut changes = Vec::new();
+        let deadzone = self.deadzone;
+        let mut i = 0;
+        while i < self.pads.len() {
+            if self.pads[i].poll(deadzone) {
+                i += 1;
+            } else {
+                changes.push(Connection::Disconnected(self.pads.remove(i).id));
+            }
+        }
+        for &id in JOYSTICKS.iter() {
+            let connected = self.pads.iter().any(|pad| pad.id == id);
+            if !connected && unsafe { ffi::glfwJoystickIsGamepad(id as c_int) } != 0 {
+                let mut pad = Gamepad::new(id);
+                pad.poll(deadzone);
+                // the first poll shouldn't report buttons held at connection
+                // as pressed
+                pad.previous = pad.buttons;
+                self.pads.push(pad);
+                changes.push(Connection::Connected(id));
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_deadzone, apply_trigger_deadzone, ffi, Gamepads};
+    use super::{Gamepad, GamepadAxis, GamepadButton, JoystickId, AXIS_COUNT, BUTTON_COUNT};
+
+    fn assert_near(a: (f32, f32), b: (f32, f32)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    fn pad() -> Gamepad {
+        Gamepad {
+            id: JoystickId::Joystick1,
+            name: String::new(),
+            buttons: [false; BUTTON_COUNT],
+            previous: [false; BUTTON_COUNT],
+            axes: [0.0; AXIS_COUNT],
+        }
+    }
+
+    fn state(a: bool, left_x: f32) -> ffi::GLFWgamepadstate {
+        let mut state = ffi::GLFWgamepadstate {
+            buttons: [0; BUTTON_COUNT],
+            axes: [0.0, 0.0, 0.0, 0.0, -1.0, -1.0],
+        };
+        state.buttons[GamepadButton::A as usize] = a as u8;
+        state.axes[0] = left_x;
+        state
+    }
+
+    #[test]
+    fn deadzone_zeroes_small_values() {
+        assert_eq!(apply_deadzone(0.1, 0.1, 0.2), (0.0, 0.0));
+        assert_eq!(apply_deadzone(0.2, 0.0, 0.2), (0.0, 0.0));
+        assert_eq!(apply_trigger_deadzone(-1.0, 0.2), 0.0);
+        assert_eq!(apply_trigger_deadzone(-0.7, 0.2), 0.0);
+    }
+
+    #[test]
+    fn deadzone_rescales_the_rest_of_the_range() {
+        assert_near(apply_deadzone(0.6, 0.0, 0.2), (0.5, 0.0));
+        assert_near(apply_deadzone(0.0, -1.0, 0.2), (0.0, -1.0));
+        // the direction is kept and the length capped at 1
+        assert_near(
+            apply_deadzone(1.0, 1.0, 0.2),
+            (0.5f32.sqrt(), 0.5f32.sqrt()),
+        );
+        assert!((apply_trigger_deadzone(0.2, 0.2) - 0.5).abs() < 1e-5);
+        assert_eq!(apply_trigger_deadzone(1.0, 0.2), 1.0);
+    }
+
+    #[test]
+    fn deadzone_is_clamped() {
+        let gamepads = Gamepads::new(1.0);
+        assert_eq!(gamepads.deadzone(), 0.99);
+        let (x, _) = apply_deadzone(1.0, 0.0, gamepads.deadzone());
+        assert!(x.is_finite());
+        assert!(apply_trigger_deadzone(1.0, gamepads.deadzone()).is_finite());
+
+        let mut gamepads = Gamepads::new(-0.5);
+        assert_eq!(gamepads.deadzone(), 0.0);
+        gamepads.set_deadzone(2.0);
+        assert_eq!(gamepads.deadzone(), 0.99);
+    }
+
+    #[test]
+    fn pressed_and_released_last_one_frame() {
+        let mut pad = pad();
+        let a = GamepadButton::A;
+
+        pad.update(&state(true, 0.0), 0.1);
+        assert!(pad.is_down(a) && pad.pressed(a) && !pad.released(a));
+
+        pad.update(&state(true, 0.0), 0.1);
+        assert!(pad.is_down(a) && !pad.pressed(a) && !pad.released(a));
+
+        pad.update(&state(false, 0.0), 0.1);
+        assert!(!pad.is_down(a) && !pad.pressed(a) && pad.released(a));
+
+        pad.update(&state(false, 0.0), 0.1);
+        assert!(!pad.is_down(a) && !pad.pressed(a) && !pad.released(a));
+        assert!(!pad.pressed(GamepadButton::B));
+    }
+
+    #[test]
+    fn update_applies_the_deadzone() {
+        let mut pad = pad();
+        pad.update(&state(false, 0.05), 0.1);
+        assert_eq!(pad.axis(GamepadAxis::LeftX), 0.0);
+        pad.update(&state(false, 1.0), 0.1);
+        assert_eq!(pad.axis(GamepadAxis::LeftX), 1.0);
+        assert_eq!(pad.axis(GamepadAxis::LeftTrigger), 0.0);
+    }
+}
@@ -0,0 +1,335 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+
+use gl::types::*;
+use gl_info;
+use gl_info::Feature;
+use std::backtrace::Backtrace;
+use std::ffi::CStr;
+use std::os::raw::c_void;
+use std::process;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static CHECK_ERRORS: AtomicBool = AtomicBool::new(false);
+static PANIC_ON_ERROR: AtomicBool = AtomicBool::new(false);
+/// The config used by `message_callback`, replaced by every `install`.
+static CALLBACK_CONFIG: Mutex<Option<DebugConfig>> = Mutex::new(None);
+
+/// Controls which GL debug messages are reported and what happens on errors.
+#[derive(Clone, Debug)]
+pub struct DebugConfig {
+    /// Ignore messages from these `GL_DEBUG_SOURCE_*` sources.
+    pub ignore_sources: Vec<GLenum>,
+    /// Ignore messages of these `GL_DEBUG_TYPE_*` types.
+    pub ignore_types: Vec<GLenum>,
+    /// Ignore messages with these ids, e.g. noisy driver performance notes.
+    pub ignore_ids: Vec<GLuint>,
+    /// Ignore `GL_DEBUG_SEVERITY_NOTIFICATION` messages.
+    pub ignore_notifications: bool,
+    /// Panic with a backtrace on `glGetError` errors. High severity debug
+    /// messages can't unwind out of the driver's callback, so they print the
+    /// message and a backtrace and abort instead.
+    pub panic_on_error: bool,
+    /// Check `glGetError` after sb6 calls even if KHR_debug is available.
+    pub check_errors: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        DebugConfig {
+            ignore_sources: Vec::new(),
+            ignore_types: Vec::new(),
+            ignore_ids: Vec::new(),
+            ignore_notifications: true,
+            panic_on_error: false,
+            check_errors: false,
+        }
+    }
+}
+
+impl DebugConfig {
+    fn is_ignored(&self, source: GLenum, gltype: GLenum, id: GLuint, severity: GLenum) -> bool {
+        (self.ignore_notifications && severity == gl::DEBUG_SEVERITY_NOTIFICATION)
+            || self.ignore_sources.contains(&source)
+            || self.ignore_types.contains(&gltype)
+            || self.ignore_ids.contains(&id)
+    }
+}
+
+pub fn source_name(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "Window System",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "Shader Compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "Third Party",
+        gl::DEBUG_SOURCE_APPLICATION => "Application",
+        _ => "Other",
+    }
+}
+
+pub fn type_name(gltype: GLenum) -> &'static str {
+    match gltype {
+        gl::DEBUG_TYPE_ERROR => "Error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "Deprecated Behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "Undefined Behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "Portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "Performance",
+        gl::DEBUG_TYPE_MARKER => "Marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "Push Group",
+        gl::DEBUG_TYPE_POP_GROUP => "Pop Group",
+        _ => "Other",
+    }
+}
+
+pub fn error_name(error: GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown GL error",
+    }
+}
+
+extern "system" fn message_callback(
+    source: GLenum,
+    gltype: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    let abort_on_error = {
+        let config = CALLBACK_CONFIG.lock().unwrap_or_else(|e| e.into_inner());
+        match *config {
+            Some(ref config) if !config.is_ignored(source, gltype, id, severity) => {
+                config.panic_on_error
+            }
+            _ => return,
+        }
+    };
+
+    let message = unsafe {
+        if length < 0 {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        } else {
+            let bytes = slice::from_raw_parts(message as *const u8, length as usize);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    };
+    let source = source_name(source);
+    let gltype = type_name(gltype);
+
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => {
+            if abort_on_error {
+                // panicking here would unwind through the driver
+                eprintln!(
+                    "GL {} {} {}: {}\n{}",
+                    source,
+                    gltype,
+                    id,
+                    message,
+                    Backtrace::force_capture()
+                );
+                process::abort();
+            }
+            error!("GL {} {} {}: {}", source, gltype, id, message)
+        }
+        gl::DEBUG_SEVERITY_MEDIUM => warn!("GL {} {} {}: {}", source, gltype, id, message),
+        gl::DEBUG_SEVERITY_LOW => info!("GL {} {} {}: {}", source, gltype, id, message),
+        _ => debug!("GL {} {} {}: {}", source, gltype, id, message),
+    }
+}
+
+fn has_khr_debug() -> bool {
+    gl_info::supports(Feature::DebugOutput) && gl::DebugMessageCallback::is_loaded()
+}
+
+/// Installs a debug message callback that routes GL messages to the `log`
+/// crate by severity. Returns false if KHR_debug is not available, in which
+/// case `glGetError` checking is enabled instead.
+pub fn install(config: &DebugConfig) -> bool {
+    if config.check_errors {
+        enable_error_checks(config.panic_on_error);
+    }
+
+    if !has_khr_debug() {
+        enable_error_checks(config.panic_on_error);
+        return false;
+    }
+
+    // every context shares the one callback config, so installing with a
+    // new config changes the filtering for contexts installed earlier
+    *CALLBACK_CONFIG.lock().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // report messages on the thread and in the call that caused them so
+        // backtraces point at the offending call
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageControl(
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            0,
+            ptr::null(),
+            gl::TRUE,
+        );
+        gl::DebugMessageCallback(message_callback, ptr::null());
+    }
+    true
+}
+
+/// Enables `glGetError` checking in `check_errors`, for contexts without
+/// KHR_debug.
+pub fn enable_error_checks(panic_on_error: bool) {
+    CHECK_ERRORS.store(true, Ordering::Relaxed);
+    PANIC_ON_ERROR.store(panic_on_error, Ordering::Relaxed);
+}
+
+/// If `glGetError` checking is enabled, logs every pending GL error against
+/// `context` and returns false if there were any.
+pub fn check_errors(context: &str) -> bool {
+    if !CHECK_ERRORS.load(Ordering::Relaxed) {
+        return true;
+    }
+    let mut ok = true;
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+        ok = false;
+        if PANIC_ON_ERROR.load(Ordering::Relaxed) {
+            panic!(
+                "{} in {}\n{}",
+                error_name(error),
+                context,
+                Backtrace::force_capture()
+            );
+        }
+        error!("{} in {}", error_name(error), context);
+    }
+    ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_ignored(config: &DebugConfig, source: GLenum, gltype: GLenum, id: GLuint) -> bool {
+        config.is_ignored(source, gltype, id, gl::DEBUG_SEVERITY_HIGH)
+    }
+
+    #[test]
+    fn notifications_ignored_by_default() {
+        let config = DebugConfig::default();
+        let (source, gltype) = (gl::DEBUG_SOURCE_API, gl::DEBUG_TYPE_OTHER);
+        assert!(config.is_ignored(source, gltype, 1, gl::DEBUG_SEVERITY_NOTIFICATION));
+        assert!(!config.is_ignored(source, gltype, 1, gl::DEBUG_SEVERITY_LOW));
+        assert!(!is_ignored(&config, source, gltype, 1));
+
+        let config = DebugConfig {
+            ignore_notifications: false,
+            ..DebugConfig::default()
+        };
+        assert!(!config.is_ignored(source, gltype, 1, gl::DEBUG_SEVERITY_NOTIFICATION));
+    }
+
+    #[test]
+    fn ignore_sources() {
+        let config = DebugConfig {
+            ignore_sources: vec![gl::DEBUG_SOURCE_SHADER_COMPILER],
+            ..DebugConfig::default()
+        };
+        let gltype = gl::DEBUG_TYPE_ERROR;
+        assert!(is_ignored(
+            &config,
+            gl::DEBUG_SOURCE_SHADER_COMPILER,
+            gltype,
+            1
+        ));
+        assert!(!is_ignored(&config, gl::DEBUG_SOURCE_API, gltype, 1));
+    }
+
+    #[test]
+    fn ignore_types() {
+        let config = DebugConfig {
+            ignore_types: vec![gl::DEBUG_TYPE_PERFORMANCE, gl::DEBUG_TYPE_PORTABILITY],
+            ..DebugConfig::default()
+        };
+        let source = gl::DEBUG_SOURCE_API;
+        assert!(is_ignored(&config, source, gl::DEBUG_TYPE_PERFORMANCE, 1));
+        assert!(is_ignored(&config, source, gl::DEBUG_TYPE_PORTABILITY, 1));
+        assert!(!is_ignored(&config, source, gl::DEBUG_TYPE_ERROR, 1));
+    }
+
+    #[test]
+    fn ignore_ids() {
+        let config = DebugConfig {
+            ignore_ids: vec![131185],
+            ..DebugConfig::default()
+        };
+        let (source, gltype) = (gl::DEBUG_SOURCE_API, gl::DEBUG_TYPE_OTHER);
+        assert!(is_ignored(&config, source, gltype, 131185));
+        assert!(!is_ignored(&config, source, gltype, 131186));
+    }
+
+    #[test]
+    fn any_matching_filter_ignores() {
+        let config = DebugConfig {
+            ignore_sources: vec![gl::DEBUG_SOURCE_THIRD_PARTY],
+            ignore_ids: vec![7],
+            ..DebugConfig::default()
+        };
+        // an ignored id is ignored whatever its source, and the other way round
+        assert!(is_ignored(
+            &config,
+            gl::DEBUG_SOURCE_API,
+            gl::DEBUG_TYPE_ERROR,
+            7
+        ));
+        assert!(is_ignored(
+            &config,
+            gl::DEBUG_SOURCE_THIRD_PARTY,
+            gl::DEBUG_TYPE_ERROR,
+            8
+        ));
+        assert!(!is_ignored(
+            &config,
+            gl::DEBUG_SOURCE_API,
+            gl::DEBUG_TYPE_ERROR,
+            8
+        ));
+    }
+}
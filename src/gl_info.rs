@@ -0,0 +1,277 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Information about the OpenGL context the app is running on, and queries
+//! for optional features that depend on the version or extensions.
+
+extern crate gl;
+
+use gl::types::*;
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Optional features an app can check for before using them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Feature {
+    /// `glDraw*BaseInstance`, core in 4.2.
+    BaseInstance,
+    /// Geometry shaders, core in 3.2. GL_ARB_geometry_shader4 isn't accepted
+    /// as its API differs from the core feature.
+    GeometryShader,
+    /// Tessellation control and evaluation shaders, core in 4.0.
+    Tessellation,
+    /// Compute shaders, core in 4.3.
+    ComputeShader,
+    /// Shader storage buffers, core in 4.3.
+    ShaderStorageBuffer,
+    /// `GL_TIME_ELAPSED` and `GL_TIMESTAMP` queries, core in 3.3.
+    TimerQuery,
+    /// Debug message callbacks, core in 4.3.
+    DebugOutput,
+}
+
+impl Feature {
+    /// The core version that includes the feature and the extensions that
+    /// provide it on older versions.
+    fn requirements(self) -> ((u32, u32), &'static [&'static str]) {
+        match self {
+            Feature::BaseInstance => ((4, 2), &["GL_ARB_base_instance"]),
+            Feature::GeometryShader => ((3, 2), &[]),
+            Feature::Tessellation => ((4, 0), &["GL_ARB_tessellation_shader"]),
+            Feature::ComputeShader => ((4, 3), &["GL_ARB_compute_shader"]),
+            Feature::ShaderStorageBuffer => ((4, 3), &["GL_ARB_shader_storage_buffer_object"]),
+            Feature::TimerQuery => ((3, 3), &["GL_ARB_timer_query"]),
+            Feature::DebugOutput => ((4, 3), &["GL_KHR_debug"]),
+        }
+    }
+
+    fn bit(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+const FEATURES: [Feature; 7] = [
+    Feature::BaseInstance,
+    Feature::GeometryShader,
+    Feature::Tessellation,
+    Feature::ComputeShader,
+    Feature::ShaderStorageBuffer,
+    Feature::TimerQuery,
+    Feature::DebugOutput,
+];
+
+/// Features of the current context, so that code without access to the
+/// `Context` such as `Object` can check them.
+static SUPPORTED: AtomicU32 = AtomicU32::new(0);
+
+/// Returns true if the current context supports `feature`. Only valid once
+/// `run` has created the context.
+pub fn supports(feature: Feature) -> bool {
+    SUPPORTED.load(Ordering::Relaxed) & feature.bit() != 0
+}
+
+#[derive(Clone, Debug)]
+pub struct GlInfo {
+    pub major_version: u32,
+    pub minor_version: u32,
+    pub version: String,
+    pub shading_language_version: String,
+    pub vendor: String,
+    pub renderer: String,
+    pub extensions: Vec<String>,
+}
+
+fn get_string(name: GLenum) -> String {
+    unsafe {
+        let s = gl::GetString(name);
+        if s.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(s as *const _).to_string_lossy().into_owned()
+        }
+    }
+}
+
+impl GlInfo {
+    /// Queries the current context.
+    pub fn query() -> GlInfo {
+        let (mut major, mut minor, mut num_extensions) = (0, 0, 0);
+        let mut extensions = Vec::new();
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions);
+            for i in 0..num_extensions as GLuint {
+                let name = gl::GetStringi(gl::EXTENSIONS, i);
+                if !name.is_null() {
+                    extensions.push(
+                        CStr::from_ptr(name as *const _)
+                            .to_string_lossy()
+                            .into_owned(),
+                    );
+                }
+            }
+        }
+        GlInfo {
+            major_version: major as u32,
+            minor_version: minor as u32,
+            version: get_string(gl::VERSION),
+            shading_language_version: get_string(gl::SHADING_LANGUAGE_VERSION),
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            extensions,
+        }
+    }
+
+    pub fn version_at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major_version, self.minor_version) >= (major, minor)
+    }
+
+    /// The GLSL version matching the context version, e.g. 330 for OpenGL
+    /// 3.3 and 450 for 4.5.
+    pub fn glsl_version(&self) -> u32 {
+        match (self.major_version, self.minor_version) {
+            (3, 0) => 130,
+            (3, 1) => 140,
+            (3, 2) => 150,
+            (major, minor) => major * 100 + minor * 10,
+        }
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|e| e == name)
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        let ((major, minor), extensions) = feature.requirements();
+        self.version_at_least(major, minor) || extensions.iter().any(|e| self.has_extension(e))
+    }
+
+    /// Logs the version, vendor and renderer, and the extensions at debug
+    /// level.
+    pub fn report(&self) {
+        info!(
+            "OpenGL {}.{} context: {}",
+            self.major_version, self.minor_version, self.version
+        );
+        info!("Vendor: {}, renderer: {}", self.vendor, self.renderer);
+        info!("GLSL version: {}", self.shading_language_version);
+        let missing: Vec<String> = FEATURES
+            .iter()
+            .filter(|f| !self.supports(**f))
+            .map(|f| format!("{:?}", f))
+            .collect();
+        if !missing.is_empty() {
+            info!("Unsupported features: {}", missing.join(", "));
+        }
+        debug!(
+            "{} extensions: {}",
+            self.extensions.len(),
+            self.extensions.join(" ")
+        );
+    }
+
+    /// Makes this the context checked by `gl_info::supports`.
+    pub(crate) fn make_current(&self) {
+        let bits = FEATURES
+            .iter()
+            .filter(|f| self.supports(**f))
+            .fold(0, |bits, f| bits | f.bit());
+        SUPPORTED.store(bits, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gl_info(major_version: u32, minor_version: u32, extensions: &[&str]) -> GlInfo {
+        GlInfo {
+            major_version,
+            minor_version,
+            version: String::new(),
+            shading_language_version: String::new(),
+            vendor: String::new(),
+            renderer: String::new(),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn version_at_least() {
+        let info = gl_info(4, 1, &[]);
+        assert!(info.version_at_least(3, 3));
+        assert!(info.version_at_least(4, 0));
+        assert!(info.version_at_least(4, 1));
+        assert!(!info.version_at_least(4, 2));
+        assert!(!info.version_at_least(5, 0));
+    }
+
+    #[test]
+    fn glsl_version() {
+        let versions = [
+            ((3, 0), 130),
+            ((3, 1), 140),
+            ((3, 2), 150),
+            ((3, 3), 330),
+            ((4, 0), 400),
+            ((4, 5), 450),
+            ((4, 6), 460),
+        ];
+        for &((major, minor), glsl) in versions.iter() {
+            assert_eq!(gl_info(major, minor, &[]).glsl_version(), glsl);
+        }
+    }
+
+    #[test]
+    fn geometry_shaders_from_3_2() {
+        assert!(gl_info(3, 2, &[]).supports(Feature::GeometryShader));
+        assert!(!gl_info(3, 1, &[]).supports(Feature::GeometryShader));
+        // the ARB extension's API differs from the core one
+        let info = gl_info(3, 1, &["GL_ARB_geometry_shader4"]);
+        assert!(!info.supports(Feature::GeometryShader));
+    }
+
+    #[test]
+    fn supports_by_version_or_extension() {
+        let info = gl_info(3, 3, &["GL_ARB_tessellation_shader", "GL_KHR_debug"]);
+        assert!(info.supports(Feature::TimerQuery));
+        assert!(info.supports(Feature::Tessellation));
+        assert!(info.supports(Feature::DebugOutput));
+        assert!(!info.supports(Feature::BaseInstance));
+        assert!(!info.supports(Feature::ComputeShader));
+
+        let info = gl_info(4, 3, &[]);
+        for feature in FEATURES.iter() {
+            assert!(info.supports(*feature), "{:?}", feature);
+        }
+    }
+
+    #[test]
+    fn has_extension_matches_whole_names() {
+        let info = gl_info(3, 3, &["GL_ARB_timer_query"]);
+        assert!(info.has_extension("GL_ARB_timer_query"));
+        assert!(!info.has_extension("GL_ARB_timer"));
+    }
+}
@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+
+use gl::types::*;
+
+/// The GL state changed by the built-in overlays, saved before they draw
+/// over the app's frame and restored afterwards so the app's next frame is
+/// unaffected.
+pub(crate) struct SavedState {
+    viewport: [GLint; 4],
+    capabilities: [(GLenum, bool); 6],
+    blend_func: [GLint; 4],
+    depth_func: GLint,
+    depth_mask: GLboolean,
+    program: GLint,
+    vertex_array: GLint,
+    array_buffer: GLint,
+    draw_framebuffer: GLint,
+    active_texture: GLint,
+    texture_2d: GLint,
+}
+
+const CAPABILITIES: [GLenum; 6] = [
+    gl::BLEND,
+    gl::CULL_FACE,
+    gl::DEPTH_TEST,
+    gl::SCISSOR_TEST,
+    gl::STENCIL_TEST,
+    gl::FRAMEBUFFER_SRGB,
+];
+
+impl SavedState {
+    pub(crate) fn save() -> SavedState {
+        let mut state = SavedState {
+            viewport: [0; 4],
+            capabilities: [(0, false); 6],
+            blend_func: [0; 4],
+            depth_func: 0,
+            depth_mask: 0,
+            program: 0,
+            vertex_array: 0,
+            array_buffer: 0,
+            draw_framebuffer: 0,
+            active_texture: 0,
+            texture_2d: 0,
+        };
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, state.viewport.as_mut_ptr());
+            for (saved, &cap) in state.capabilities.iter_mut().zip(CAPABILITIES.iter()) {
+                *saved = (cap, gl::IsEnabled(cap) == gl::TRUE);
+            }
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut state.blend_func[0]);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut state.blend_func[1]);
+            gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut state.blend_func[2]);
+            gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut state.blend_func[3]);
+            gl::GetIntegerv(gl::DEPTH_FUNC, &mut state.depth_func);
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut state.depth_mask);
+            gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut state.program);
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut state.vertex_array);
+            gl::GetIntegerv(gl::ARRAY_BUFFER_BINDING, &mut state.array_buffer);
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut state.draw_framebuffer);
+            gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut state.active_texture);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut state.texture_2d);
+        }
+        state
+    }
+
+    /// Sets up state for drawing blended 2D geometry over the frame in
+    /// `framebuffer`.
+    pub(crate) fn set_overlay_state(framebuffer: GLuint) {
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, framebuffer);
+            for &cap in CAPABILITIES.iter() {
+                gl::Disable(cap);
+            }
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+
+    pub(crate) fn restore(&self) {
+        unsafe {
+            gl::Viewport(
+                self.viewport[0],
+                self.viewport[1],
+                self.viewport[2],
+                self.viewport[3],
+            );
+            for &(cap, enabled) in self.capabilities.iter() {
+                if enabled {
+                    gl::Enable(cap);
+                } else {
+                    gl::Disable(cap);
+                }
+            }
+            gl::BlendFuncSeparate(
+                self.blend_func[0] as GLenum,
+                self.blend_func[1] as GLenum,
+                self.blend_func[2] as GLenum,
+                self.blend_func[3] as GLenum,
+            );
+            gl::DepthFunc(self.depth_func as GLenum);
+            gl::DepthMask(self.depth_mask);
+            gl::UseProgram(self.program as GLuint);
+            gl::BindVertexArray(self.vertex_array as GLuint);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.array_buffer as GLuint);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.draw_framebuffer as GLuint);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture_2d as GLuint);
+            gl::ActiveTexture(self.active_texture as GLenum);
+        }
+    }
+}
@@ -0,0 +1,405 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+use app::AppInfo;
+use capture::{CaptureError, Image};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug)]
+pub enum GoldenError {
+    ExampleNotFound(PathBuf),
+    ExampleFailed(String),
+    MissingReference(PathBuf),
+    /// A file the example needs is not in the media root, usually because
+    /// the media pack isn't installed. `check` skips the example.
+    MissingMedia(PathBuf),
+    SizeMismatch((u32, u32), (u32, u32)),
+    Mismatch(Comparison, PathBuf),
+    CaptureError(CaptureError),
+    IoError(io::Error),
+}
+
+impl From<CaptureError> for GoldenError {
+    fn from(e: CaptureError) -> GoldenError {
+        GoldenError::CaptureError(e)
+    }
+}
+
+impl From<io::Error> for GoldenError {
+    fn from(e: io::Error) -> GoldenError {
+        GoldenError::IoError(e)
+    }
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GoldenError::ExampleNotFound(ref path) => {
+                write!(fmt, "Example binary '{}' not found", path.display())
+            }
+            GoldenError::ExampleFailed(ref e) => write!(fmt, "Example failed: {}", e),
+            GoldenError::MissingReference(ref path) => write!(
+                fmt,
+                "Reference image '{}' not found, run with SB6_BLESS=1 to create it",
+                path.display()
+            ),
+            GoldenError::MissingMedia(ref path) => {
+                write!(fmt, "Media file '{}' not found", path.display())
+            }
+            GoldenError::SizeMismatch(actual, expected) => write!(
+                fmt,
+                "Image size {}x{} does not match reference size {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            GoldenError::Mismatch(ref c, ref dir) => write!(
+                fmt,
+                "{} pixels differ (max difference {}), see output in '{}'",
+                c.mismatched,
+                c.max_difference,
+                dir.display()
+            ),
+            GoldenError::CaptureError(ref e) => e.fmt(fmt),
+            GoldenError::IoError(ref e) => e.fmt(fmt),
+        }
+    }
+}
+
+/// The result of comparing two images.
+#[derive(Debug)]
+pub struct Comparison {
+    /// Number of pixels with a channel differing by more than the tolerance.
+    pub mismatched: usize,
+    /// Largest difference of any channel.
+    pub max_difference: u8,
+    /// Mismatched pixels in red over a dimmed copy of the expected image.
+    pub diff: Image,
+}
+
+/// Compares two images channel by channel, treating differences up to
+/// `tolerance` as equal.
+pub fn compare(actual: &Image, expected: &Image, tolerance: u8) -> Result<Comparison, GoldenError> {
+    if actual.width != expected.width || actual.height != expected.height {
+        return Err(GoldenError::SizeMismatch(
+            (actual.width, actual.height),
+            (expected.width, expected.height),
+        ));
+    }
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(expected.pixels.len());
+    for (a, e) in actual.pixels.chunks(4).zip(expected.pixels.chunks(4)) {
+        let difference = a
+            .iter()
+            .zip(e.iter())
+            .map(|(&a, &e)| a.abs_diff(e))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (u32::from(e[0]) * 3 + u32::from(e[1]) * 6 + u32::from(e[2])) / 10;
+            let dimmed = (luma / 4) as u8;
+            diff.extend_from_slice(&[dimmed, dimmed, dimmed, 255]);
+        }
+    }
+
+    Ok(Comparison {
+        mismatched,
+        max_difference,
+        diff: Image {
+            width: expected.width,
+            height: expected.height,
+            pixels: diff,
+        },
+    })
+}
+
+/// A golden image check for one example. The example binary is run headless
+/// with a fixed time step, one frame is captured and compared against the
+/// reference PPM `<example>.ppm` in the reference directory. On failure the
+/// captured frame and a diff image are written to `target/<profile>/golden`.
+/// Set `SB6_BLESS=1` to write the references from the current output instead.
+pub struct Golden {
+    example: String,
+    reference_dir: PathBuf,
+    media_root: Option<PathBuf>,
+    media: Vec<String>,
+    frame: u32,
+    size: (u32, u32),
+    time_step: f64,
+    tolerance: u8,
+    max_mismatched: usize,
+}
+
+impl Golden {
+    /// Checks the example binary called `example` against the reference in
+    /// `reference_dir`, by default comparing frame 30 rendered at 60 frames
+    /// per second in a 200x150 window. Tests usually build the directory from
+    /// `CARGO_MANIFEST_DIR` so they don't depend on the working directory.
+    pub fn new<P: Into<PathBuf>>(example: &str, reference_dir: P) -> Golden {
+        Golden {
+            example: String::from(example),
+            reference_dir: reference_dir.into(),
+            media_root: None,
+            media: Vec::new(),
+            frame: 30,
+            size: (200, 150),
+            time_step: 1.0 / 60.0,
+            tolerance: 2,
+            max_mismatched: 0,
+        }
+    }
+
+    /// The frame to capture, counting from 0.
+    pub fn frame(mut self, frame: u32) -> Golden {
+        self.frame = frame;
+        self
+    }
+
+    /// Size of the rendered frame. Kept small so the references stay small.
+    pub fn size(mut self, width: u32, height: u32) -> Golden {
+        self.size = (width, height);
+        self
+    }
+
+    /// Simulated seconds per frame.
+    pub fn time_step(mut self, time_step: f64) -> Golden {
+        self.time_step = time_step;
+        self
+    }
+
+    /// Largest per channel difference still treated as a match.
+    pub fn tolerance(mut self, tolerance: u8) -> Golden {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Number of mismatched pixels allowed before the check fails.
+    pub fn max_mismatched(mut self, max_mismatched: usize) -> Golden {
+        self.max_mismatched = max_mismatched;
+        self
+    }
+
+    /// The media root passed to the example as `SB6_MEDIA_ROOT`. Without it
+    /// the example uses `SB6_MEDIA_ROOT` from the environment or its default,
+    /// relative to the working directory.
+    pub fn media_root<P: Into<PathBuf>>(mut self, media_root: P) -> Golden {
+        self.media_root = Some(media_root.into());
+        self
+    }
+
+    /// A file under the media root the example loads. If any are missing the
+    /// check fails with `MissingMedia` before running the example, and
+    /// `check` skips it, so samples using the downloaded media pack can be
+    /// tested where it is installed.
+    pub fn requires_media(mut self, path: &str) -> Golden {
+        self.media.push(String::from(path));
+        self
+    }
+
+    /// Examples are built next to the test binaries, in `target/<profile>/examples`.
+    fn example_path(&self) -> Result<PathBuf, GoldenError> {
+        let exe = try!(env::current_exe());
+        let mut path = PathBuf::from(exe.parent().and_then(|p| p.parent()).unwrap_or(&exe));
+        path.push("examples");
+        path.push(&self.example);
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        if !path.exists() {
+            return Err(GoldenError::ExampleNotFound(path));
+        }
+        Ok(path)
+    }
+
+    fn output_dir(&self) -> Result<PathBuf, GoldenError> {
+        let exe = try!(env::current_exe());
+        let mut dir = PathBuf::from(exe.parent().and_then(|p| p.parent()).unwrap_or(&exe));
+        dir.push("golden");
+        try!(fs::create_dir_all(&dir));
+        Ok(dir)
+    }
+
+    /// Runs the example and returns the captured frame.
+    pub fn capture(&self, path: &Path) -> Result<Image, GoldenError> {
+        let example = try!(self.example_path());
+        let mut command = Command::new(&example);
+        if let Some(ref media_root) = self.media_root {
+            command.env("SB6_MEDIA_ROOT", media_root);
+        }
+        let output = try!(command
+            .env("SB6_HEADLESS", "true")
+            .env("SB6_WIDTH", self.size.0.to_string())
+            .env("SB6_HEIGHT", self.size.1.to_string())
+            .env("SB6_MAX_FRAMES", (self.frame + 1).to_string())
+            .env("SB6_TIME_STEP", self.time_step.to_string())
+            .env("SB6_CAPTURE_FRAME", self.frame.to_string())
+            .env("SB6_CAPTURE_PATH", path)
+            .output());
+        if !output.status.success() {
+            return Err(GoldenError::ExampleFailed(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+        Ok(try!(Image::load_ppm(&path.to_string_lossy())))
+    }
+
+    /// Runs the example and compares the captured frame with the reference.
+    pub fn run(&self) -> Result<(), GoldenError> {
+        // where the example will look, see `AppInfo::media_root`
+        let media_root = match self.media_root {
+            Some(ref media_root) => media_root.clone(),
+            None => env::var_os("SB6_MEDIA_ROOT")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(AppInfo::default().media_root)),
+        };
+        for file in &self.media {
+            let path = media_root.join(file);
+            if !path.is_file() {
+                return Err(GoldenError::MissingMedia(path));
+            }
+        }
+
+        let output_dir = try!(self.output_dir());
+        let actual_path = output_dir.join(format!("{}.actual.ppm", self.example));
+        let actual = try!(self.capture(&actual_path));
+
+        let reference_path = self.reference_dir.join(format!("{}.ppm", self.example));
+        if env::var("SB6_BLESS").is_ok() {
+            try!(fs::create_dir_all(&self.reference_dir));
+            try!(actual.save_ppm(&reference_path.to_string_lossy()));
+            return Ok(());
+        }
+        if !reference_path.exists() {
+            return Err(GoldenError::MissingReference(reference_path));
+        }
+
+        let expected = try!(Image::load_ppm(&reference_path.to_string_lossy()));
+        let comparison = try!(compare(&actual, &expected, self.tolerance));
+        if comparison.mismatched > self.max_mismatched {
+            let diff_path = output_dir.join(format!("{}.diff.ppm", self.example));
+            try!(comparison.diff.save_ppm(&diff_path.to_string_lossy()));
+            return Err(GoldenError::Mismatch(comparison, output_dir));
+        }
+        Ok(())
+    }
+
+    /// Runs the check, panicking with a description on failure. Examples
+    /// whose media files are missing are skipped with a message instead.
+    pub fn check(&self) {
+        match self.run() {
+            Ok(_) => (),
+            Err(GoldenError::MissingMedia(ref path)) => eprintln!(
+                "Skipping golden image check for '{}': '{}' not found",
+                self.example,
+                path.display()
+            ),
+            Err(e) => panic!("Golden image check for '{}' failed: {}", self.example, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, pixels: &[[u8; 4]]) -> Image {
+        Image {
+            width,
+            height,
+            pixels: pixels.iter().flat_map(|p| p.iter().cloned()).collect(),
+        }
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let a = image(2, 1, &[[10, 20, 30, 255], [200, 100, 0, 255]]);
+        let c = compare(&a, &a, 0).unwrap();
+        assert_eq!(c.mismatched, 0);
+        assert_eq!(c.max_difference, 0);
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let a = image(2, 1, &[[10, 20, 30, 255], [200, 100, 0, 255]]);
+        let b = image(2, 1, &[[12, 20, 30, 255], [200, 98, 1, 255]]);
+        let c = compare(&a, &b, 2).unwrap();
+        assert_eq!(c.mismatched, 0);
+        assert_eq!(c.max_difference, 2);
+
+        let c = compare(&a, &b, 1).unwrap();
+        assert_eq!(c.mismatched, 2);
+    }
+
+    #[test]
+    fn counts_mismatched_pixels_not_channels() {
+        let a = image(3, 1, &[[0, 0, 0, 255], [0, 0, 0, 255], [0, 0, 0, 255]]);
+        let b = image(3, 1, &[[90, 90, 90, 255], [0, 0, 0, 255], [0, 0, 0, 0]]);
+        let c = compare(&a, &b, 2).unwrap();
+        assert_eq!(c.mismatched, 2);
+        assert_eq!(c.max_difference, 255);
+    }
+
+    #[test]
+    fn diff_image_marks_mismatches() {
+        let expected = image(2, 1, &[[200, 200, 200, 255], [40, 80, 120, 255]]);
+        let actual = image(2, 1, &[[200, 200, 200, 255], [0, 0, 0, 255]]);
+        let c = compare(&actual, &expected, 2).unwrap();
+        assert_eq!((c.diff.width, c.diff.height), (2, 1));
+        // matching pixels are a dimmed grey of the expected luma
+        assert_eq!(&c.diff.pixels[..4], &[50, 50, 50, 255]);
+        assert_eq!(&c.diff.pixels[4..], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let a = image(2, 1, &[[0; 4], [0; 4]]);
+        let b = image(1, 2, &[[0; 4], [0; 4]]);
+        match compare(&a, &b, 0) {
+            Err(GoldenError::SizeMismatch((2, 1), (1, 2))) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_media_is_reported_before_running() {
+        let golden = Golden::new("no_such_example", "references")
+            .media_root("no_such_media")
+            .requires_media("objects/dragon.sbm");
+        match golden.run() {
+            Err(GoldenError::MissingMedia(ref path)) => {
+                assert_eq!(path, &Path::new("no_such_media").join("objects/dragon.sbm"))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,492 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Programs that rebuild themselves when their shader files change.
+//!
+//! Each `Context` has a `ProgramRegistry`, whose preprocessor searches the
+//! `shaders` directory under `AppInfo::media_root` for includes. Programs
+//! loaded through it are
+//! polled once a frame: when any of their source files, including files
+//! pulled in with `#include`, has a new modification time the program is
+//! compiled and linked again. If that fails the error is logged and the last
+//! good program stays in use, otherwise `App::on_programs_reloaded` is called
+//! so the app can look up its uniform locations again.
+//!
+//! Variants of a program built with different `#define`s are loaded with
+//! `ProgramRegistry::load_variant`. Each combination of files and defines is
+//! built once, so switching between variants every frame is cheap. Files
+//! holding every stage behind `#pragma stage` markers are loaded with
+//! `ProgramRegistry::load_file`.
+//!
+//! ```ignore
+//! let vs = ctx.media_path("shaders/ssao/render.vs.glsl");
+//! let fs = ctx.media_path("shaders/ssao/render.fs.glsl");
+//! self.program = try!(ctx
+//!     .programs_mut()
+//!     .load(&[(&vs, gl::VERTEX_SHADER), (&fs, gl::FRAGMENT_SHADER)]));
+//! gl::UseProgram(ctx.programs().get(self.program));
+//! ```
+
+extern crate gl;
+
+use error::Error;
+use gl::types::*;
+use preprocessor::Preprocessor;
+use program;
+use shader;
+use shader::LoadError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Identifies a program in a `ProgramRegistry`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ProgramId(usize);
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: PathBuf) -> WatchedFile {
+        let modified = modified_time(&path);
+        WatchedFile { path, modified }
+    }
+
+    fn changed(&self) -> bool {
+        modified_time(&self.path) != self.modified
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Where a program's shaders come from.
+#[derive(Clone, PartialEq, Debug)]
+enum Sources {
+    /// One file per stage, paired with its shader type.
+    Stages(Vec<(String, GLenum)>),
+    /// One file holding every stage, see `program::load`.
+    Combined(String),
+}
+
+impl Sources {
+    /// The first file, to name the program in messages.
+    fn name(&self) -> &str {
+        match *self {
+            Sources::Stages(ref stages) => stages.first().map_or("", |stage| stage.0.as_str()),
+            Sources::Combined(ref path) => path,
+        }
+    }
+}
+
+struct Entry {
+    sources: Sources,
+    defines: Vec<(String, String)>,
+    program: GLuint,
+    files: Vec<WatchedFile>,
+    /// Rebuild on the next poll even if no file changed.
+    stale: bool,
+}
+
+impl Entry {
+    fn outdated(&self) -> bool {
+        self.stale || self.files.iter().any(|file| file.changed())
+    }
+}
+
+#[derive(Default)]
+pub struct ProgramRegistry {
+    entries: Vec<Option<Entry>>,
+    preprocessor: Preprocessor,
+}
+
+/// Compiles and links `sources`, adding every file read to `files` whether
+/// or not the build succeeds so a fix to any of them triggers another
+/// attempt.
+fn build(
+    base: &Preprocessor,
+    sources: &Sources,
+    defines: &[(String, String)],
+    files: &mut Vec<PathBuf>,
+) -> Result<GLuint, Error> {
+    let mut preprocessor = base.clone();
+    for (name, value) in defines {
+        preprocessor.define(name.as_str(), value.as_str());
+    }
+    match *sources {
+        Sources::Stages(ref stages) => build_stages(&preprocessor, stages, files),
+        Sources::Combined(ref path) => build_combined(&preprocessor, path, files),
+    }
+}
+
+fn build_stages(
+    preprocessor: &Preprocessor,
+    stages: &[(String, GLenum)],
+    files: &mut Vec<PathBuf>,
+) -> Result<GLuint, Error> {
+    let mut shaders = Vec::with_capacity(stages.len());
+    for &(ref path, shader_type) in stages {
+        files.push(PathBuf::from(path));
+        match shader::load_with_source(path, shader_type, preprocessor) {
+            Ok((shader, source)) => {
+                files.extend(source.files.into_iter().skip(1));
+                shaders.push(shader);
+            }
+            Err(e) => {
+                if let LoadError::CompileError(ref e) = e {
+                    files.extend(e.source.files.iter().skip(1).cloned());
+                }
+                for shader in shaders {
+                    unsafe {
+                        gl::DeleteShader(shader);
+                    }
+                }
+                return Err(Error::file(path, e));
+            }
+        }
+    }
+    Ok(try!(program::link_from_shaders(&shaders)))
+}
+
+fn build_combined(
+    preprocessor: &Preprocessor,
+    path: &str,
+    files: &mut Vec<PathBuf>,
+) -> Result<GLuint, Error> {
+    files.push(PathBuf::from(path));
+    match shader::load_stages_with(path, preprocessor) {
+        Ok(stages) => {
+            let mut shaders = Vec::with_capacity(stages.len());
+            for (shader, source) in stages {
+                files.extend(source.files.into_iter().skip(1));
+                shaders.push(shader);
+            }
+            program::link_from_shaders(&shaders)
+                .map_err(|e| Error::from(LoadError::LinkError(PathBuf::from(path), e)))
+        }
+        Err(e) => {
+            if let LoadError::CompileError(ref e) = e {
+                files.extend(e.source.files.iter().skip(1).cloned());
+            }
+            Err(Error::file(path, e))
+        }
+    }
+}
+
+fn watch(mut files: Vec<PathBuf>) -> Vec<WatchedFile> {
+    files.sort();
+    files.dedup();
+    files.into_iter().map(WatchedFile::new).collect()
+}
+
+impl ProgramRegistry {
+    pub fn new() -> ProgramRegistry {
+        ProgramRegistry::with_preprocessor(Preprocessor::new())
+    }
+
+    /// A registry building every program with `preprocessor`, plus the
+    /// defines of each variant.
+    pub fn with_preprocessor(preprocessor: Preprocessor) -> ProgramRegistry {
+        ProgramRegistry {
+            entries: Vec::new(),
+            preprocessor,
+        }
+    }
+
+    /// The preprocessor programs are built with. Pass it to
+    /// `shader::load_with` to load single shaders with the same include
+    /// paths.
+    pub fn preprocessor(&self) -> &Preprocessor {
+        &self.preprocessor
+    }
+
+    /// Loads, compiles and links a program from shader files paired with
+    /// their shader types. Unlike a reload, a failure here is returned.
+    pub fn load(&mut self, stages: &[(&str, GLenum)]) -> Result<ProgramId, Error> {
+        self.load_variant(stages, &[])
+    }
+
+    /// Like `load`, adding `#define name value` after the `#version` of each
+    /// stage for each pair in `defines`. Returns the existing program if this
+    /// variant was loaded before.
+    pub fn load_variant(
+        &mut self,
+        stages: &[(&str, GLenum)],
+        defines: &[(&str, &str)],
+    ) -> Result<ProgramId, Error> {
+        let stages: Vec<(String, GLenum)> = stages
+            .iter()
+            .map(|&(path, shader_type)| (path.to_string(), shader_type))
+            .collect();
+        self.load_sources(Sources::Stages(stages), defines)
+    }
+
+    /// Loads a program from one file holding every stage, each starting with
+    /// a `#pragma stage` marker as for `program::load`.
+    pub fn load_file(&mut self, path: &str) -> Result<ProgramId, Error> {
+        self.load_file_variant(path, &[])
+    }
+
+    /// Like `load_file`, with `defines` as for `load_variant`.
+    pub fn load_file_variant(
+        &mut self,
+        path: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<ProgramId, Error> {
+        self.load_sources(Sources::Combined(path.to_string()), defines)
+    }
+
+    fn load_sources(
+        &mut self,
+        sources: Sources,
+        defines: &[(&str, &str)],
+    ) -> Result<ProgramId, Error> {
+        // later values win, as with repeated `Preprocessor::define` calls
+        let mut defines: Vec<(String, String)> = defines
+            .iter()
+            .rev()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        defines.sort_by(|a, b| a.0.cmp(&b.0));
+        defines.dedup_by(|a, b| a.0 == b.0);
+
+        let existing = self.entries.iter().position(|entry| match *entry {
+            Some(ref entry) => entry.sources == sources && entry.defines == defines,
+            None => false,
+        });
+        if let Some(index) = existing {
+            return Ok(ProgramId(index));
+        }
+
+        let mut files = Vec::new();
+        let program = try!(build(&self.preprocessor, &sources, &defines, &mut files));
+        self.entries.push(Some(Entry {
+            sources,
+            defines,
+            program,
+            files: watch(files),
+            stale: false,
+        }));
+        Ok(ProgramId(self.entries.len() - 1))
+    }
+
+    /// The current program for `id`, or 0 if it has been removed.
+    pub fn get(&self, id: ProgramId) -> GLuint {
+        match self.entries.get(id.0) {
+            Some(Some(entry)) => entry.program,
+            _ => 0,
+        }
+    }
+
+    /// The files `id` was built from, including any included files.
+    pub fn files(&self, id: ProgramId) -> Vec<&Path> {
+        match self.entries.get(id.0) {
+            Some(Some(entry)) => entry.files.iter().map(|f| f.path.as_path()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Deletes the program and stops watching its files.
+    pub fn remove(&mut self, id: ProgramId) {
+        if let Some(entry) = self.entries.get_mut(id.0).and_then(|entry| entry.take()) {
+            unsafe {
+                gl::DeleteProgram(entry.program);
+            }
+        }
+    }
+
+    /// Rebuilds `id` on the next poll whether or not its files changed.
+    pub fn reload(&mut self, id: ProgramId) {
+        if let Some(Some(entry)) = self.entries.get_mut(id.0) {
+            entry.stale = true;
+        }
+    }
+
+    /// Rebuilds every program on the next poll.
+    pub fn reload_all(&mut self) {
+        for entry in self.entries.iter_mut().flat_map(|entry| entry.as_mut()) {
+            entry.stale = true;
+        }
+    }
+
+    /// The programs that need rebuilding because a file changed or a reload
+    /// was asked for.
+    fn outdated(&self) -> Vec<ProgramId> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match *entry {
+                Some(ref entry) if entry.outdated() => Some(ProgramId(index)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Rebuilds programs whose files changed, returning the ones that were
+    /// replaced.
+    pub(crate) fn poll(&mut self) -> Vec<ProgramId> {
+        let mut reloaded = Vec::new();
+        for id in self.outdated() {
+            let entry = match self.entries[id.0] {
+                Some(ref mut entry) => entry,
+                None => continue,
+            };
+            entry.stale = false;
+            let mut files = Vec::new();
+            match build(
+                &self.preprocessor,
+                &entry.sources,
+                &entry.defines,
+                &mut files,
+            ) {
+                Ok(program) => {
+                    unsafe {
+                        gl::DeleteProgram(entry.program);
+                    }
+                    entry.program = program;
+                    info!("Reloaded program {}", entry.sources.name());
+                    reloaded.push(id);
+                }
+                Err(e) => error!("{}\nKeeping the last program that built", e),
+            }
+            entry.files = watch(files);
+        }
+        reloaded
+    }
+
+    /// Deletes every program.
+    pub(crate) fn free(&mut self) {
+        for entry in self.entries.drain(..).flatten() {
+            unsafe {
+                gl::DeleteProgram(entry.program);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{watch, Entry, ProgramId, ProgramRegistry, Sources, WatchedFile};
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("sb6_hot_reload_{}_{}", name, ::std::process::id()))
+    }
+
+    /// Creates `path` with a modification time `age` seconds in the past.
+    fn touch(path: &PathBuf, age: u64) {
+        let file = File::create(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    /// An entry watching `files`, without a GL program behind it.
+    fn entry(files: &[&PathBuf]) -> Option<Entry> {
+        Some(Entry {
+            sources: Sources::Combined(String::new()),
+            defines: Vec::new(),
+            program: 0,
+            files: watch(files.iter().map(|&path| path.clone()).collect()),
+            stale: false,
+        })
+    }
+
+    #[test]
+    fn watched_file_detects_changes() {
+        let path = temp_path("watched");
+        touch(&path, 100);
+        let file = WatchedFile::new(path.clone());
+        assert!(!file.changed());
+
+        touch(&path, 50);
+        assert!(file.changed());
+
+        let file = WatchedFile::new(path.clone());
+        fs::remove_file(&path).unwrap();
+        assert!(file.changed());
+
+        // a missing file that appears is a change too
+        let file = WatchedFile::new(path.clone());
+        assert!(!file.changed());
+        touch(&path, 10);
+        assert!(file.changed());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn registry_rebuilds_programs_using_changed_files() {
+        let common = temp_path("common");
+        let a = temp_path("a");
+        let b = temp_path("b");
+        for path in [&common, &a, &b].iter() {
+            touch(path, 100);
+        }
+        let mut registry = ProgramRegistry::new();
+        registry.entries.push(entry(&[&a, &common]));
+        registry.entries.push(entry(&[&b]));
+        registry.entries.push(None);
+        registry.entries.push(entry(&[&b, &common]));
+        assert!(registry.outdated().is_empty());
+
+        touch(&common, 50);
+        assert_eq!(registry.outdated(), [ProgramId(0), ProgramId(3)]);
+        registry.entries[0] = entry(&[&a, &common]);
+        registry.entries[3] = entry(&[&b, &common]);
+        assert!(registry.outdated().is_empty());
+
+        touch(&b, 50);
+        assert_eq!(registry.outdated(), [ProgramId(1), ProgramId(3)]);
+        registry.entries[1] = entry(&[&b]);
+        registry.entries[3] = entry(&[&b, &common]);
+
+        registry.reload(ProgramId(1));
+        assert_eq!(registry.outdated(), [ProgramId(1)]);
+        registry.reload_all();
+        assert_eq!(
+            registry.outdated(),
+            [ProgramId(0), ProgramId(1), ProgramId(3)]
+        );
+
+        for path in [&common, &a, &b].iter() {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn sources_are_named_by_their_first_file() {
+        let stages = Sources::Stages(vec![
+            ("a.vs.glsl".to_string(), 0),
+            ("a.fs.glsl".to_string(), 1),
+        ]);
+        assert_eq!(stages.name(), "a.vs.glsl");
+        assert_eq!(Sources::Combined("a.glsl".to_string()).name(), "a.glsl");
+    }
+}
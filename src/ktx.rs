@@ -25,6 +25,7 @@
 extern crate gl;
 
 use gl::types::*;
+use gl_debug;
 use reader::BufferReader;
 use std::fmt;
 use std::fs;
@@ -80,6 +81,19 @@ macro_rules! load_ktx_or_panic {
     };
 }
 
+/// Loads a texture, returning an `sb6::Error` naming the file from the
+/// enclosing function on failure.
+#[macro_export]
+macro_rules! try_load_ktx {
+    ($path:expr) => {{
+        let path = $path;
+        match sb6::ktx::load(path) {
+            Ok(texture) => texture,
+            Err(e) => return Err(sb6::Error::file(path, e)),
+        }
+    }};
+}
+
 const IDENTIFIER: [u8; 12] = [
     0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
 ];
@@ -325,5 +339,7 @@ pub fn load(filename: &str) -> Result<GLuint, LoadError> {
         }
     }
 
+    gl_debug::check_errors("ktx::load");
+
     Ok(tex)
 }
@@ -29,11 +29,39 @@ extern crate gl;
 extern crate glfw;
 
 pub use app::*;
+pub use context::Context;
+pub use error::Error;
+pub use gl_info::Feature;
+pub use window::RenderCallback;
 
 mod app;
+pub mod camera;
+pub mod capture;
+pub mod clock;
+mod context;
+pub mod debug_draw;
+pub mod diagnostic;
+pub mod egl;
+mod error;
+mod font8x8;
+pub mod gamepad;
+pub mod gl_debug;
+pub mod gl_info;
+mod gl_state;
+pub mod golden;
+pub mod hot_reload;
 pub mod ktx;
 pub mod object;
+pub mod offscreen;
+pub mod options;
+pub mod preprocessor;
 pub mod program;
 mod reader;
+pub mod replay;
 pub mod shader;
+pub mod stats;
+mod stats_overlay;
+pub mod stereo;
+pub mod text_overlay;
 pub mod vmath;
+mod window;
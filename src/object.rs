@@ -25,6 +25,9 @@
 extern crate gl;
 
 use gl::types::*;
+use gl_debug;
+use gl_info;
+use gl_info::Feature;
 use reader::BufferReader;
 use std::fmt;
 use std::fs;
@@ -34,6 +37,7 @@ use std::mem;
 use std::path::Path;
 use std::ptr;
 use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 macro_rules! fourcc(
     ($a:expr, $b:expr, $c:expr, $d:expr) => (
@@ -139,6 +143,20 @@ macro_rules! load_object_or_panic {
     };
 }
 
+/// Loads an object, returning an `sb6::Error` naming the file from the
+/// enclosing function on failure.
+#[macro_export]
+macro_rules! try_load_object {
+    ($obj:expr, $path:expr) => {{
+        let path = $path;
+        if let Err(e) = $obj.load(path) {
+            return Err(sb6::Error::file(path, e));
+        }
+    }};
+}
+
+static BASE_INSTANCE_WARNED: AtomicBool = AtomicBool::new(false);
+
 pub struct Object {
     vertex_buffer: GLuint,
     index_buffer: GLuint,
@@ -349,6 +367,8 @@ impl Object {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         }
 
+        gl_debug::check_errors("Object::load");
+
         Ok(())
     }
 
@@ -373,27 +393,58 @@ impl Object {
         self.render_sub_object(0, instance_count, base_instance);
     }
 
+    /// Draws `instance_count` instances of a sub-object. If base instance
+    /// draws are not supported `base_instance` must be 0, other values are
+    /// ignored with a warning.
     pub fn render_sub_object(&self, object_index: u32, instance_count: u32, base_instance: u32) {
+        let use_base_instance = if gl_info::supports(Feature::BaseInstance) {
+            true
+        } else {
+            if base_instance != 0 && !BASE_INSTANCE_WARNED.swap(true, Ordering::Relaxed) {
+                warn!("Base instance draws are not supported, ignoring base_instance");
+            }
+            false
+        };
         unsafe {
             gl::BindVertexArray(self.vao);
 
             if self.index_buffer != 0 {
-                gl::DrawElementsInstancedBaseInstance(
-                    gl::TRIANGLES,
-                    self.num_indices as i32,
-                    self.index_type,
-                    ptr::null(),
-                    instance_count as i32,
-                    base_instance,
-                );
+                if use_base_instance {
+                    gl::DrawElementsInstancedBaseInstance(
+                        gl::TRIANGLES,
+                        self.num_indices as i32,
+                        self.index_type,
+                        ptr::null(),
+                        instance_count as i32,
+                        base_instance,
+                    );
+                } else {
+                    gl::DrawElementsInstanced(
+                        gl::TRIANGLES,
+                        self.num_indices as i32,
+                        self.index_type,
+                        ptr::null(),
+                        instance_count as i32,
+                    );
+                }
             } else {
-                gl::DrawArraysInstancedBaseInstance(
-                    gl::TRIANGLES,
-                    self.sub_object[object_index as usize].first as i32,
-                    self.sub_object[object_index as usize].count as i32,
-                    instance_count as i32,
-                    base_instance,
-                );
+                let sub_object = &self.sub_object[object_index as usize];
+                if use_base_instance {
+                    gl::DrawArraysInstancedBaseInstance(
+                        gl::TRIANGLES,
+                        sub_object.first as i32,
+                        sub_object.count as i32,
+                        instance_count as i32,
+                        base_instance,
+                    );
+                } else {
+                    gl::DrawArraysInstanced(
+                        gl::TRIANGLES,
+                        sub_object.first as i32,
+                        sub_object.count as i32,
+                        instance_count as i32,
+                    );
+                }
             }
         }
     }
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+
+use gl::types::*;
+use std::fmt;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum FramebufferError {
+    Incomplete(GLenum),
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FramebufferError::Incomplete(status) => {
+                write!(fmt, "Framebuffer incomplete (status 0x{:04X})", status)
+            }
+        }
+    }
+}
+
+/// An offscreen render target with an RGBA8 color buffer and a combined
+/// depth/stencil buffer. Used in place of the window's default framebuffer
+/// when running headless.
+pub struct Offscreen {
+    fbo: GLuint,
+    color: GLuint,
+    depth_stencil: GLuint,
+    width: u32,
+    height: u32,
+}
+
+impl Offscreen {
+    pub fn new(width: u32, height: u32) -> Result<Offscreen, FramebufferError> {
+        let mut offscreen = Offscreen {
+            fbo: 0,
+            color: 0,
+            depth_stencil: 0,
+            width: 0,
+            height: 0,
+        };
+        unsafe {
+            gl::GenFramebuffers(1, &mut offscreen.fbo);
+            gl::GenRenderbuffers(1, &mut offscreen.color);
+            gl::GenRenderbuffers(1, &mut offscreen.depth_stencil);
+        }
+        if let Err(e) = offscreen.resize(width, height) {
+            offscreen.free();
+            return Err(e);
+        }
+        Ok(offscreen)
+    }
+
+    /// Reallocates the color and depth/stencil storage at the given size.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.color);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::RGBA8,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_stencil);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                self.color,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth_stencil,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(FramebufferError::Incomplete(status));
+            }
+        }
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+
+    pub fn framebuffer(&self) -> GLuint {
+        self.fbo
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        }
+    }
+
+    pub fn free(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.color);
+            gl::DeleteRenderbuffers(1, &self.depth_stencil);
+        }
+
+        self.fbo = 0;
+        self.color = 0;
+        self.depth_stencil = 0;
+        self.width = 0;
+        self.height = 0;
+    }
+}
@@ -0,0 +1,428 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Command line and environment overrides for `AppInfo`, applied by
+//! `AppInfo::from_args` so samples can be reconfigured without recompiling.
+//! Command line flags take precedence over `SB6_*` environment variables.
+
+use app::{AppInfo, StereoMode};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionsError {
+    /// `--help` or `-h` was passed.
+    Help,
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionsError::Help => write!(fmt, "Help requested"),
+            OptionsError::UnknownFlag(ref flag) => write!(fmt, "Unknown option '{}'", flag),
+            OptionsError::MissingValue(ref flag) => write!(fmt, "Missing value for '{}'", flag),
+            OptionsError::InvalidValue(ref flag, ref value) => {
+                write!(fmt, "Invalid value '{}' for '{}'", value, flag)
+            }
+        }
+    }
+}
+
+pub const USAGE: &str = "Options:
+    --width N             window width
+    --height N            window height
+    --gl-version M.N      OpenGL context version
+    --samples N           multisample count, 0 to disable
+    --fullscreen          run fullscreen on the primary monitor
+    --windowed            run in a window
+    --vsync, --no-vsync   wait for vertical blank when swapping
+    --debug, --no-debug   create a debug context
+    --frames N            exit after N frames
+    --stereo              render a separate image for each eye
+    --stereo-fallback M   side-by-side or anaglyph without quad buffers
+    --stats               show the frame time graph, toggled with F3
+    --media-root PATH     directory containing the media files
+    --headless            render offscreen without showing a window
+    --time-step SECONDS   advance time by a fixed step per frame
+    --capture-frame N     save frame N to the capture path
+    --capture-path PATH   destination for --capture-frame (.png or .ppm)
+    --record PATH         record frame times and input to a file
+    --replay PATH         replay a recording instead of live input
+    --help                print this message
+
+Each option can also be set with an environment variable, e.g. SB6_WIDTH=1920,
+SB6_VSYNC=false, SB6_GL_VERSION=3.3 or SB6_MAX_FRAMES=100.";
+
+/// Parses "true"/"false" as well as "1"/"0", "yes"/"no" and "on"/"off".
+fn parse_bool(s: &str) -> Option<bool> {
+    match &*s.to_lowercase() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a version of the form "4.3".
+fn parse_version(s: &str) -> Option<(u32, u32)> {
+    let mut parts = s.splitn(2, '.');
+    let major = parts.next().and_then(|s| s.parse().ok());
+    let minor = parts.next().unwrap_or("0").parse().ok();
+    match (major, minor) {
+        (Some(major), Some(minor)) => Some((major, minor)),
+        _ => None,
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, OptionsError> {
+    value
+        .parse()
+        .map_err(|_| OptionsError::InvalidValue(name.to_string(), value.to_string()))
+}
+
+/// Parses a value that must be greater than zero.
+fn parse_positive<T: FromStr + PartialOrd + Default>(
+    name: &str,
+    value: &str,
+) -> Result<T, OptionsError> {
+    let parsed: T = try!(parse(name, value));
+    // also rejects NaN, which doesn't compare greater than anything
+    if parsed > T::default() {
+        Ok(parsed)
+    } else {
+        Err(OptionsError::InvalidValue(
+            name.to_string(),
+            value.to_string(),
+        ))
+    }
+}
+
+/// Applies a single named option to `info`. Names are the long flag names
+/// without the leading dashes.
+fn apply(info: &mut AppInfo, name: &str, value: &str) -> Result<(), OptionsError> {
+    let flag = |v: Option<bool>| {
+        v.ok_or_else(|| OptionsError::InvalidValue(name.to_string(), value.to_string()))
+    };
+    match name {
+        "width" => info.window_width = try!(parse(name, value)),
+        "height" => info.window_height = try!(parse(name, value)),
+        "gl-version" => {
+            let version = try!(parse_version(value)
+                .ok_or_else(|| OptionsError::InvalidValue(name.to_string(), value.to_string())));
+            info.major_version = version.0;
+            info.minor_version = version.1;
+            // an explicit older version is also the new minimum
+            if version < (info.min_major_version, info.min_minor_version) {
+                info.min_major_version = version.0;
+                info.min_minor_version = version.1;
+            }
+        }
+        "samples" => info.samples = try!(parse(name, value)),
+        "fullscreen" => info.fullscreen = try!(flag(parse_bool(value))),
+        "windowed" => info.fullscreen = !try!(flag(parse_bool(value))),
+        "vsync" => info.vsync = try!(flag(parse_bool(value))),
+        "debug" => info.debug = try!(flag(parse_bool(value))),
+        "stereo" => info.stereo = try!(flag(parse_bool(value))),
+        "stereo-fallback" => {
+            info.stereo_fallback = match value {
+                "side-by-side" => StereoMode::SideBySide,
+                "anaglyph" => StereoMode::Anaglyph,
+                _ => {
+                    return Err(OptionsError::InvalidValue(
+                        name.to_string(),
+                        value.to_string(),
+                    ))
+                }
+            }
+        }
+        "stats" => info.stats_overlay = try!(flag(parse_bool(value))),
+        "frames" => info.max_frames = Some(try!(parse_positive(name, value))),
+        "media-root" => info.media_root = value.to_string(),
+        "headless" => info.headless = try!(flag(parse_bool(value))),
+        "time-step" => {
+            let step: f64 = try!(parse_positive(name, value));
+            if !step.is_finite() {
+                return Err(OptionsError::InvalidValue(
+                    name.to_string(),
+                    value.to_string(),
+                ));
+            }
+            info.time_step = Some(step);
+        }
+        "capture-frame" => info.capture_frame = Some(try!(parse(name, value))),
+        "capture-path" => info.capture_path = value.to_string(),
+        "record" => info.record_path = Some(value.to_string()),
+        "replay" => info.replay_path = Some(value.to_string()),
+        _ => return Err(OptionsError::UnknownFlag(format!("--{}", name))),
+    }
+    Ok(())
+}
+
+/// Option names and the environment variables that set them.
+const ENV_VARS: [(&str, &str); 18] = [
+    ("width", "SB6_WIDTH"),
+    ("height", "SB6_HEIGHT"),
+    ("gl-version", "SB6_GL_VERSION"),
+    ("samples", "SB6_SAMPLES"),
+    ("fullscreen", "SB6_FULLSCREEN"),
+    ("vsync", "SB6_VSYNC"),
+    ("debug", "SB6_DEBUG"),
+    ("frames", "SB6_MAX_FRAMES"),
+    ("stats", "SB6_STATS"),
+    ("stereo", "SB6_STEREO"),
+    ("stereo-fallback", "SB6_STEREO_FALLBACK"),
+    ("media-root", "SB6_MEDIA_ROOT"),
+    ("headless", "SB6_HEADLESS"),
+    ("time-step", "SB6_TIME_STEP"),
+    ("capture-frame", "SB6_CAPTURE_FRAME"),
+    ("capture-path", "SB6_CAPTURE_PATH"),
+    ("record", "SB6_RECORD"),
+    ("replay", "SB6_REPLAY"),
+];
+
+/// Applies any `SB6_*` environment variables to `info`. Invalid values are
+/// logged and ignored.
+pub fn apply_env(info: &mut AppInfo) {
+    apply_vars(info, |var| env::var(var).ok());
+}
+
+/// Applies the variables in `ENV_VARS` that `lookup` finds, returning the
+/// names of those with invalid values.
+fn apply_vars<F: Fn(&str) -> Option<String>>(info: &mut AppInfo, lookup: F) -> Vec<&'static str> {
+    let mut invalid = Vec::new();
+    for &(name, var) in ENV_VARS.iter() {
+        if let Some(value) = lookup(var) {
+            if let Err(e) = apply(info, name, &value) {
+                warn!("Ignoring {}: {}", var, e);
+                invalid.push(var);
+            }
+        }
+    }
+    invalid
+}
+
+/// Applies command line flags to `info`. Flags take a value either as the
+/// next argument or after an `=`. Boolean flags need no value, and have a
+/// `--no-` form to turn them off.
+pub fn apply_args<I: IntoIterator<Item = String>>(
+    info: &mut AppInfo,
+    args: I,
+) -> Result<(), OptionsError> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            return Err(OptionsError::UnknownFlag(arg));
+        }
+        let (name, inline_value) = match arg[2..].find('=') {
+            Some(i) => (&arg[2..i + 2], Some(arg[i + 3..].to_string())),
+            None => (&arg[2..], None),
+        };
+        let value = match name {
+            "fullscreen" | "windowed" | "vsync" | "debug" | "headless" | "stats" | "stereo" => {
+                inline_value.unwrap_or_else(|| "true".to_string())
+            }
+            _ if name.starts_with("no-") && inline_value.is_none() => {
+                try!(apply(info, &name[3..], "false")
+                    .map_err(|_| OptionsError::UnknownFlag(arg.clone())));
+                continue;
+            }
+            _ => match inline_value {
+                Some(value) => value,
+                None => try!(args
+                    .next()
+                    .ok_or_else(|| OptionsError::MissingValue(arg.clone()))),
+            },
+        };
+        try!(apply(info, name, &value));
+    }
+    Ok(())
+}
+
+/// Applies environment variables and then the process's command line to
+/// `info`. `--help` is reported as `OptionsError::Help`.
+pub fn apply_overrides(info: &mut AppInfo) -> Result<(), OptionsError> {
+    apply_env(info);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Err(OptionsError::Help);
+    }
+    apply_args(info, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<AppInfo, OptionsError> {
+        let mut info = AppInfo::default();
+        try!(apply_args(&mut info, args.iter().map(|s| s.to_string())));
+        Ok(info)
+    }
+
+    #[test]
+    fn values_and_flags() {
+        let info = args(&["--width", "320", "--height=240", "--headless", "--no-vsync"]).unwrap();
+        assert_eq!((info.window_width, info.window_height), (320, 240));
+        assert!(info.headless);
+        assert!(!info.vsync);
+
+        let info = args(&["--frames", "10", "--time-step=0.5"]).unwrap();
+        assert_eq!(info.max_frames, Some(10));
+        assert_eq!(info.time_step, Some(0.5));
+    }
+
+    #[test]
+    fn rejects_zero_frames() {
+        assert_eq!(
+            args(&["--frames", "0"]).err(),
+            Some(OptionsError::InvalidValue(
+                "frames".to_string(),
+                "0".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_non_positive_time_steps() {
+        for &step in ["0", "-0.1", "NaN", "inf", "abc"].iter() {
+            assert_eq!(
+                args(&["--time-step", step]).err(),
+                Some(OptionsError::InvalidValue(
+                    "time-step".to_string(),
+                    step.to_string()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_and_missing() {
+        assert_eq!(
+            args(&["--bogus", "1"]).err(),
+            Some(OptionsError::UnknownFlag("--bogus".to_string()))
+        );
+        assert_eq!(
+            args(&["--width"]).err(),
+            Some(OptionsError::MissingValue("--width".to_string()))
+        );
+    }
+
+    #[test]
+    fn gl_version_lowers_the_minimum() {
+        let info = args(&["--gl-version", "3.2"]).unwrap();
+        assert_eq!((info.major_version, info.minor_version), (3, 2));
+        assert_eq!((info.min_major_version, info.min_minor_version), (3, 2));
+
+        // newer versions keep the default minimum to fall back to
+        let defaults = AppInfo::default();
+        let info = args(&["--gl-version=4.5"]).unwrap();
+        assert_eq!((info.major_version, info.minor_version), (4, 5));
+        assert_eq!(
+            (info.min_major_version, info.min_minor_version),
+            (defaults.min_major_version, defaults.min_minor_version)
+        );
+
+        let info = args(&["--gl-version", "4"]).unwrap();
+        assert_eq!((info.major_version, info.minor_version), (4, 0));
+        for &version in ["four", "4.x", ""].iter() {
+            assert_eq!(
+                args(&["--gl-version", version]).err(),
+                Some(OptionsError::InvalidValue(
+                    "gl-version".to_string(),
+                    version.to_string()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn windowed() {
+        let mut info = AppInfo::default();
+        info.fullscreen = true;
+        let windowed = |info: &AppInfo, arg: &str| {
+            let mut info = info.clone();
+            apply_args(&mut info, Some(arg.to_string())).unwrap();
+            info.fullscreen
+        };
+        assert!(!windowed(&info, "--windowed"));
+        assert!(!windowed(&info, "--windowed=true"));
+        assert!(windowed(&info, "--windowed=false"));
+        info.fullscreen = false;
+        assert!(windowed(&info, "--no-windowed"));
+        assert!(windowed(&info, "--windowed=no"));
+    }
+
+    #[test]
+    fn environment_variables() {
+        let vars = [
+            ("SB6_WIDTH", "1920"),
+            ("SB6_VSYNC", "off"),
+            ("SB6_GL_VERSION", "3.3"),
+            ("SB6_MAX_FRAMES", "100"),
+            ("SB6_MEDIA_ROOT", "/data/media"),
+            ("SB6_STEREO_FALLBACK", "anaglyph"),
+            ("SB6_HEIGHT", "tall"),
+            ("SB6_TIME_STEP", "0"),
+        ];
+        let lookup = |var: &str| {
+            vars.iter()
+                .find(|&&(name, _)| name == var)
+                .map(|&(_, value)| value.to_string())
+        };
+        let defaults = AppInfo::default();
+        let mut info = AppInfo::default();
+        info.vsync = true;
+        let invalid = apply_vars(&mut info, lookup);
+        assert_eq!(invalid, ["SB6_HEIGHT", "SB6_TIME_STEP"]);
+        assert_eq!(info.window_width, 1920);
+        // invalid values leave the field alone
+        assert_eq!(info.window_height, defaults.window_height);
+        assert_eq!(info.time_step, None);
+        assert!(!info.vsync);
+        assert_eq!((info.major_version, info.minor_version), (3, 3));
+        assert_eq!(info.max_frames, Some(100));
+        assert_eq!(info.media_root, "/data/media");
+        assert_eq!(info.stereo_fallback, StereoMode::Anaglyph);
+    }
+
+    #[test]
+    fn args_override_environment_variables() {
+        let mut info = AppInfo::default();
+        apply_vars(&mut info, |var| match var {
+            "SB6_WIDTH" => Some("1920".to_string()),
+            "SB6_HEADLESS" => Some("1".to_string()),
+            _ => None,
+        });
+        apply_args(
+            &mut info,
+            vec!["--width=640".to_string(), "--no-headless".to_string()],
+        )
+        .unwrap();
+        assert_eq!(info.window_width, 640);
+        assert!(!info.headless);
+    }
+}
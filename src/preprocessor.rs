@@ -0,0 +1,872 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Resolves `#include` directives in GLSL source before it is compiled.
+//!
+//! `#include "file"` is looked up relative to the including file and then
+//! in each include path, `#include <file>` only in the include paths. A file
+//! containing `#pragma once` is only included the first time, and classic
+//! `#ifndef` guards also work as the driver's preprocessor sees the combined
+//! source.
+//!
+//! Included files are numbered in the order they are first included, with
+//! the top level source as 0. `#line` directives are emitted around each
+//! include so the line numbers and source string numbers in the driver's
+//! info log refer to the original files, and `Source::file` maps the numbers
+//! back to paths.
+//!
+//! `Preprocessor::define` adds `#define`s after the top level `#version`, so
+//! one file can be compiled into several variants, and
+//! `Preprocessor::set_max_version` lowers `#version` directives above the
+//! version the context supports. `Context::preprocessor` sets the limit to
+//! the context's GLSL version.
+//!
+//! `split_stages` separates a file holding several shader stages, each
+//! starting with `#pragma stage vertex`, `#pragma stage fragment` and so on.
+//! Lines before the first stage, such as the `#version`, are shared by every
+//! stage.
+
+extern crate gl;
+
+use gl::types::*;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    /// An included file was not found relative to the including file or in
+    /// any include path.
+    NotFound {
+        file: PathBuf,
+        line: usize,
+        include: String,
+    },
+    /// A file includes itself, directly or through other files. The chain of
+    /// includes ends with the repeated file.
+    Cycle(Vec<PathBuf>),
+    /// A malformed `#include`, `#version` or `#pragma stage`, a `#version`
+    /// in an included file, or a stage marker where stages aren't expected.
+    Syntax {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+    IoError(PathBuf, io::Error),
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PreprocessError::NotFound {
+                ref file,
+                line,
+                ref include,
+            } => write!(
+                fmt,
+                "{}:{}: cannot find include '{}'",
+                file.display(),
+                line,
+                include
+            ),
+            PreprocessError::Cycle(ref chain) => {
+                try!(write!(fmt, "Include cycle: "));
+                for (i, path) in chain.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(fmt, " -> "));
+                    }
+                    try!(write!(fmt, "{}", path.display()));
+                }
+                Ok(())
+            }
+            PreprocessError::Syntax {
+                ref file,
+                line,
+                ref message,
+            } => write!(fmt, "{}:{}: {}", file.display(), line, message),
+            PreprocessError::IoError(ref path, ref e) => {
+                write!(fmt, "Error reading '{}': {}", path.display(), e)
+            }
+        }
+    }
+}
+
+/// Preprocessed source ready to compile.
+#[derive(Clone, Debug)]
+pub struct Source {
+    pub text: String,
+    /// Each file included, indexed by source string number.
+    pub files: Vec<PathBuf>,
+    /// The contents of each file before preprocessing, for showing the lines
+    /// compile errors refer to.
+    pub contents: Vec<String>,
+}
+
+impl Source {
+    /// The file with source string number `index`.
+    pub fn file(&self, index: usize) -> Option<&Path> {
+        self.files.get(index).map(|path| path.as_path())
+    }
+
+    /// The original text of source string number `index`.
+    pub fn contents(&self, index: usize) -> Option<&str> {
+        self.contents.get(index).map(|text| text.as_str())
+    }
+}
+
+enum Directive<'a> {
+    Include(&'a str, bool),
+    PragmaOnce,
+    /// The version number and the profile, if any.
+    Version(u32, &'a str),
+    Stage(Stage),
+    Other,
+}
+
+/// Classifies a line outside of any block comment.
+fn parse_directive(line: &str) -> Result<Directive<'_>, &'static str> {
+    let directive = match line.trim_start().strip_prefix('#') {
+        Some(directive) => directive.trim_start(),
+        None => return Ok(Directive::Other),
+    };
+    if let Some(name) = directive.strip_prefix("include") {
+        let name = name.trim();
+        let (name, close) = if let Some(name) = name.strip_prefix('"') {
+            (name, '"')
+        } else if let Some(name) = name.strip_prefix('<') {
+            (name, '>')
+        } else {
+            return Err("expected \"file\" or <file> after #include");
+        };
+        return match name.find(close) {
+            Some(end) => Ok(Directive::Include(&name[..end], close == '"')),
+            None => Err("unterminated #include file name"),
+        };
+    }
+    if let Some(pragma) = directive.strip_prefix("pragma") {
+        let mut words = pragma.split_whitespace();
+        match words.next() {
+            Some("once") => return Ok(Directive::PragmaOnce),
+            Some("stage") => {
+                return match words.next().map(Stage::from_name) {
+                    Some(Some(stage)) => Ok(Directive::Stage(stage)),
+                    Some(None) => Err("unknown stage after #pragma stage"),
+                    None => Err("expected a stage name after #pragma stage"),
+                }
+            }
+            _ => (),
+        }
+    }
+    if let Some(version) = directive.strip_prefix("version") {
+        let mut words = version.split_whitespace();
+        let number = match words.next().map(|word| word.parse()) {
+            Some(Ok(number)) => number,
+            _ => return Err("expected a version number after #version"),
+        };
+        return Ok(Directive::Version(number, words.next().unwrap_or("")));
+    }
+    Ok(Directive::Other)
+}
+
+/// Updates `in_comment` for the block comments opened or closed on `line`.
+/// Returns whether the line started inside a comment.
+fn track_comments(line: &str, in_comment: &mut bool) -> bool {
+    let started_in_comment = *in_comment;
+    let mut rest = line;
+    loop {
+        if *in_comment {
+            match rest.find("*/") {
+                Some(i) => {
+                    *in_comment = false;
+                    rest = &rest[i + 2..];
+                }
+                None => break,
+            }
+        } else {
+            let block = rest.find("/*");
+            let line_comment = rest.find("//");
+            match (block, line_comment) {
+                (Some(b), Some(l)) if l < b => break,
+                (Some(b), _) => {
+                    *in_comment = true;
+                    rest = &rest[b + 2..];
+                }
+                (None, _) => break,
+            }
+        }
+    }
+    started_in_comment
+}
+
+/// A shader stage named by `#pragma stage`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Stage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl Stage {
+    /// The stage for a `#pragma stage` name: `vertex`, `tess_control`,
+    /// `tess_evaluation`, `geometry`, `fragment` or `compute`.
+    pub fn from_name(name: &str) -> Option<Stage> {
+        match name {
+            "vertex" => Some(Stage::Vertex),
+            "tess_control" => Some(Stage::TessControl),
+            "tess_evaluation" => Some(Stage::TessEvaluation),
+            "geometry" => Some(Stage::Geometry),
+            "fragment" => Some(Stage::Fragment),
+            "compute" => Some(Stage::Compute),
+            _ => None,
+        }
+    }
+
+    pub fn shader_type(self) -> GLenum {
+        match self {
+            Stage::Vertex => gl::VERTEX_SHADER,
+            Stage::TessControl => gl::TESS_CONTROL_SHADER,
+            Stage::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            Stage::Geometry => gl::GEOMETRY_SHADER,
+            Stage::Fragment => gl::FRAGMENT_SHADER,
+            Stage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+}
+
+/// Splits `src`, read from `path`, at its `#pragma stage` markers. Each
+/// stage's source is the shared lines before the first marker followed by
+/// the stage's own lines, with every other line left blank so line numbers
+/// in compile errors match the file.
+pub fn split_stages(src: &str, path: &Path) -> Result<Vec<(Stage, String)>, PreprocessError> {
+    let syntax_error = |line: usize, message: &str| PreprocessError::Syntax {
+        file: path.to_path_buf(),
+        line,
+        message: message.to_string(),
+    };
+    let lines: Vec<&str> = src.lines().collect();
+    let mut markers: Vec<(usize, Stage)> = Vec::new();
+    let mut in_comment = false;
+    for (i, line) in lines.iter().enumerate() {
+        if track_comments(line, &mut in_comment) {
+            continue;
+        }
+        let directive = try!(parse_directive(line).map_err(|message| syntax_error(i + 1, message)));
+        if let Directive::Stage(stage) = directive {
+            if markers.iter().any(|&(_, s)| s == stage) {
+                return Err(syntax_error(i + 1, "stage already defined in this file"));
+            }
+            markers.push((i, stage));
+        }
+    }
+    if markers.is_empty() {
+        return Err(syntax_error(1, "no #pragma stage markers found"));
+    }
+
+    let shared = markers[0].0;
+    Ok(markers
+        .iter()
+        .enumerate()
+        .map(|(m, &(start, stage))| {
+            let end = markers.get(m + 1).map_or(lines.len(), |&(next, _)| next);
+            let mut text = String::new();
+            for (i, line) in lines.iter().enumerate() {
+                if i < shared || (i > start && i < end) {
+                    text.push_str(line);
+                }
+                text.push('\n');
+            }
+            (stage, text)
+        })
+        .collect())
+}
+
+/// Whether `src` has a `#version` directive outside of comments.
+fn has_version(src: &str) -> bool {
+    let mut in_comment = false;
+    src.lines().any(|line| {
+        !track_comments(line, &mut in_comment)
+            && matches!(parse_directive(line), Ok(Directive::Version(..)))
+    })
+}
+
+/// State for one `process` call.
+struct Context {
+    out: String,
+    files: Vec<PathBuf>,
+    contents: Vec<String>,
+    stack: Vec<PathBuf>,
+    once: HashSet<PathBuf>,
+}
+
+/// Resolves includes using a list of include paths, and adds defines.
+#[derive(Clone, Debug, Default)]
+pub struct Preprocessor {
+    include_paths: Vec<PathBuf>,
+    defines: BTreeMap<String, String>,
+    max_version: Option<u32>,
+}
+
+impl Preprocessor {
+    /// A preprocessor that only finds includes relative to the including file.
+    pub fn new() -> Preprocessor {
+        Preprocessor {
+            include_paths: Vec::new(),
+            defines: BTreeMap::new(),
+            max_version: None,
+        }
+    }
+
+    /// Adds a directory to search after those already added.
+    pub fn add_include_path<P: Into<PathBuf>>(&mut self, path: P) {
+        self.include_paths.push(path.into());
+    }
+
+    pub fn include_paths(&self) -> &[PathBuf] {
+        &self.include_paths
+    }
+
+    /// Adds `#define name value` after the `#version` line, replacing any
+    /// earlier value for `name`. `value` may be empty.
+    pub fn define<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.defines.insert(name.into(), value.into());
+    }
+
+    pub fn undefine(&mut self, name: &str) {
+        self.defines.remove(name);
+    }
+
+    /// The defines in name order.
+    pub fn defines(&self) -> &BTreeMap<String, String> {
+        &self.defines
+    }
+
+    /// Lowers `#version` directives above `version`, e.g. `410 core` becomes
+    /// `330 core` with a limit of 330, so shaders that don't need newer
+    /// features still build on older contexts.
+    pub fn set_max_version(&mut self, version: Option<u32>) {
+        self.max_version = version;
+    }
+
+    pub fn max_version(&self) -> Option<u32> {
+        self.max_version
+    }
+
+    /// The `#define` lines to inject.
+    fn define_lines(&self) -> String {
+        let mut lines = String::new();
+        for (name, value) in &self.defines {
+            lines.push_str(format!("#define {} {}", name, value).trim_end());
+            lines.push('\n');
+        }
+        lines
+    }
+
+    /// Reads and preprocesses the file at `path`.
+    pub fn process_file(&self, path: &Path) -> Result<Source, PreprocessError> {
+        let src =
+            try!(fs::read_to_string(path)
+                .map_err(|e| PreprocessError::IoError(path.to_path_buf(), e)));
+        self.process(&src, path)
+    }
+
+    /// Preprocesses `src`, which was read from `path`. Relative includes are
+    /// resolved against the directory containing `path`.
+    pub fn process(&self, src: &str, path: &Path) -> Result<Source, PreprocessError> {
+        let mut ctx = Context {
+            out: String::with_capacity(src.len()),
+            files: Vec::new(),
+            contents: Vec::new(),
+            stack: Vec::new(),
+            once: HashSet::new(),
+        };
+        if !self.defines.is_empty() && !has_version(src) {
+            ctx.out.push_str(&self.define_lines());
+            ctx.out.push_str("#line 1 0\n");
+        }
+        try!(self.process_into(&mut ctx, src, path));
+        Ok(Source {
+            text: ctx.out,
+            files: ctx.files,
+            contents: ctx.contents,
+        })
+    }
+
+    fn resolve(&self, including: &Path, name: &str, relative: bool) -> Option<PathBuf> {
+        let local = if relative {
+            including.parent().map(|dir| dir.join(name))
+        } else {
+            None
+        };
+        local
+            .into_iter()
+            .chain(self.include_paths.iter().map(|dir| dir.join(name)))
+            .find(|path| path.is_file())
+    }
+
+    fn process_into(
+        &self,
+        ctx: &mut Context,
+        src: &str,
+        path: &Path,
+    ) -> Result<(), PreprocessError> {
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if ctx.stack.contains(&key) {
+            let mut chain = ctx.stack.clone();
+            chain.push(key);
+            return Err(PreprocessError::Cycle(chain));
+        }
+        let index = ctx.files.len();
+        ctx.files.push(path.to_path_buf());
+        ctx.contents.push(src.to_string());
+        ctx.stack.push(key.clone());
+        if index > 0 {
+            ctx.out.push_str(&format!("#line 1 {}\n", index));
+        }
+
+        let mut in_comment = false;
+        for (i, line) in src.lines().enumerate() {
+            let line_number = i + 1;
+            if track_comments(line, &mut in_comment) {
+                ctx.out.push_str(line);
+                ctx.out.push('\n');
+                continue;
+            }
+            let syntax_error = |message: &str| PreprocessError::Syntax {
+                file: path.to_path_buf(),
+                line: line_number,
+                message: message.to_string(),
+            };
+            match try!(parse_directive(line).map_err(syntax_error)) {
+                Directive::Include(name, relative) => {
+                    let include = try!(self.resolve(path, name, relative).ok_or_else(|| {
+                        PreprocessError::NotFound {
+                            file: path.to_path_buf(),
+                            line: line_number,
+                            include: name.to_string(),
+                        }
+                    }));
+                    let include_key =
+                        fs::canonicalize(&include).unwrap_or_else(|_| include.clone());
+                    if ctx.once.contains(&include_key) {
+                        // keep the line count for the info log
+                        ctx.out.push('\n');
+                        continue;
+                    }
+                    let include_src = try!(fs::read_to_string(&include)
+                        .map_err(|e| PreprocessError::IoError(include.clone(), e)));
+                    try!(self.process_into(ctx, &include_src, &include));
+                    ctx.out
+                        .push_str(&format!("#line {} {}\n", line_number + 1, index));
+                }
+                Directive::PragmaOnce => {
+                    ctx.once.insert(key.clone());
+                    ctx.out.push('\n');
+                }
+                Directive::Stage(_) => {
+                    return Err(syntax_error(
+                        "#pragma stage is only allowed in multi-stage sources, see program::load \
+                         and ProgramRegistry::load_file",
+                    ));
+                }
+                Directive::Version(..) if index > 0 => {
+                    return Err(syntax_error("#version is not allowed in an included file"));
+                }
+                Directive::Version(version, profile) => {
+                    match self.max_version {
+                        Some(max) if version > max => {
+                            ctx.out
+                                .push_str(format!("#version {} {}", max, profile).trim_end());
+                        }
+                        _ => ctx.out.push_str(line),
+                    }
+                    ctx.out.push('\n');
+                    if !self.defines.is_empty() {
+                        ctx.out.push_str(&self.define_lines());
+                        ctx.out.push_str(&format!("#line {} 0\n", line_number + 1));
+                    }
+                }
+                Directive::Other => {
+                    ctx.out.push_str(line);
+                    ctx.out.push('\n');
+                }
+            }
+        }
+
+        ctx.stack.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// A directory of shader files, removed when dropped.
+    struct Fixture {
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &str)]) -> Fixture {
+            let root = env::temp_dir().join(format!("sb6_preprocessor_{}_{}", name, process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for &(path, contents) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            Fixture { root }
+        }
+
+        fn path(&self, path: &str) -> PathBuf {
+            self.root.join(path)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn relative_includes_before_include_paths() {
+        let fixture = Fixture::new(
+            "order",
+            &[
+                ("shaders/a/main.glsl", "#include \"common.glsl\"\n"),
+                ("shaders/a/common.glsl", "// relative\n"),
+                ("shaders/common.glsl", "// search path\n"),
+            ],
+        );
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.add_include_path(fixture.path("shaders"));
+        let source = preprocessor
+            .process_file(&fixture.path("shaders/a/main.glsl"))
+            .unwrap();
+        assert!(source.text.contains("// relative"));
+        assert_eq!(source.files[1], fixture.path("shaders/a/common.glsl"));
+
+        // <file> skips the including file's directory
+        let source = preprocessor
+            .process(
+                "#include <common.glsl>\n",
+                &fixture.path("shaders/a/main.glsl"),
+            )
+            .unwrap();
+        assert!(source.text.contains("// search path"));
+    }
+
+    #[test]
+    fn include_paths_in_order() {
+        let fixture = Fixture::new(
+            "paths",
+            &[
+                ("first/x.glsl", "// first\n"),
+                ("second/x.glsl", "// second\n"),
+                ("second/y.glsl", "// y\n"),
+            ],
+        );
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.add_include_path(fixture.path("first"));
+        preprocessor.add_include_path(fixture.path("second"));
+        let main = fixture.path("main.glsl");
+        let source = preprocessor
+            .process("#include \"x.glsl\"\n#include \"y.glsl\"\n", &main)
+            .unwrap();
+        assert!(source.text.contains("// first"));
+        assert!(!source.text.contains("// second"));
+        assert!(source.text.contains("// y"));
+
+        // without the search paths only relative includes resolve
+        match Preprocessor::new().process("\n#include \"x.glsl\"\n", &main) {
+            Err(PreprocessError::NotFound {
+                line: 2,
+                ref include,
+                ..
+            }) => assert_eq!(include, "x.glsl"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pragma_once() {
+        let fixture = Fixture::new(
+            "once",
+            &[
+                (
+                    "main.glsl",
+                    "#include \"a.glsl\"\n#include \"b.glsl\"\n#include \"a.glsl\"\n",
+                ),
+                ("a.glsl", "#pragma once\nfloat a;\n"),
+                ("b.glsl", "#include \"a.glsl\"\nfloat b;\n"),
+            ],
+        );
+        let source = Preprocessor::new()
+            .process_file(&fixture.path("main.glsl"))
+            .unwrap();
+        assert_eq!(source.text.matches("float a;").count(), 1);
+        assert_eq!(source.text.matches("float b;").count(), 1);
+        assert_eq!(source.files.len(), 3);
+    }
+
+    #[test]
+    fn include_cycle() {
+        let fixture = Fixture::new(
+            "cycle",
+            &[
+                ("main.glsl", "#include \"a.glsl\"\n"),
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"a.glsl\"\n"),
+            ],
+        );
+        match Preprocessor::new().process_file(&fixture.path("main.glsl")) {
+            Err(PreprocessError::Cycle(chain)) => {
+                let names: Vec<_> = chain
+                    .iter()
+                    .map(|p| p.file_name().unwrap().to_owned())
+                    .collect();
+                assert_eq!(names, ["main.glsl", "a.glsl", "b.glsl", "a.glsl"]);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn line_directives() {
+        let fixture = Fixture::new(
+            "line",
+            &[
+                (
+                    "main.glsl",
+                    "#version 330 core\n#include \"a.glsl\"\nvoid main() {}\n",
+                ),
+                ("a.glsl", "// a1\n#include \"b.glsl\"\n// a3\n"),
+                ("b.glsl", "// b1\n"),
+            ],
+        );
+        let source = Preprocessor::new()
+            .process_file(&fixture.path("main.glsl"))
+            .unwrap();
+        assert_eq!(
+            source.text,
+            "#version 330 core\n\
+             #line 1 1\n\
+             // a1\n\
+             #line 1 2\n\
+             // b1\n\
+             #line 3 1\n\
+             // a3\n\
+             #line 3 0\n\
+             void main() {}\n"
+        );
+        assert_eq!(source.file(2), Some(fixture.path("b.glsl").as_path()));
+        assert_eq!(
+            source.contents(1),
+            Some("// a1\n#include \"b.glsl\"\n// a3\n")
+        );
+    }
+
+    #[test]
+    fn includes_in_comments_are_ignored() {
+        let source = Preprocessor::new()
+            .process(
+                "/*\n#include \"missing.glsl\"\n*/\n// #include \"missing.glsl\"\n",
+                Path::new("main.glsl"),
+            )
+            .unwrap();
+        assert_eq!(source.files.len(), 1);
+    }
+
+    #[test]
+    fn version_in_include_is_an_error() {
+        let fixture = Fixture::new("version", &[("a.glsl", "\n#version 330\n")]);
+        match Preprocessor::new().process("#include \"a.glsl\"\n", &fixture.path("main.glsl")) {
+            Err(PreprocessError::Syntax {
+                line: 2, ref file, ..
+            }) => assert_eq!(file, &fixture.path("a.glsl")),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn defines_after_version() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.define("B", "2");
+        preprocessor.define("A", "");
+        let source = preprocessor
+            .process(
+                "// header\n#version 330 core\nfloat x = B;\n",
+                Path::new("main.glsl"),
+            )
+            .unwrap();
+        assert_eq!(
+            source.text,
+            "// header\n\
+             #version 330 core\n\
+             #define A\n\
+             #define B 2\n\
+             #line 3 0\n\
+             float x = B;\n"
+        );
+    }
+
+    #[test]
+    fn defines_without_version() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.define("A", "1");
+        let source = preprocessor
+            .process("float x = A;\n", Path::new("main.glsl"))
+            .unwrap();
+        assert_eq!(source.text, "#define A 1\n#line 1 0\nfloat x = A;\n");
+
+        // no defines, no extra lines
+        let source = Preprocessor::new()
+            .process("float x;\n", Path::new("main.glsl"))
+            .unwrap();
+        assert_eq!(source.text, "float x;\n");
+    }
+
+    #[test]
+    fn line_directives_after_defines() {
+        let fixture = Fixture::new(
+            "define_line",
+            &[
+                (
+                    "main.glsl",
+                    "#version 330 core\n#include \"a.glsl\"\nvoid main() {}\n",
+                ),
+                ("a.glsl", "// a1\n"),
+            ],
+        );
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.define("A", "1");
+        let source = preprocessor
+            .process_file(&fixture.path("main.glsl"))
+            .unwrap();
+        assert_eq!(
+            source.text,
+            "#version 330 core\n\
+             #define A 1\n\
+             #line 2 0\n\
+             #line 1 1\n\
+             // a1\n\
+             #line 3 0\n\
+             void main() {}\n"
+        );
+    }
+
+    #[test]
+    fn max_version() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.set_max_version(Some(330));
+        let main = Path::new("main.glsl");
+        let source = preprocessor.process("#version 410 core\n", main).unwrap();
+        assert_eq!(source.text, "#version 330 core\n");
+        let source = preprocessor.process("#version 420\n", main).unwrap();
+        assert_eq!(source.text, "#version 330\n");
+
+        // versions at or below the limit are left alone
+        let source = preprocessor.process("#version 150 core\n", main).unwrap();
+        assert_eq!(source.text, "#version 150 core\n");
+
+        preprocessor.set_max_version(None);
+        let source = preprocessor.process("#version 410 core\n", main).unwrap();
+        assert_eq!(source.text, "#version 410 core\n");
+    }
+
+    #[test]
+    fn split_stages_shares_leading_lines() {
+        let src = "#version 330 core\n\
+                   uniform float t;\n\
+                   #pragma stage vertex\n\
+                   void main() { gl_Position = vec4(t); }\n\
+                   #pragma stage fragment\n\
+                   out vec4 color;\n\
+                   void main() { color = vec4(t); }\n";
+        let stages = split_stages(src, Path::new("main.glsl")).unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].0, Stage::Vertex);
+        assert_eq!(stages[1].0, Stage::Fragment);
+
+        // every stage keeps the file's line numbers, with the other stages'
+        // lines and the markers blanked
+        assert_eq!(
+            stages[0].1,
+            "#version 330 core\n\
+             uniform float t;\n\
+             \n\
+             void main() { gl_Position = vec4(t); }\n\
+             \n\
+             \n\
+             \n"
+        );
+        assert_eq!(
+            stages[1].1,
+            "#version 330 core\n\
+             uniform float t;\n\
+             \n\
+             \n\
+             \n\
+             out vec4 color;\n\
+             void main() { color = vec4(t); }\n"
+        );
+
+        // defines added to a stage map back to the file's lines
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.define("A", "1");
+        let source = preprocessor
+            .process(&stages[1].1, Path::new("main.glsl"))
+            .unwrap();
+        assert!(source
+            .text
+            .starts_with("#version 330 core\n#define A 1\n#line 2 0\nuniform float t;\n"));
+    }
+
+    #[test]
+    fn split_stages_errors() {
+        let path = Path::new("main.glsl");
+        let line_of = |src: &str| match split_stages(src, path) {
+            Err(PreprocessError::Syntax { line, .. }) => line,
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(line_of("#version 330\n\n#pragma stage pixel\n"), 3);
+        assert_eq!(
+            line_of("#pragma stage vertex\n#pragma stage fragment\n#pragma stage vertex\n"),
+            3
+        );
+        assert_eq!(line_of("#pragma stage\n"), 1);
+        assert_eq!(line_of("#version 330\nvoid main() {}\n"), 1);
+
+        // markers in comments don't count
+        assert!(split_stages("/*\n#pragma stage pixel\n*/\n#pragma stage vertex\n", path).is_ok());
+    }
+}
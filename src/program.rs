@@ -24,9 +24,15 @@
 
 extern crate gl;
 use gl::types::*;
+use gl_debug;
+use preprocessor;
+use preprocessor::{Preprocessor, Source};
+use shader;
 use std::ffi;
+use std::fmt;
 use std::iter;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::ptr;
 
 #[derive(Clone, PartialEq, Debug)]
@@ -34,6 +40,14 @@ pub enum ProgramError {
     ProgramInfoLog(String),
 }
 
+impl fmt::Display for ProgramError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProgramError::ProgramInfoLog(ref log) => write!(fmt, "Program link failed:\n{}", log),
+        }
+    }
+}
+
 pub fn check_link_status(program: GLuint) -> Result<(), ProgramError> {
     unsafe {
         // Get the link status
@@ -70,21 +84,82 @@ pub fn link_from_shaders(shaders: &[GLuint]) -> Result<GLuint, ProgramError> {
         }
 
         gl::LinkProgram(program);
-        try!(check_link_status(program));
+        gl_debug::check_errors("program::link_from_shaders");
+        let status = check_link_status(program);
 
         for shader in shaders.iter() {
             gl::DeleteShader(*shader);
         }
 
-        Ok(program)
+        match status {
+            Ok(_) => Ok(program),
+            Err(e) => {
+                gl::DeleteProgram(program);
+                Err(e)
+            }
+        }
     }
 }
 
+/// Loads a file holding every stage of a program, each starting with a
+/// `#pragma stage` marker, and builds the program. See
+/// `preprocessor::split_stages` for the format. Link errors name the file.
+pub fn load(filename: &str) -> Result<GLuint, shader::LoadError> {
+    load_with(filename, &Preprocessor::new())
+}
+
+/// Like `load`, resolving `#include` directives with `preprocessor`.
+pub fn load_with(filename: &str, preprocessor: &Preprocessor) -> Result<GLuint, shader::LoadError> {
+    let shaders = try!(shader::load_stages_with(filename, preprocessor));
+    let shaders: Vec<GLuint> = shaders.into_iter().map(|(shader, _)| shader).collect();
+    link_from_shaders(&shaders)
+        .map_err(|e| shader::LoadError::LinkError(PathBuf::from(filename), e))
+}
+
+/// Builds a program from source holding every stage, as for `load`. Errors
+/// refer to the source as `<source>`.
+pub fn create_from_source(src: &str) -> Result<GLuint, shader::LoadError> {
+    let path = Path::new("<source>");
+    let stages = try!(preprocessor::split_stages(src, path));
+    let mut shaders = Vec::with_capacity(stages.len());
+    for (stage, text) in stages {
+        // the stage keeps the line numbers of the whole source, so compile
+        // errors can show lines from it
+        let source = Source {
+            text,
+            files: vec![path.to_path_buf()],
+            contents: vec![src.to_string()],
+        };
+        match shader::compile(source, stage.shader_type()) {
+            Ok((shader, _)) => shaders.push(shader),
+            Err(e) => {
+                for shader in shaders {
+                    unsafe {
+                        gl::DeleteShader(shader);
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    link_from_shaders(&shaders).map_err(|e| shader::LoadError::LinkError(path.to_path_buf(), e))
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum UniformError {
     UniformNotFound(GLuint, String, GLint),
 }
 
+impl fmt::Display for UniformError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UniformError::UniformNotFound(program, ref name, _) => {
+                write!(fmt, "Uniform '{}' not found in program {}", name, program)
+            }
+        }
+    }
+}
+
 pub fn get_uniform_location(program: GLuint, name: &str) -> Result<GLint, UniformError> {
     let result = unsafe {
         gl::GetUniformLocation(
@@ -0,0 +1,645 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Recording and replay of window events and frame times.
+//!
+//! Run with `--record=PATH` to save every window event along with the time
+//! passed to `update` and `render` each frame. Running with `--replay=PATH`
+//! then feeds the same times and events back, ignoring live input, so the
+//! app sees exactly the same sequence again. Combined with `--headless` and
+//! `--capture-frame` this turns a bug report into a reproducible test.
+//! Headless replays resize the offscreen target to follow recorded
+//! framebuffer size changes.
+//!
+//! Gamepads are not recorded. They aren't polled during a replay, so
+//! `Context::gamepads` is empty and a session driven by a gamepad won't
+//! replay the same way; use the keyboard and mouse when recording.
+//!
+//! The file starts with `SB6R` and a version byte, followed by records that
+//! each start with a tag byte. A frame record holds the frame time and is
+//! followed by the events handled during that frame. Values are little
+//! endian.
+
+extern crate glfw;
+
+use app::{Action, Key, Modifiers, MouseButton};
+use reader::BufferReader;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::PathBuf;
+
+const MAGIC: [u8; 4] = *b"SB6R";
+const VERSION: u8 = 1;
+
+const TAG_FRAME: u8 = 0;
+const TAG_KEY: u8 = 1;
+const TAG_CHAR: u8 = 2;
+const TAG_SIZE: u8 = 3;
+const TAG_FRAMEBUFFER_SIZE: u8 = 4;
+const TAG_MOUSE_BUTTON: u8 = 5;
+const TAG_CURSOR_POS: u8 = 6;
+const TAG_SCROLL: u8 = 7;
+const TAG_FOCUS: u8 = 8;
+const TAG_FILE_DROP: u8 = 9;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    MagicError,
+    /// The file was written by an incompatible version.
+    VersionError(u8),
+    /// The file is truncated or contains an unknown record.
+    FormatError,
+    IoError(io::Error),
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> ReplayError {
+        ReplayError::IoError(e)
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::MagicError => write!(fmt, "Not a valid recording"),
+            ReplayError::VersionError(version) => {
+                write!(fmt, "Unsupported recording version {}", version)
+            }
+            ReplayError::FormatError => write!(fmt, "Invalid or truncated recording"),
+            ReplayError::IoError(ref e) => e.fmt(fmt),
+        }
+    }
+}
+
+/// Every key, used to decode key codes without transmuting.
+const KEYS: [Key; 121] = [
+    Key::Space,
+    Key::Apostrophe,
+    Key::Comma,
+    Key::Minus,
+    Key::Period,
+    Key::Slash,
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::Semicolon,
+    Key::Equal,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::LeftBracket,
+    Key::Backslash,
+    Key::RightBracket,
+    Key::GraveAccent,
+    Key::World1,
+    Key::World2,
+    Key::Escape,
+    Key::Enter,
+    Key::Tab,
+    Key::Backspace,
+    Key::Insert,
+    Key::Delete,
+    Key::Right,
+    Key::Left,
+    Key::Down,
+    Key::Up,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Home,
+    Key::End,
+    Key::CapsLock,
+    Key::ScrollLock,
+    Key::NumLock,
+    Key::PrintScreen,
+    Key::Pause,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::F13,
+    Key::F14,
+    Key::F15,
+    Key::F16,
+    Key::F17,
+    Key::F18,
+    Key::F19,
+    Key::F20,
+    Key::F21,
+    Key::F22,
+    Key::F23,
+    Key::F24,
+    Key::F25,
+    Key::Kp0,
+    Key::Kp1,
+    Key::Kp2,
+    Key::Kp3,
+    Key::Kp4,
+    Key::Kp5,
+    Key::Kp6,
+    Key::Kp7,
+    Key::Kp8,
+    Key::Kp9,
+    Key::KpDecimal,
+    Key::KpDivide,
+    Key::KpMultiply,
+    Key::KpSubtract,
+    Key::KpAdd,
+    Key::KpEnter,
+    Key::KpEqual,
+    Key::LeftShift,
+    Key::LeftControl,
+    Key::LeftAlt,
+    Key::LeftSuper,
+    Key::RightShift,
+    Key::RightControl,
+    Key::RightAlt,
+    Key::RightSuper,
+    Key::Menu,
+    Key::Unknown,
+];
+
+const MOUSE_BUTTONS: [MouseButton; 8] = [
+    MouseButton::Button1,
+    MouseButton::Button2,
+    MouseButton::Button3,
+    MouseButton::Button4,
+    MouseButton::Button5,
+    MouseButton::Button6,
+    MouseButton::Button7,
+    MouseButton::Button8,
+];
+
+const ACTIONS: [Action; 3] = [Action::Release, Action::Press, Action::Repeat];
+
+/// Writes frame times and events to a file as the app runs. Write errors are
+/// held until `finish` so recording never interrupts the app.
+pub struct Recorder {
+    path: String,
+    writer: BufWriter<File>,
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, ReplayError> {
+        let mut writer = BufWriter::new(try!(File::create(path)));
+        try!(writer.write_all(&MAGIC));
+        try!(writer.write_all(&[VERSION]));
+        Ok(Recorder {
+            path: path.to_string(),
+            writer,
+            error: None,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Starts a new frame that sees time `time`.
+    pub fn frame(&mut self, time: f64) {
+        let mut record = vec![TAG_FRAME];
+        record.extend_from_slice(&time.to_le_bytes());
+        self.write(&record);
+    }
+
+    /// Records an event handled in the current frame. Events the run loop
+    /// doesn't pass on to the app are skipped.
+    pub fn event(&mut self, event: &glfw::WindowEvent) {
+        let mut record = Vec::new();
+        match *event {
+            glfw::WindowEvent::Key(key, scancode, action, mods) => {
+                record.push(TAG_KEY);
+                record.extend_from_slice(&(key as i32).to_le_bytes());
+                record.extend_from_slice(&scancode.to_le_bytes());
+                record.push(action as u8);
+                record.push(mods.bits() as u8);
+            }
+            glfw::WindowEvent::Char(c) => {
+                record.push(TAG_CHAR);
+                record.extend_from_slice(&(c as u32).to_le_bytes());
+            }
+            glfw::WindowEvent::Size(w, h) | glfw::WindowEvent::FramebufferSize(w, h) => {
+                record.push(match *event {
+                    glfw::WindowEvent::Size(..) => TAG_SIZE,
+                    _ => TAG_FRAMEBUFFER_SIZE,
+                });
+                record.extend_from_slice(&w.to_le_bytes());
+                record.extend_from_slice(&h.to_le_bytes());
+            }
+            glfw::WindowEvent::MouseButton(button, action, mods) => {
+                record.push(TAG_MOUSE_BUTTON);
+                record.push(button as u8);
+                record.push(action as u8);
+                record.push(mods.bits() as u8);
+            }
+            glfw::WindowEvent::CursorPos(x, y) | glfw::WindowEvent::Scroll(x, y) => {
+                record.push(match *event {
+                    glfw::WindowEvent::CursorPos(..) => TAG_CURSOR_POS,
+                    _ => TAG_SCROLL,
+                });
+                record.extend_from_slice(&x.to_le_bytes());
+                record.extend_from_slice(&y.to_le_bytes());
+            }
+            glfw::WindowEvent::Focus(focused) => {
+                record.push(TAG_FOCUS);
+                record.push(focused as u8);
+            }
+            glfw::WindowEvent::FileDrop(ref paths) => {
+                record.push(TAG_FILE_DROP);
+                record.extend_from_slice(&(paths.len() as u32).to_le_bytes());
+                for path in paths {
+                    let path = path.to_string_lossy();
+                    record.extend_from_slice(&(path.len() as u32).to_le_bytes());
+                    record.extend_from_slice(path.as_bytes());
+                }
+            }
+            _ => return,
+        }
+        self.write(&record);
+    }
+
+    fn write(&mut self, record: &[u8]) {
+        if self.error.is_none() {
+            if let Err(e) = self.writer.write_all(record) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Flushes the file, returning the first error hit while recording.
+    pub fn finish(mut self) -> Result<(), ReplayError> {
+        if let Some(e) = self.error.take() {
+            return Err(ReplayError::IoError(e));
+        }
+        try!(self.writer.flush());
+        Ok(())
+    }
+}
+
+/// The time and events of one recorded frame.
+#[derive(Clone, Debug)]
+pub struct RecordedFrame {
+    pub time: f64,
+    pub events: Vec<glfw::WindowEvent>,
+}
+
+/// A recording loaded for replay.
+pub struct Recording {
+    frames: Vec<RecordedFrame>,
+}
+
+fn read_bytes<'a>(reader: &mut BufferReader<'a>, len: usize) -> Result<&'a [u8], ReplayError> {
+    unsafe { reader.pop_slice::<u8>(len) }.map_err(|_| ReplayError::FormatError)
+}
+
+fn read_u8(reader: &mut BufferReader) -> Result<u8, ReplayError> {
+    Ok(try!(read_bytes(reader, 1))[0])
+}
+
+fn read_4(reader: &mut BufferReader) -> Result<[u8; 4], ReplayError> {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(try!(read_bytes(reader, 4)));
+    Ok(bytes)
+}
+
+fn read_i32(reader: &mut BufferReader) -> Result<i32, ReplayError> {
+    Ok(i32::from_le_bytes(try!(read_4(reader))))
+}
+
+fn read_u32(reader: &mut BufferReader) -> Result<u32, ReplayError> {
+    Ok(u32::from_le_bytes(try!(read_4(reader))))
+}
+
+fn read_f64(reader: &mut BufferReader) -> Result<f64, ReplayError> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(try!(read_bytes(reader, 8)));
+    Ok(f64::from_le_bytes(bytes))
+}
+
+fn read_action(reader: &mut BufferReader) -> Result<Action, ReplayError> {
+    let action = try!(read_u8(reader));
+    ACTIONS
+        .iter()
+        .cloned()
+        .find(|&a| a as u8 == action)
+        .ok_or(ReplayError::FormatError)
+}
+
+fn read_mods(reader: &mut BufferReader) -> Result<Modifiers, ReplayError> {
+    Modifiers::from_bits(i32::from(try!(read_u8(reader)))).ok_or(ReplayError::FormatError)
+}
+
+fn read_event(reader: &mut BufferReader, tag: u8) -> Result<glfw::WindowEvent, ReplayError> {
+    let event = match tag {
+        TAG_KEY => {
+            let code = try!(read_i32(reader));
+            let key = try!(KEYS
+                .iter()
+                .cloned()
+                .find(|&k| k as i32 == code)
+                .ok_or(ReplayError::FormatError));
+            let scancode = try!(read_i32(reader));
+            let action = try!(read_action(reader));
+            glfw::WindowEvent::Key(key, scancode, action, try!(read_mods(reader)))
+        }
+        TAG_CHAR => {
+            let c = try!(read_u32(reader));
+            glfw::WindowEvent::Char(try!(
+                ::std::char::from_u32(c).ok_or(ReplayError::FormatError)
+            ))
+        }
+        TAG_SIZE => glfw::WindowEvent::Size(try!(read_i32(reader)), try!(read_i32(reader))),
+        TAG_FRAMEBUFFER_SIZE => {
+            glfw::WindowEvent::FramebufferSize(try!(read_i32(reader)), try!(read_i32(reader)))
+        }
+        TAG_MOUSE_BUTTON => {
+            let index = try!(read_u8(reader)) as usize;
+            let button = try!(MOUSE_BUTTONS.get(index).ok_or(ReplayError::FormatError));
+            let action = try!(read_action(reader));
+            glfw::WindowEvent::MouseButton(*button, action, try!(read_mods(reader)))
+        }
+        TAG_CURSOR_POS => {
+            glfw::WindowEvent::CursorPos(try!(read_f64(reader)), try!(read_f64(reader)))
+        }
+        TAG_SCROLL => glfw::WindowEvent::Scroll(try!(read_f64(reader)), try!(read_f64(reader))),
+        TAG_FOCUS => glfw::WindowEvent::Focus(try!(read_u8(reader)) != 0),
+        TAG_FILE_DROP => {
+            let count = try!(read_u32(reader));
+            let mut paths = Vec::new();
+            for _ in 0..count {
+                let len = try!(read_u32(reader)) as usize;
+                let bytes = try!(read_bytes(reader, len));
+                let path = String::from_utf8_lossy(bytes).into_owned();
+                paths.push(PathBuf::from(path));
+            }
+            glfw::WindowEvent::FileDrop(paths)
+        }
+        _ => return Err(ReplayError::FormatError),
+    };
+    Ok(event)
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Result<Recording, ReplayError> {
+        let mut bytes = Vec::new();
+        try!(try!(File::open(path)).read_to_end(&mut bytes));
+        Recording::parse(&bytes)
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Recording, ReplayError> {
+        let mut reader = BufferReader::new(bytes);
+
+        if try!(read_bytes(&mut reader, MAGIC.len()).map_err(|_| ReplayError::MagicError)) != MAGIC
+        {
+            return Err(ReplayError::MagicError);
+        }
+        let version = try!(read_u8(&mut reader));
+        if version != VERSION {
+            return Err(ReplayError::VersionError(version));
+        }
+
+        let mut frames: Vec<RecordedFrame> = Vec::new();
+        while reader.bytes_read() < reader.len() {
+            let tag = try!(read_u8(&mut reader));
+            if tag == TAG_FRAME {
+                frames.push(RecordedFrame {
+                    time: try!(read_f64(&mut reader)),
+                    events: Vec::new(),
+                });
+                continue;
+            }
+            let event = try!(read_event(&mut reader, tag));
+            match frames.last_mut() {
+                Some(frame) => frame.events.push(event),
+                None => return Err(ReplayError::FormatError),
+            }
+        }
+        Ok(Recording { frames })
+    }
+
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.frames
+    }
+
+    /// The number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The time of each frame, for a `ScriptedClock`.
+    pub fn times(&self) -> Vec<f64> {
+        self.frames.iter().map(|frame| frame.time).collect()
+    }
+
+    /// The events handled during `frame`, or none past the end.
+    pub fn events(&self, frame: u32) -> &[glfw::WindowEvent] {
+        match self.frames.get(frame as usize) {
+            Some(frame) => &frame.events,
+            None => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn temp_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("sb6_replay_{}_{}", name, ::std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// At least one event of every recorded type.
+    fn events() -> Vec<glfw::WindowEvent> {
+        vec![
+            glfw::WindowEvent::Key(Key::A, 38, Action::Press, Modifiers::Shift),
+            glfw::WindowEvent::Key(Key::Unknown, -1, Action::Repeat, Modifiers::empty()),
+            glfw::WindowEvent::Char('\u{e9}'),
+            glfw::WindowEvent::Size(640, 480),
+            glfw::WindowEvent::FramebufferSize(1280, 960),
+            glfw::WindowEvent::MouseButton(
+                MouseButton::Button8,
+                Action::Release,
+                Modifiers::Control | Modifiers::Alt,
+            ),
+            glfw::WindowEvent::CursorPos(12.5, -3.25),
+            glfw::WindowEvent::Scroll(0.0, -1.0),
+            glfw::WindowEvent::Focus(false),
+            glfw::WindowEvent::FileDrop(vec![PathBuf::from("a.ktx"), PathBuf::from("b c.sbm")]),
+        ]
+    }
+
+    fn record(path: &str) {
+        let mut recorder = Recorder::create(path).unwrap();
+        recorder.frame(0.0);
+        recorder.frame(0.25);
+        for event in events() {
+            recorder.event(&event);
+        }
+        // not passed to apps, so not recorded
+        recorder.event(&glfw::WindowEvent::Close);
+        recorder.frame(0.5);
+        recorder.event(&glfw::WindowEvent::Focus(true));
+        recorder.finish().unwrap();
+    }
+
+    fn recorded_bytes(name: &str) -> Vec<u8> {
+        let path = temp_path(name);
+        record(&path);
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round_trip");
+        record(&path);
+        let recording = Recording::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(recording.len(), 3);
+        assert_eq!(recording.times(), [0.0, 0.25, 0.5]);
+        assert!(recording.events(0).is_empty());
+        assert_eq!(recording.events(1), &events()[..]);
+        assert_eq!(recording.events(2), [glfw::WindowEvent::Focus(true)]);
+        assert!(recording.events(3).is_empty());
+    }
+
+    #[test]
+    fn empty_recording() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        assert!(Recording::parse(&bytes).unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = recorded_bytes("truncated");
+        // cutting inside the last record
+        match Recording::parse(&bytes[..bytes.len() - 1]) {
+            Err(ReplayError::FormatError) => (),
+            _ => panic!("truncated record was accepted"),
+        }
+        // every prefix either loads or fails cleanly
+        for len in 0..bytes.len() {
+            match Recording::parse(&bytes[..len]) {
+                Err(ReplayError::MagicError) => assert!(len < MAGIC.len()),
+                Err(ReplayError::FormatError) | Ok(_) => assert!(len >= MAGIC.len()),
+                Err(e) => panic!("unexpected error '{}' at length {}", e, len),
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_input() {
+        let bytes = recorded_bytes("corrupt");
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        match Recording::parse(&bad_magic) {
+            Err(ReplayError::MagicError) => (),
+            _ => panic!("bad magic was accepted"),
+        }
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = VERSION + 1;
+        match Recording::parse(&bad_version) {
+            Err(ReplayError::VersionError(v)) => assert_eq!(v, VERSION + 1),
+            _ => panic!("bad version was accepted"),
+        }
+
+        // an unknown tag after the first frame
+        let mut bad_tag = bytes[..14].to_vec();
+        bad_tag.push(0xff);
+        match Recording::parse(&bad_tag) {
+            Err(ReplayError::FormatError) => (),
+            _ => panic!("unknown tag was accepted"),
+        }
+
+        // an event before any frame
+        let mut orphan = bytes[..5].to_vec();
+        orphan.extend_from_slice(&[TAG_FOCUS, 1]);
+        match Recording::parse(&orphan) {
+            Err(ReplayError::FormatError) => (),
+            _ => panic!("event without a frame was accepted"),
+        }
+
+        // an out of range mouse button
+        let mut bad_button = bytes[..14].to_vec();
+        bad_button.extend_from_slice(&[TAG_MOUSE_BUTTON, 8, 0, 0]);
+        match Recording::parse(&bad_button) {
+            Err(ReplayError::FormatError) => (),
+            _ => panic!("bad mouse button was accepted"),
+        }
+    }
+}
@@ -24,7 +24,12 @@
 
 extern crate gl;
 
+use diagnostic::{self, Diagnostic};
 use gl::types::*;
+use gl_debug;
+use preprocessor;
+use preprocessor::{PreprocessError, Preprocessor, Source};
+use program::ProgramError;
 use std::ffi;
 use std::fmt;
 use std::fs::File;
@@ -32,7 +37,7 @@ use std::io;
 use std::io::Read;
 use std::iter;
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::ptr;
 
 #[derive(Clone, PartialEq, Debug)]
@@ -40,9 +45,68 @@ pub enum ShaderError {
     ShaderInfoLog(String),
 }
 
+impl fmt::Display for ShaderError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderError::ShaderInfoLog(ref log) => write!(fmt, "Shader compile failed:\n{}", log),
+        }
+    }
+}
+
+impl ShaderError {
+    /// The messages in the info log, see `sb6::diagnostic`.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match *self {
+            ShaderError::ShaderInfoLog(ref log) => diagnostic::parse_info_log(log),
+        }
+    }
+}
+
+/// A failed compile of a shader loaded from file.
+#[derive(Clone, Debug)]
+pub struct CompileError {
+    /// The info log as reported by the driver.
+    pub log: String,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The preprocessed source, mapping source string numbers to files.
+    pub source: Source,
+}
+
+impl CompileError {
+    fn new(log: String, source: Source) -> CompileError {
+        CompileError {
+            diagnostics: diagnostic::parse_info_log(&log),
+            log,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    /// Shows each diagnostic with the line it refers to, or the raw log when
+    /// the driver's format wasn't recognised.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.diagnostics.iter().all(|d| d.line.is_none()) {
+            return write!(fmt, "Shader compile failed:\n{}", self.log);
+        }
+        try!(writeln!(fmt, "Shader compile failed:"));
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                try!(writeln!(fmt));
+            }
+            let index = diagnostic.source_string.unwrap_or(0) as usize;
+            try!(diagnostic.render(fmt, self.source.file(index), self.source.contents(index)));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub enum LoadError {
-    CompileError(String),
+    CompileError(CompileError),
+    PreprocessError(PreprocessError),
+    /// Linking the stages of a multi-stage file failed, see `program::load`.
+    LinkError(PathBuf, ProgramError),
     IoError(io::Error),
 }
 
@@ -52,10 +116,18 @@ impl From<io::Error> for LoadError {
     }
 }
 
+impl From<PreprocessError> for LoadError {
+    fn from(e: PreprocessError) -> LoadError {
+        LoadError::PreprocessError(e)
+    }
+}
+
 impl fmt::Display for LoadError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             LoadError::CompileError(ref e) => write!(fmt, "{}", e),
+            LoadError::PreprocessError(ref e) => e.fmt(fmt),
+            LoadError::LinkError(ref path, ref e) => write!(fmt, "{}: {}", path.display(), e),
             LoadError::IoError(ref e) => e.fmt(fmt),
         }
     }
@@ -69,6 +141,27 @@ macro_rules! load_shader_or_panic {
     };
 }
 
+/// Loads a shader, returning an `sb6::Error` naming the file from the
+/// enclosing function on failure. An optional third argument is the
+/// `Preprocessor` to resolve includes with, as for `load_with`.
+#[macro_export]
+macro_rules! try_load_shader {
+    ($path:expr, $shader_type:expr) => {{
+        let path = $path;
+        match sb6::shader::load(path, $shader_type) {
+            Ok(shader) => shader,
+            Err(e) => return Err(sb6::Error::file(path, e)),
+        }
+    }};
+    ($path:expr, $shader_type:expr, $preprocessor:expr) => {{
+        let path = $path;
+        match sb6::shader::load_with(path, $shader_type, $preprocessor) {
+            Ok(shader) => shader,
+            Err(e) => return Err(sb6::Error::file(path, e)),
+        }
+    }};
+}
+
 pub fn check_compile_status(shader: GLuint) -> Result<(), ShaderError> {
     unsafe {
         // Get the compile status
@@ -102,20 +195,108 @@ pub fn create_from_source(src: &str, shader_type: GLenum) -> Result<GLuint, Shad
         let result = gl::CreateShader(shader_type);
         gl::ShaderSource(result, 1, &cstr.deref().as_ptr(), ptr::null());
         gl::CompileShader(result);
+        gl_debug::check_errors("shader::create_from_source");
         match check_compile_status(result) {
             Ok(_) => Ok(result),
-            Err(e) => Err(e),
+            Err(e) => {
+                gl::DeleteShader(result);
+                Err(e)
+            }
         }
     }
 }
 
+/// Loads and compiles a shader, resolving `#include` directives relative to
+/// the including file, see `sb6::preprocessor`. Use `load_with` and
+/// `Context::preprocessor` to also search the media `shaders` directory.
 pub fn load(filename: &str, shader_type: GLenum) -> Result<GLuint, LoadError> {
-    let mut file = try!(File::open(&Path::new(filename)));
+    load_with(filename, shader_type, &Preprocessor::new())
+}
+
+/// Like `load_with`, compiling a variant of the shader with
+/// `#define name value` added after its `#version` for each pair in
+/// `defines`. Pass `Context::preprocessor` to keep its include path and
+/// version limit.
+pub fn load_with_defines(
+    filename: &str,
+    shader_type: GLenum,
+    preprocessor: &Preprocessor,
+    defines: &[(&str, &str)],
+) -> Result<GLuint, LoadError> {
+    let mut preprocessor = preprocessor.clone();
+    for &(name, value) in defines {
+        preprocessor.define(name, value);
+    }
+    load_with(filename, shader_type, &preprocessor)
+}
+
+/// Loads and compiles a shader, resolving `#include` directives with
+/// `preprocessor`.
+pub fn load_with(
+    filename: &str,
+    shader_type: GLenum,
+    preprocessor: &Preprocessor,
+) -> Result<GLuint, LoadError> {
+    load_with_source(filename, shader_type, preprocessor).map(|(shader, _)| shader)
+}
+
+/// Like `load_with`, also returning the preprocessed source, whose `files`
+/// lists every file the shader was built from.
+pub fn load_with_source(
+    filename: &str,
+    shader_type: GLenum,
+    preprocessor: &Preprocessor,
+) -> Result<(GLuint, Source), LoadError> {
+    let path = Path::new(filename);
+    let src = try!(read_file(path));
+    compile(try!(preprocessor.process(&src, path)), shader_type)
+}
+
+/// Loads and compiles each stage of a file holding several stages, see
+/// `preprocessor::split_stages`. Returns the stages in the order they
+/// appear in the file.
+pub fn load_stages_with(
+    filename: &str,
+    preprocessor: &Preprocessor,
+) -> Result<Vec<(GLuint, Source)>, LoadError> {
+    let path = Path::new(filename);
+    let src = try!(read_file(path));
+    let stages = try!(preprocessor::split_stages(&src, path));
+    let mut shaders = Vec::with_capacity(stages.len());
+    for (stage, text) in stages {
+        let result = preprocessor
+            .process(&text, path)
+            .map_err(LoadError::from)
+            .and_then(|source| compile(source, stage.shader_type()));
+        match result {
+            Ok(shader) => shaders.push(shader),
+            Err(e) => {
+                for (shader, _) in shaders {
+                    unsafe {
+                        gl::DeleteShader(shader);
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(shaders)
+}
+
+fn read_file(path: &Path) -> Result<String, LoadError> {
+    let mut file = try!(File::open(path));
     let mut src = String::new();
     try!(file.read_to_string(&mut src));
+    Ok(src)
+}
 
-    match create_from_source(&src, shader_type) {
-        Ok(result) => Ok(result),
-        Err(ShaderError::ShaderInfoLog(msg)) => Err(LoadError::CompileError(msg)),
+/// Compiles preprocessed `source`, returning it with the shader so callers
+/// can see which files it was built from.
+pub(crate) fn compile(source: Source, shader_type: GLenum) -> Result<(GLuint, Source), LoadError> {
+    match create_from_source(&source.text, shader_type) {
+        Ok(result) => Ok((result, source)),
+        Err(ShaderError::ShaderInfoLog(log)) => {
+            Err(LoadError::CompileError(CompileError::new(log, source)))
+        }
     }
 }
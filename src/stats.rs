@@ -0,0 +1,381 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Frame timing statistics gathered by the run loop. CPU times are measured
+//! with the wall clock regardless of the `Clock` the app runs with, and GPU
+//! render times with `GL_TIME_ELAPSED` queries when available.
+
+extern crate gl;
+
+use gl::types::*;
+use gl_info;
+use gl_info::Feature;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+
+/// Number of frames kept in the history.
+pub const HISTORY_LENGTH: usize = 240;
+
+/// Timings for one frame, all in seconds.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameTiming {
+    pub frame: u32,
+    /// Wall time from the start of the frame to the end of event handling,
+    /// including waiting for the buffer swap.
+    pub cpu: f64,
+    /// Time spent in `App::update`, summed over all fixed updates.
+    pub update: f64,
+    /// Time spent in `App::render`. This is the time to submit the commands,
+    /// not to execute them.
+    pub render: f64,
+    /// GPU time spent executing the commands submitted by `App::render`.
+    /// Results arrive a few frames late and are `None` until then, or if
+    /// timer queries are not supported.
+    pub gpu: Option<f64>,
+}
+
+/// Statistics over a set of samples, in seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub samples: usize,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl Summary {
+    pub fn from_samples(samples: &[f64]) -> Summary {
+        if samples.is_empty() {
+            return Summary::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| {
+            let i = (p * (sorted.len() - 1) as f64).round() as usize;
+            sorted[i]
+        };
+        Summary {
+            samples: sorted.len(),
+            min: sorted[0],
+            avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            max: sorted[sorted.len() - 1],
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "min {:.2}ms avg {:.2}ms max {:.2}ms p50 {:.2}ms p95 {:.2}ms p99 {:.2}ms",
+            self.min * 1000.0,
+            self.avg * 1000.0,
+            self.max * 1000.0,
+            self.p50 * 1000.0,
+            self.p95 * 1000.0,
+            self.p99 * 1000.0
+        )
+    }
+}
+
+/// Number of timer queries in flight, results are read this many frames
+/// after they are issued.
+const GPU_QUERY_COUNT: usize = 4;
+
+/// A ring of `GL_TIME_ELAPSED` queries, so results can be read without
+/// stalling on the frame that was just submitted.
+struct GpuTimer {
+    queries: [GLuint; GPU_QUERY_COUNT],
+    frames: [Option<u32>; GPU_QUERY_COUNT],
+    next: usize,
+}
+
+impl GpuTimer {
+    fn new() -> GpuTimer {
+        let mut queries = [0; GPU_QUERY_COUNT];
+        unsafe {
+            gl::GenQueries(GPU_QUERY_COUNT as GLsizei, queries.as_mut_ptr());
+        }
+        GpuTimer {
+            queries,
+            frames: [None; GPU_QUERY_COUNT],
+            next: 0,
+        }
+    }
+
+    /// Reads the result of query `i`, waiting for it if `wait` is set.
+    fn result(&mut self, i: usize, wait: bool) -> Option<(u32, f64)> {
+        let frame = self.frames[i]?;
+        unsafe {
+            if !wait {
+                let mut available = 0;
+                gl::GetQueryObjectiv(self.queries[i], gl::QUERY_RESULT_AVAILABLE, &mut available);
+                if available == 0 {
+                    return None;
+                }
+            }
+            let mut elapsed: GLuint64 = 0;
+            gl::GetQueryObjectui64v(self.queries[i], gl::QUERY_RESULT, &mut elapsed);
+            self.frames[i] = None;
+            Some((frame, elapsed as f64 * 1.0e-9))
+        }
+    }
+
+    /// Starts timing `frame`. Returns the result of the query being reused if
+    /// it had not been read yet.
+    fn begin(&mut self, frame: u32) -> Option<(u32, f64)> {
+        let i = self.next;
+        let result = self.result(i, true);
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, self.queries[i]);
+        }
+        self.frames[i] = Some(frame);
+        result
+    }
+
+    fn end(&mut self) {
+        unsafe {
+            gl::EndQuery(gl::TIME_ELAPSED);
+        }
+        self.next = (self.next + 1) % GPU_QUERY_COUNT;
+    }
+
+    /// Returns the results of all queries that have finished.
+    fn poll(&mut self) -> Vec<(u32, f64)> {
+        (0..GPU_QUERY_COUNT)
+            .filter_map(|i| self.result(i, false))
+            .collect()
+    }
+
+    fn free(&mut self) {
+        unsafe {
+            gl::DeleteQueries(GPU_QUERY_COUNT as GLsizei, self.queries.as_ptr());
+        }
+        self.queries = [0; GPU_QUERY_COUNT];
+        self.frames = [None; GPU_QUERY_COUNT];
+    }
+}
+
+/// A rolling history of frame timings, available to apps through
+/// `Context::stats`.
+pub struct FrameStats {
+    history: VecDeque<FrameTiming>,
+    current: FrameTiming,
+    frame_start: Option<Instant>,
+    render_start: Option<Instant>,
+    gpu_timer: Option<GpuTimer>,
+}
+
+fn seconds_since(start: Option<Instant>) -> f64 {
+    match start {
+        Some(start) => {
+            let elapsed = start.elapsed();
+            elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1.0e-9
+        }
+        None => 0.0,
+    }
+}
+
+impl FrameStats {
+    pub(crate) fn new() -> FrameStats {
+        FrameStats {
+            history: VecDeque::with_capacity(HISTORY_LENGTH),
+            current: FrameTiming::default(),
+            frame_start: None,
+            render_start: None,
+            gpu_timer: None,
+        }
+    }
+
+    /// Starts timing the GPU with `GL_TIME_ELAPSED` queries if the context
+    /// supports them. Apps can't use their own `GL_TIME_ELAPSED` queries in
+    /// `render` while this is enabled, as they can't be nested.
+    pub(crate) fn enable_gpu_timer(&mut self) {
+        if gl_info::supports(Feature::TimerQuery) {
+            self.gpu_timer = Some(GpuTimer::new());
+        } else {
+            info!("Timer queries are not supported, GPU times are unavailable");
+        }
+    }
+
+    pub(crate) fn begin_frame(&mut self, frame: u32) {
+        self.current = FrameTiming {
+            frame,
+            ..FrameTiming::default()
+        };
+        self.frame_start = Some(Instant::now());
+    }
+
+    pub(crate) fn end_update(&mut self) {
+        self.current.update = seconds_since(self.frame_start);
+    }
+
+    pub(crate) fn begin_render(&mut self) {
+        self.render_start = Some(Instant::now());
+        let frame = self.current.frame;
+        let result = match self.gpu_timer {
+            Some(ref mut timer) => timer.begin(frame),
+            None => None,
+        };
+        if let Some((frame, gpu)) = result {
+            self.set_gpu_time(frame, gpu);
+        }
+    }
+
+    pub(crate) fn end_render(&mut self) {
+        if let Some(ref mut timer) = self.gpu_timer {
+            timer.end();
+        }
+        self.current.render = seconds_since(self.render_start);
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.current.cpu = seconds_since(self.frame_start);
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.current);
+
+        let results = match self.gpu_timer {
+            Some(ref mut timer) => timer.poll(),
+            None => Vec::new(),
+        };
+        for (frame, gpu) in results {
+            self.set_gpu_time(frame, gpu);
+        }
+    }
+
+    fn set_gpu_time(&mut self, frame: u32, gpu: f64) {
+        if let Some(timing) = self.history.iter_mut().rev().find(|t| t.frame == frame) {
+            timing.gpu = Some(gpu);
+        }
+    }
+
+    pub(crate) fn free(&mut self) {
+        if let Some(ref mut timer) = self.gpu_timer {
+            timer.free();
+        }
+        self.gpu_timer = None;
+    }
+
+    /// Timings for the most recent frames, oldest first.
+    pub fn history(&self) -> &VecDeque<FrameTiming> {
+        &self.history
+    }
+
+    /// Timings for the last completed frame.
+    pub fn last(&self) -> Option<&FrameTiming> {
+        self.history.back()
+    }
+
+    /// Returns true if GPU times are being measured.
+    pub fn has_gpu_times(&self) -> bool {
+        self.gpu_timer.is_some()
+    }
+
+    /// Summarizes the value returned by `f` over the history, skipping
+    /// frames where it returns `None`.
+    pub fn summary<F: Fn(&FrameTiming) -> Option<f64>>(&self, f: F) -> Summary {
+        let samples: Vec<f64> = self.history.iter().filter_map(f).collect();
+        Summary::from_samples(&samples)
+    }
+
+    pub fn cpu_summary(&self) -> Summary {
+        self.summary(|t| Some(t.cpu))
+    }
+
+    pub fn update_summary(&self) -> Summary {
+        self.summary(|t| Some(t.update))
+    }
+
+    pub fn render_summary(&self) -> Summary {
+        self.summary(|t| Some(t.render))
+    }
+
+    pub fn gpu_summary(&self) -> Summary {
+        self.summary(|t| t.gpu)
+    }
+
+    /// Frames per second, from the average CPU frame time.
+    pub fn fps(&self) -> f64 {
+        let avg = self.cpu_summary().avg;
+        if avg > 0.0 {
+            1.0 / avg
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_summary() {
+        assert_eq!(Summary::from_samples(&[]), Summary::default());
+    }
+
+    #[test]
+    fn single_sample() {
+        let s = Summary::from_samples(&[0.5]);
+        assert_eq!(s.samples, 1);
+        assert_eq!((s.min, s.avg, s.max), (0.5, 0.5, 0.5));
+        assert_eq!((s.p50, s.p95, s.p99), (0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn min_avg_max_ignore_order() {
+        let s = Summary::from_samples(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(s.samples, 4);
+        assert_eq!((s.min, s.avg, s.max), (1.0, 2.5, 4.0));
+    }
+
+    #[test]
+    fn percentiles_round_to_nearest_rank() {
+        // 1..=101, so percentile p is at index p * 100
+        let samples: Vec<f64> = (1..102).rev().map(f64::from).collect();
+        let s = Summary::from_samples(&samples);
+        assert_eq!((s.p50, s.p95, s.p99), (51.0, 96.0, 100.0));
+
+        // index 0.95 * 9 = 8.55 rounds to 9, 0.5 * 9 = 4.5 rounds up to 5
+        let samples: Vec<f64> = (0..10).map(f64::from).collect();
+        let s = Summary::from_samples(&samples);
+        assert_eq!((s.p50, s.p95, s.p99), (5.0, 9.0, 9.0));
+    }
+
+    #[test]
+    fn nan_does_not_panic() {
+        let s = Summary::from_samples(&[2.0, f64::NAN, 1.0]);
+        assert_eq!(s.samples, 3);
+        assert_eq!(s.min, 1.0);
+    }
+}
@@ -0,0 +1,219 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+
+use error::Error;
+use gl::types::*;
+use gl_state::SavedState;
+use program;
+use shader;
+use stats::{FrameStats, HISTORY_LENGTH};
+use std::mem;
+use std::ptr;
+
+const VS_SRC: &str = r"
+#version 330 core
+
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec4 color;
+
+out vec4 vs_color;
+
+void main(void)
+{
+    gl_Position = vec4(position, 0.0, 1.0);
+    vs_color = color;
+}
+";
+
+const FS_SRC: &str = r"
+#version 330 core
+
+in vec4 vs_color;
+
+out vec4 color;
+
+void main(void)
+{
+    color = vs_color;
+}
+";
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const GRID: [f32; 4] = [1.0, 1.0, 1.0, 0.3];
+const UPDATE: [f32; 4] = [0.2, 0.4, 1.0, 0.9];
+const RENDER: [f32; 4] = [0.2, 0.9, 0.3, 0.9];
+const OTHER: [f32; 4] = [0.6, 0.6, 0.6, 0.9];
+const GPU: [f32; 4] = [1.0, 0.5, 0.0, 1.0];
+
+/// Height of a grid line, one frame at 60Hz.
+const GRID_STEP: f64 = 1.0 / 60.0;
+
+const MARGIN: i32 = 8;
+const MAX_WIDTH: i32 = 480;
+const MAX_HEIGHT: i32 = 160;
+
+/// Position and color, 6 floats per vertex.
+const VERTEX_SIZE: usize = 6;
+
+/// Draws the frame time history as a bar graph in the bottom left corner.
+/// Each bar is the CPU frame time split into update, render and the rest,
+/// with the GPU time marked in orange. Grid lines are one 60Hz frame apart.
+pub(crate) struct StatsOverlay {
+    program: GLuint,
+    vao: GLuint,
+    buffer: GLuint,
+    vertices: Vec<f32>,
+}
+
+impl StatsOverlay {
+    pub(crate) fn new() -> Result<StatsOverlay, Error> {
+        let vs = try!(shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
+        let fs = try!(shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+        let program = try!(program::link_from_shaders(&[vs, fs]));
+
+        let mut overlay = StatsOverlay {
+            program,
+            vao: 0,
+            buffer: 0,
+            vertices: Vec::new(),
+        };
+        let stride = (VERTEX_SIZE * mem::size_of::<f32>()) as GLsizei;
+        unsafe {
+            gl::GenVertexArrays(1, &mut overlay.vao);
+            gl::BindVertexArray(overlay.vao);
+            gl::GenBuffers(1, &mut overlay.buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, overlay.buffer);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(
+                1,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * mem::size_of::<f32>()) as *const _,
+            );
+            gl::EnableVertexAttribArray(1);
+            gl::BindVertexArray(0);
+        }
+        Ok(overlay)
+    }
+
+    /// Adds a rectangle in normalized device coordinates.
+    fn rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: [f32; 4]) {
+        for &(x, y) in [(x0, y0), (x1, y0), (x1, y1), (x0, y0), (x1, y1), (x0, y1)].iter() {
+            self.vertices.extend_from_slice(&[x, y]);
+            self.vertices.extend_from_slice(&color);
+        }
+    }
+
+    fn build(&mut self, stats: &FrameStats) {
+        self.vertices.clear();
+        self.rect(-1.0, -1.0, 1.0, 1.0, BACKGROUND);
+
+        // scale to whole 60Hz frames, at least two
+        let max = stats
+            .history()
+            .iter()
+            .map(|t| t.gpu.unwrap_or(0.0).max(t.cpu))
+            .fold(0.0, f64::max);
+        let steps = (max / GRID_STEP).ceil().max(2.0);
+        let scale = 2.0 / (steps * GRID_STEP);
+        let y = |t: f64| (-1.0 + t * scale) as f32;
+
+        let line = 2.0 / MAX_HEIGHT as f32;
+        for i in 1..steps as u32 {
+            let grid_y = y(f64::from(i) * GRID_STEP);
+            self.rect(-1.0, grid_y, 1.0, grid_y + line, GRID);
+        }
+
+        let bar_width = 2.0 / HISTORY_LENGTH as f32;
+        let offset = HISTORY_LENGTH - stats.history().len();
+        for (i, t) in stats.history().iter().enumerate() {
+            let x0 = -1.0 + (offset + i) as f32 * bar_width;
+            let x1 = x0 + bar_width;
+            let update_end = t.update;
+            let render_end = t.update + t.render;
+            self.rect(x0, y(0.0), x1, y(update_end), UPDATE);
+            self.rect(x0, y(update_end), x1, y(render_end), RENDER);
+            if t.cpu > render_end {
+                self.rect(x0, y(render_end), x1, y(t.cpu), OTHER);
+            }
+            if let Some(gpu) = t.gpu {
+                self.rect(x0, y(gpu) - line, x1, y(gpu) + line, GPU);
+            }
+        }
+    }
+
+    /// Draws the graph over the frame in `framebuffer`, which is
+    /// `framebuffer_size` pixels.
+    pub(crate) fn draw(
+        &mut self,
+        stats: &FrameStats,
+        framebuffer: GLuint,
+        framebuffer_size: (u32, u32),
+    ) {
+        let width = (framebuffer_size.0 as i32 - 2 * MARGIN).min(MAX_WIDTH);
+        let height = (framebuffer_size.1 as i32 / 4).min(MAX_HEIGHT);
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        self.build(stats);
+
+        let saved = SavedState::save();
+        SavedState::set_overlay_state(framebuffer);
+        unsafe {
+            gl::Viewport(MARGIN, MARGIN, width, height);
+            gl::UseProgram(self.program);
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (self.vertices.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                self.vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+            gl::DrawArrays(
+                gl::TRIANGLES,
+                0,
+                (self.vertices.len() / VERTEX_SIZE) as GLsizei,
+            );
+        }
+        saved.restore();
+    }
+
+    pub(crate) fn free(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+        self.program = 0;
+        self.vao = 0;
+        self.buffer = 0;
+    }
+}
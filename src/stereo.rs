@@ -0,0 +1,343 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! Stereo rendering. When `AppInfo::stereo` is set the run loop calls
+//! `App::render_eye` once per eye instead of `render`. With a quad buffered
+//! stereo framebuffer each eye draws to its own back buffer. Otherwise each
+//! eye draws to an offscreen target and the two are composed side by side or
+//! as a red/cyan anaglyph, chosen by `AppInfo::stereo_fallback`.
+//!
+//! Use `vmath::stereo_view` and `vmath::stereo_frustum` with `Eye::offset`
+//! to build each eye's matrices.
+
+extern crate gl;
+
+use error::Error;
+use gl::types::*;
+use gl_state::SavedState;
+use offscreen::FramebufferError;
+use program;
+use shader;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    /// The horizontal offset of this eye from the center of the head, for
+    /// eyes `separation` apart. The left eye is negative.
+    pub fn offset(self, separation: f32) -> f32 {
+        match self {
+            Eye::Left => -0.5 * separation,
+            Eye::Right => 0.5 * separation,
+        }
+    }
+}
+
+/// How the two eyes reach the screen.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum StereoMode {
+    /// Separate left and right back buffers, for stereo capable displays.
+    QuadBuffer,
+    /// The left eye in the left half of the window and the right eye in the
+    /// right half, for 3D TVs and headsets.
+    SideBySide,
+    /// The left eye's red channel combined with the right eye's green and
+    /// blue, for red/cyan glasses.
+    Anaglyph,
+}
+
+const VS_SRC: &str = r"
+#version 330 core
+
+out vec2 uv;
+
+void main(void)
+{
+    vec2 position = vec2(gl_VertexID & 1, gl_VertexID >> 1) * 4.0 - 1.0;
+    uv = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+";
+
+const FS_SRC: &str = r"
+#version 330 core
+
+uniform sampler2D eye;
+uniform vec3 channels;
+
+in vec2 uv;
+out vec4 color;
+
+void main(void)
+{
+    color = vec4(texture(eye, uv).rgb * channels, 1.0);
+}
+";
+
+/// An offscreen color texture and depth/stencil buffer for one eye.
+struct EyeTarget {
+    fbo: GLuint,
+    color: GLuint,
+    depth_stencil: GLuint,
+}
+
+impl EyeTarget {
+    fn new() -> EyeTarget {
+        let mut target = EyeTarget {
+            fbo: 0,
+            color: 0,
+            depth_stencil: 0,
+        };
+        unsafe {
+            gl::GenFramebuffers(1, &mut target.fbo);
+            gl::GenTextures(1, &mut target.color);
+            gl::GenRenderbuffers(1, &mut target.depth_stencil);
+        }
+        target
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.color);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ::std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_stencil);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.color,
+                0,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth_stencil,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(FramebufferError::Incomplete(status));
+            }
+        }
+        Ok(())
+    }
+
+    fn free(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.color);
+            gl::DeleteRenderbuffers(1, &self.depth_stencil);
+        }
+        self.fbo = 0;
+        self.color = 0;
+        self.depth_stencil = 0;
+    }
+}
+
+/// The offscreen targets and composition pass for the fallback modes.
+struct Composer {
+    targets: [EyeTarget; 2],
+    size: (u32, u32),
+    program: GLuint,
+    channels_location: GLint,
+    vao: GLuint,
+}
+
+/// Directs each eye's rendering to the right place and composes the eyes
+/// into the window for the fallback modes.
+pub(crate) struct StereoRenderer {
+    mode: StereoMode,
+    composer: Option<Composer>,
+}
+
+impl StereoRenderer {
+    pub(crate) fn new(mode: StereoMode) -> Result<StereoRenderer, Error> {
+        if mode == StereoMode::QuadBuffer {
+            return Ok(StereoRenderer {
+                mode,
+                composer: None,
+            });
+        }
+        let vs = try!(shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
+        let fs = try!(shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+        let program = try!(program::link_from_shaders(&[vs, fs]));
+        let channels_location = try!(program::get_uniform_location(program, "channels"));
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        Ok(StereoRenderer {
+            mode,
+            composer: Some(Composer {
+                targets: [EyeTarget::new(), EyeTarget::new()],
+                size: (0, 0),
+                program,
+                channels_location,
+                vao,
+            }),
+        })
+    }
+
+    pub(crate) fn mode(&self) -> StereoMode {
+        self.mode
+    }
+
+    /// Returns the size each eye renders at for a window framebuffer of
+    /// `size`, resizing the eye targets if needed.
+    pub(crate) fn prepare(&mut self, size: (u32, u32)) -> Result<(u32, u32), FramebufferError> {
+        let eye_size = match self.mode {
+            StereoMode::SideBySide => ((size.0 / 2).max(1), size.1.max(1)),
+            _ => (size.0.max(1), size.1.max(1)),
+        };
+        if let Some(ref mut composer) = self.composer {
+            if composer.size != eye_size {
+                for target in composer.targets.iter_mut() {
+                    try!(target.resize(eye_size.0, eye_size.1));
+                }
+                composer.size = eye_size;
+            }
+        }
+        Ok(eye_size)
+    }
+
+    /// Binds the framebuffer `eye` draws to and returns it. `framebuffer` is
+    /// the window's default framebuffer.
+    pub(crate) fn begin_eye(&self, eye: Eye, framebuffer: GLuint) -> GLuint {
+        let target = match self.composer {
+            Some(ref composer) => composer.targets[eye as usize].fbo,
+            None => framebuffer,
+        };
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target);
+            // only the window's own framebuffer has left and right buffers
+            if self.mode == StereoMode::QuadBuffer && target == 0 {
+                gl::DrawBuffer(match eye {
+                    Eye::Left => gl::BACK_LEFT,
+                    Eye::Right => gl::BACK_RIGHT,
+                });
+            }
+        }
+        target
+    }
+
+    /// Composes both eyes into `framebuffer`, which is `size` pixels.
+    pub(crate) fn finish(&self, framebuffer: GLuint, size: (u32, u32)) {
+        let composer = match self.composer {
+            Some(ref composer) => composer,
+            None => {
+                if framebuffer == 0 {
+                    unsafe {
+                        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                        gl::DrawBuffer(gl::BACK);
+                    }
+                }
+                return;
+            }
+        };
+        let saved = SavedState::save();
+        SavedState::set_overlay_state(framebuffer);
+        let (width, height) = (size.0 as GLsizei, size.1 as GLsizei);
+        unsafe {
+            gl::UseProgram(composer.program);
+            gl::BindVertexArray(composer.vao);
+            gl::ActiveTexture(gl::TEXTURE0);
+            for (i, target) in composer.targets.iter().enumerate() {
+                gl::BindTexture(gl::TEXTURE_2D, target.color);
+                match self.mode {
+                    StereoMode::SideBySide => {
+                        gl::Disable(gl::BLEND);
+                        let half = width / 2;
+                        gl::Viewport(half * i as GLsizei, 0, half, height);
+                        gl::Uniform3f(composer.channels_location, 1.0, 1.0, 1.0);
+                    }
+                    _ => {
+                        // red from the left eye, then add green and blue from
+                        // the right
+                        gl::Viewport(0, 0, width, height);
+                        if i == 0 {
+                            gl::Disable(gl::BLEND);
+                            gl::Uniform3f(composer.channels_location, 1.0, 0.0, 0.0);
+                        } else {
+                            gl::Enable(gl::BLEND);
+                            gl::BlendFunc(gl::ONE, gl::ONE);
+                            gl::Uniform3f(composer.channels_location, 0.0, 1.0, 1.0);
+                        }
+                    }
+                }
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+        }
+        saved.restore();
+    }
+
+    pub(crate) fn free(&mut self) {
+        if let Some(ref mut composer) = self.composer {
+            for target in composer.targets.iter_mut() {
+                target.free();
+            }
+            unsafe {
+                gl::DeleteProgram(composer.program);
+                gl::DeleteVertexArrays(1, &composer.vao);
+            }
+        }
+        self.composer = None;
+    }
+}
@@ -0,0 +1,596 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! A grid of text drawn over the frame with an embedded 8x8 bitmap font,
+//! for printing help and stats from `App::render`. Port of SB6's
+//! `sb6::text_overlay`.
+//!
+//! ```ignore
+//! use std::fmt::Write;
+//!
+//! self.overlay.clear();
+//! self.overlay.print("R: Toggle random points\n");
+//! write!(self.overlay, "Points: {}", self.point_count).unwrap();
+//! self.overlay.draw(ctx);
+//! ```
+
+extern crate gl;
+
+use app::default_framebuffer;
+use context::Context;
+use error::Error;
+use font8x8;
+use gl::types::*;
+use gl_state::SavedState;
+use program;
+use shader;
+use std::fmt;
+use std::mem;
+use std::ptr;
+
+const VS_SRC: &str = r"
+#version 330 core
+
+// column, row and character of this quad
+layout (location = 0) in uvec4 cell;
+
+uniform vec2 cell_size;
+
+out vec2 uv;
+
+void main(void)
+{
+    vec2 corner = vec2(gl_VertexID & 1, gl_VertexID >> 1);
+    vec2 position = vec2(-1.0, 1.0) + (vec2(cell.xy) + corner) * vec2(cell_size.x, -cell_size.y);
+    gl_Position = vec4(position, 0.0, 1.0);
+    uv = (vec2(cell.z % 16u, cell.z / 16u) + corner) / vec2(16.0, 8.0);
+}
+";
+
+const FS_SRC: &str = r"
+#version 330 core
+
+uniform sampler2D font;
+uniform vec4 text_color;
+uniform vec4 background_color;
+
+in vec2 uv;
+
+out vec4 color;
+
+void main(void)
+{
+    color = mix(background_color, text_color, texture(font, uv).r);
+}
+";
+
+/// Glyphs in the font texture are laid out in a 16x8 grid by character code.
+const FONT_COLUMNS: usize = 16;
+const FONT_ROWS: usize = 8;
+const GLYPH_SIZE: usize = 8;
+
+fn create_font_texture() -> GLuint {
+    let width = FONT_COLUMNS * GLYPH_SIZE;
+    let mut pixels = vec![0u8; width * FONT_ROWS * GLYPH_SIZE];
+    for c in 0..(FONT_COLUMNS * FONT_ROWS) as u8 {
+        let glyph = match font8x8::glyph(c) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        let x0 = (c as usize % FONT_COLUMNS) * GLYPH_SIZE;
+        let y0 = (c as usize / FONT_COLUMNS) * GLYPH_SIZE;
+        for (y, bits) in glyph.iter().enumerate() {
+            for x in 0..GLYPH_SIZE {
+                if bits & (1 << x) != 0 {
+                    pixels[(y0 + y) * width + x0 + x] = 0xff;
+                }
+            }
+        }
+    }
+
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::R8 as GLint,
+            width as GLsizei,
+            (FONT_ROWS * GLYPH_SIZE) as GLsizei,
+            0,
+            gl::RED,
+            gl::UNSIGNED_BYTE,
+            pixels.as_ptr() as *const _,
+        );
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_S,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_T,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+    }
+    texture
+}
+
+/// The character used for `c` in the grid: `c` itself if the font has a
+/// glyph for it, otherwise `?`.
+fn grid_char(c: char) -> u8 {
+    if c.is_ascii() && font8x8::glyph(c as u8).is_some() {
+        c as u8
+    } else {
+        b'?'
+    }
+}
+
+/// A `columns` by `rows` grid of characters with a cursor, the text behind a
+/// `TextOverlay`. Text printed past the last row scrolls the grid up.
+#[derive(Clone, Debug)]
+pub struct TextGrid {
+    columns: usize,
+    rows: usize,
+    chars: Vec<u8>,
+    cursor_x: usize,
+    cursor_y: usize,
+}
+
+impl TextGrid {
+    pub fn new(columns: usize, rows: usize) -> TextGrid {
+        TextGrid {
+            columns,
+            rows,
+            chars: vec![b' '; columns * rows],
+            cursor_x: 0,
+            cursor_y: 0,
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The characters in row `y`, with `?` for characters the font lacks.
+    pub fn row(&self, y: usize) -> &[u8] {
+        &self.chars[y * self.columns..(y + 1) * self.columns]
+    }
+
+    /// Clears the text and moves the cursor to the top left.
+    pub fn clear(&mut self) {
+        for c in self.chars.iter_mut() {
+            *c = b' ';
+        }
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+    }
+
+    pub fn move_cursor(&mut self, x: usize, y: usize) {
+        self.cursor_x = x.min(self.columns);
+        self.cursor_y = y.min(self.rows.saturating_sub(1));
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_x, self.cursor_y)
+    }
+
+    /// Moves the text up by `lines` rows, clearing the rows at the bottom.
+    pub fn scroll(&mut self, lines: usize) {
+        let lines = lines.min(self.rows);
+        let start = lines * self.columns;
+        let len = self.chars.len();
+        self.chars.copy_within(start..len, 0);
+        for c in self.chars[len - start..].iter_mut() {
+            *c = b' ';
+        }
+        self.cursor_y = self.cursor_y.saturating_sub(lines);
+    }
+
+    fn new_line(&mut self) {
+        self.cursor_x = 0;
+        if self.cursor_y + 1 < self.rows {
+            self.cursor_y += 1;
+        } else {
+            self.scroll(1);
+            self.cursor_y = self.rows - 1;
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        if self.rows == 0 || self.columns == 0 {
+            return;
+        }
+        match c {
+            '\n' => self.new_line(),
+            '\r' => self.cursor_x = 0,
+            '\t' => {
+                let next = (self.cursor_x / 4 + 1) * 4;
+                while self.cursor_x < next.min(self.columns) {
+                    self.put(' ');
+                }
+            }
+            _ => {
+                if self.cursor_x >= self.columns {
+                    self.new_line();
+                }
+                self.chars[self.cursor_y * self.columns + self.cursor_x] = grid_char(c);
+                self.cursor_x += 1;
+            }
+        }
+    }
+
+    /// Prints `text` at the cursor, wrapping at the last column. Tabs stop
+    /// every 4 columns. Characters the font lacks, including anything outside
+    /// ASCII, are shown as one `?` each.
+    pub fn print(&mut self, text: &str) {
+        for c in text.chars() {
+            self.put(c);
+        }
+    }
+
+    /// Prints `text` starting at column `x` and row `y` without moving the
+    /// cursor. Text past the last column is clipped, and characters are
+    /// shown as for `print`, with control characters as `?`.
+    pub fn print_at(&mut self, x: usize, y: usize, text: &str) {
+        if y >= self.rows {
+            return;
+        }
+        for (i, c) in text.chars().enumerate() {
+            if x + i >= self.columns {
+                break;
+            }
+            self.chars[y * self.columns + x + i] = grid_char(c);
+        }
+    }
+
+    /// The column, row and character of each cell that isn't blank.
+    fn cells<'a>(&'a self) -> impl Iterator<Item = (usize, usize, u8)> + 'a {
+        let columns = self.columns;
+        self.chars
+            .iter()
+            .enumerate()
+            .filter(|&(_, &c)| c != b' ')
+            .map(move |(i, &c)| (i % columns, i / columns, c))
+    }
+}
+
+impl fmt::Write for TextGrid {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.print(s);
+        Ok(())
+    }
+}
+
+/// A `TextGrid` drawn from the top left of the frame.
+pub struct TextOverlay {
+    grid: TextGrid,
+    scale: u32,
+    text_color: [f32; 4],
+    background_color: [f32; 4],
+    dirty: bool,
+    instance_count: usize,
+    program: GLuint,
+    vao: GLuint,
+    instance_buffer: GLuint,
+    font_texture: GLuint,
+    cell_size_location: GLint,
+    text_color_location: GLint,
+    background_color_location: GLint,
+}
+
+impl TextOverlay {
+    pub fn new(columns: usize, rows: usize) -> Result<TextOverlay, Error> {
+        let vs = try!(shader::create_from_source(VS_SRC, gl::VERTEX_SHADER));
+        let fs = try!(shader::create_from_source(FS_SRC, gl::FRAGMENT_SHADER));
+        let program = try!(program::link_from_shaders(&[vs, fs]));
+        let cell_size_location = try!(program::get_uniform_location(program, "cell_size"));
+        let text_color_location = try!(program::get_uniform_location(program, "text_color"));
+        let background_color_location =
+            try!(program::get_uniform_location(program, "background_color"));
+        let font_location = try!(program::get_uniform_location(program, "font"));
+
+        let mut overlay = TextOverlay {
+            grid: TextGrid::new(columns, rows),
+            scale: 1,
+            text_color: [1.0, 1.0, 1.0, 1.0],
+            background_color: [0.0, 0.0, 0.0, 0.5],
+            dirty: true,
+            instance_count: 0,
+            program,
+            vao: 0,
+            instance_buffer: 0,
+            font_texture: create_font_texture(),
+            cell_size_location,
+            text_color_location,
+            background_color_location,
+        };
+
+        unsafe {
+            gl::UseProgram(program);
+            gl::Uniform1i(font_location, 0);
+            gl::UseProgram(0);
+
+            gl::GenVertexArrays(1, &mut overlay.vao);
+            gl::BindVertexArray(overlay.vao);
+            gl::GenBuffers(1, &mut overlay.instance_buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, overlay.instance_buffer);
+            gl::VertexAttribIPointer(
+                0,
+                4,
+                gl::UNSIGNED_SHORT,
+                (4 * mem::size_of::<u16>()) as GLsizei,
+                ptr::null(),
+            );
+            gl::VertexAttribDivisor(0, 1);
+            gl::EnableVertexAttribArray(0);
+            gl::BindVertexArray(0);
+        }
+
+        Ok(overlay)
+    }
+
+    pub fn columns(&self) -> usize {
+        self.grid.columns()
+    }
+
+    pub fn rows(&self) -> usize {
+        self.grid.rows()
+    }
+
+    /// The text being drawn.
+    pub fn grid(&self) -> &TextGrid {
+        &self.grid
+    }
+
+    /// The text being drawn, to change it directly.
+    pub fn grid_mut(&mut self) -> &mut TextGrid {
+        self.dirty = true;
+        &mut self.grid
+    }
+
+    /// Draws each font pixel as a `scale` by `scale` block of screen
+    /// coordinates.
+    pub fn set_scale(&mut self, scale: u32) {
+        self.scale = scale.max(1);
+    }
+
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.text_color = color;
+    }
+
+    /// The color drawn behind each character, transparent to disable.
+    pub fn set_background_color(&mut self, color: [f32; 4]) {
+        self.background_color = color;
+    }
+
+    /// See `TextGrid::clear`.
+    pub fn clear(&mut self) {
+        self.grid_mut().clear();
+    }
+
+    pub fn move_cursor(&mut self, x: usize, y: usize) {
+        self.grid.move_cursor(x, y);
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.grid.cursor()
+    }
+
+    /// See `TextGrid::scroll`.
+    pub fn scroll(&mut self, lines: usize) {
+        self.grid_mut().scroll(lines);
+    }
+
+    /// See `TextGrid::print`. Use `write!` for formatted text.
+    pub fn print(&mut self, text: &str) {
+        self.grid_mut().print(text);
+    }
+
+    /// See `TextGrid::print_at`.
+    pub fn print_at(&mut self, x: usize, y: usize, text: &str) {
+        self.grid_mut().print_at(x, y, text);
+    }
+
+    fn update_instances(&mut self) {
+        let mut instances: Vec<u16> = Vec::new();
+        for (x, y, c) in self.grid.cells() {
+            instances.extend_from_slice(&[x as u16, y as u16, u16::from(c), 0]);
+        }
+        self.instance_count = instances.len() / 4;
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (instances.len() * mem::size_of::<u16>()) as GLsizeiptr,
+                instances.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+        self.dirty = false;
+    }
+
+    /// Draws the text over the frame in `sb6::default_framebuffer()`. GL state
+    /// changed while drawing is restored afterwards.
+    pub fn draw(&mut self, ctx: &Context) {
+        let saved = SavedState::save();
+        SavedState::set_overlay_state(default_framebuffer());
+
+        if self.dirty {
+            self.update_instances();
+        }
+
+        let (width, height) = ctx.framebuffer_size();
+        let (scale_x, scale_y) = ctx.content_scale();
+        let cell_width = (GLYPH_SIZE as f32) * self.scale as f32 * scale_x.round().max(1.0);
+        let cell_height = (GLYPH_SIZE as f32) * self.scale as f32 * scale_y.round().max(1.0);
+
+        unsafe {
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+            gl::UseProgram(self.program);
+            gl::Uniform2f(
+                self.cell_size_location,
+                2.0 * cell_width / width.max(1) as f32,
+                2.0 * cell_height / height.max(1) as f32,
+            );
+            gl::Uniform4fv(self.text_color_location, 1, self.text_color.as_ptr());
+            gl::Uniform4fv(
+                self.background_color_location,
+                1,
+                self.background_color.as_ptr(),
+            );
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.font_texture);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, self.instance_count as GLsizei);
+        }
+
+        saved.restore();
+    }
+
+    pub fn free(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.instance_buffer);
+            gl::DeleteTextures(1, &self.font_texture);
+        }
+        self.program = 0;
+        self.vao = 0;
+        self.instance_buffer = 0;
+        self.font_texture = 0;
+    }
+}
+
+impl fmt::Write for TextOverlay {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.print(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextGrid;
+
+    fn rows(grid: &TextGrid) -> Vec<String> {
+        (0..grid.rows())
+            .map(|y| String::from_utf8(grid.row(y).to_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn print_wraps_at_the_last_column() {
+        let mut grid = TextGrid::new(4, 3);
+        grid.print("abcdef");
+        assert_eq!(rows(&grid), ["abcd", "ef  ", "    "]);
+        assert_eq!(grid.cursor(), (2, 1));
+    }
+
+    #[test]
+    fn tabs_stop_every_four_columns() {
+        let mut grid = TextGrid::new(10, 1);
+        grid.print("a\tb\tc");
+        assert_eq!(rows(&grid), ["a   b   c "]);
+
+        let mut grid = TextGrid::new(6, 2);
+        grid.print("abcde\tf");
+        assert_eq!(rows(&grid), ["abcde ", "f     "]);
+    }
+
+    #[test]
+    fn newline_and_carriage_return() {
+        let mut grid = TextGrid::new(4, 2);
+        grid.print("ab\ncd\rx");
+        assert_eq!(rows(&grid), ["ab  ", "xd  "]);
+        assert_eq!(grid.cursor(), (1, 1));
+    }
+
+    #[test]
+    fn printing_past_the_last_row_scrolls() {
+        let mut grid = TextGrid::new(3, 2);
+        grid.print("one\ntwo\nsix");
+        assert_eq!(rows(&grid), ["two", "six"]);
+        assert_eq!(grid.cursor(), (3, 1));
+
+        grid.print("\n");
+        assert_eq!(rows(&grid), ["six", "   "]);
+        assert_eq!(grid.cursor(), (0, 1));
+    }
+
+    #[test]
+    fn scroll_moves_the_cursor_up() {
+        let mut grid = TextGrid::new(2, 3);
+        grid.print("aa\nbb\ncc");
+        grid.scroll(2);
+        assert_eq!(rows(&grid), ["cc", "  ", "  "]);
+        assert_eq!(grid.cursor(), (2, 0));
+
+        grid.scroll(5);
+        assert_eq!(rows(&grid), ["  ", "  ", "  "]);
+    }
+
+    #[test]
+    fn print_at_clips_and_keeps_the_cursor() {
+        let mut grid = TextGrid::new(4, 2);
+        grid.move_cursor(1, 1);
+        grid.print_at(2, 0, "xyz");
+        grid.print_at(0, 2, "off the grid");
+        assert_eq!(rows(&grid), ["  xy", "    "]);
+        assert_eq!(grid.cursor(), (1, 1));
+    }
+
+    #[test]
+    fn move_cursor_stays_on_the_grid() {
+        let mut grid = TextGrid::new(4, 2);
+        grid.move_cursor(10, 10);
+        assert_eq!(grid.cursor(), (4, 1));
+    }
+
+    #[test]
+    fn non_ascii_is_one_question_mark_per_char() {
+        let mut grid = TextGrid::new(6, 2);
+        grid.print("é→a");
+        grid.print_at(0, 1, "ü\u{7}b");
+        assert_eq!(rows(&grid), ["??a   ", "??b   "]);
+    }
+
+    #[test]
+    fn clear_resets_text_and_cursor() {
+        let mut grid = TextGrid::new(3, 2);
+        grid.print("abc\nd");
+        grid.clear();
+        assert_eq!(rows(&grid), ["   ", "   "]);
+        assert_eq!(grid.cursor(), (0, 0));
+    }
+}
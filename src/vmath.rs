@@ -244,6 +244,57 @@ pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
     }
 }
 
+/// A perspective projection for a frustum whose near plane spans `left` to
+/// `right` and `bottom` to `top`, like `glFrustum`.
+pub fn frustum(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+    let width = right - left;
+    let height = top - bottom;
+    let depth = far - near;
+    Mat4 {
+        col0: vec4(2.0 * near / width, 0.0, 0.0, 0.0),
+        col1: vec4(0.0, 2.0 * near / height, 0.0, 0.0),
+        col2: vec4(
+            (right + left) / width,
+            (top + bottom) / height,
+            -(far + near) / depth,
+            -1.0,
+        ),
+        col3: vec4(0.0, 0.0, -2.0 * far * near / depth, 0.0),
+    }
+}
+
+/// The projection for one eye of a stereo pair, where `eye_offset` is the
+/// eye's horizontal distance from the center of the head (see
+/// `stereo::Eye::offset`). The frustum is sheared rather than rotated so both
+/// eyes' images line up at `convergence` units from the viewer, which is
+/// where objects appear at the depth of the screen.
+pub fn stereo_frustum(
+    fovy: f32,
+    aspect: f32,
+    near: f32,
+    far: f32,
+    eye_offset: f32,
+    convergence: f32,
+) -> Mat4 {
+    let top = near * deg_to_rad(0.5 * fovy).tan();
+    let half_width = aspect * top;
+    let shift = eye_offset * near / convergence;
+    frustum(
+        -half_width - shift,
+        half_width - shift,
+        -top,
+        top,
+        near,
+        far,
+    )
+}
+
+/// Moves the viewer of `view` sideways to an eye `eye_offset` from the
+/// center of the head.
+pub fn stereo_view(view: &Mat4, eye_offset: f32) -> Mat4 {
+    translate(-eye_offset, 0.0, 0.0) * *view
+}
+
 #[allow(dead_code)]
 pub fn translate(x: f32, y: f32, z: f32) -> Mat4 {
     Mat4 {
@@ -332,6 +383,114 @@ impl Mat4 {
             col3: Vec4::zero(),
         }
     }
+
+    fn to_array(self) -> [f32; 16] {
+        let (a, b, c, d) = (self.col0, self.col1, self.col2, self.col3);
+        [
+            a.x, a.y, a.z, a.w, b.x, b.y, b.z, b.w, c.x, c.y, c.z, c.w, d.x, d.y, d.z, d.w,
+        ]
+    }
+
+    fn from_array(m: &[f32; 16]) -> Mat4 {
+        Mat4 {
+            col0: vec4(m[0], m[1], m[2], m[3]),
+            col1: vec4(m[4], m[5], m[6], m[7]),
+            col2: vec4(m[8], m[9], m[10], m[11]),
+            col3: vec4(m[12], m[13], m[14], m[15]),
+        }
+    }
+
+    /// Returns the inverse, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let m = self.to_array();
+        let mut inv = [0.0; 16];
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14]
+            + m[13] * m[6] * m[11]
+            - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14]
+            - m[12] * m[6] * m[11]
+            + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13]
+            + m[12] * m[5] * m[11]
+            - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13]
+            - m[12] * m[5] * m[10]
+            + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14]
+            - m[13] * m[2] * m[11]
+            + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14]
+            + m[12] * m[2] * m[11]
+            - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13]
+            - m[12] * m[1] * m[11]
+            + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13]
+            + m[12] * m[1] * m[10]
+            - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14]
+            + m[13] * m[2] * m[7]
+            - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14]
+            - m[12] * m[2] * m[7]
+            + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13]
+            + m[12] * m[1] * m[7]
+            - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13]
+            - m[12] * m[1] * m[6]
+            + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10]
+            - m[9] * m[2] * m[7]
+            + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10]
+            + m[8] * m[2] * m[7]
+            - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9]
+            - m[8] * m[1] * m[7]
+            + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9]
+            + m[8] * m[1] * m[6]
+            - m[8] * m[2] * m[5];
+
+        let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+        if det == 0.0 {
+            return None;
+        }
+        for x in inv.iter_mut() {
+            *x /= det;
+        }
+        Some(Mat4::from_array(&inv))
+    }
+
+    /// Transforms a point, dividing by the resulting w.
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let v = *self * vec4(p.x, p.y, p.z, 1.0);
+        vec3(v.x / v.w, v.y / v.w, v.z / v.w)
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+    fn mul(self, rhs: Vec4) -> Vec4 {
+        (self.col0 * rhs.x) + (self.col1 * rhs.y) + (self.col2 * rhs.z) + (self.col3 * rhs.w)
+    }
 }
 
 impl Mul for Mat4 {
@@ -365,3 +524,109 @@ impl fmt::Display for Mat4 {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Mat4, b: Mat4) {
+        for (x, y) in a.to_array().iter().zip(b.to_array().iter()) {
+            assert!((x - y).abs() < 1.0e-5, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn inverse_of_identity() {
+        assert_near(identity().inverse().unwrap(), identity());
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        let m = perspective(50.0, 1.5, 0.1, 100.0)
+            * translate(1.0, -2.0, -5.0)
+            * rotate(30.0, 0.0, 1.0, 0.0)
+            * scale(2.0, 0.5, 1.0);
+        let inv = m.inverse().unwrap();
+        assert_near(m * inv, identity());
+        assert_near(inv * m, identity());
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Mat4::zero().inverse().is_none());
+        assert!(scale(1.0, 0.0, 1.0).inverse().is_none());
+    }
+
+    #[test]
+    fn transform_point_translates() {
+        let p = translate(1.0, 2.0, 3.0).transform_point(vec3(1.0, 1.0, 1.0));
+        assert_eq!((p.x, p.y, p.z), (2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn transform_point_divides_by_w() {
+        let proj = perspective(90.0, 1.0, 1.0, 10.0);
+        // a point on the near plane maps to depth -1, the far plane to 1
+        let near = proj.transform_point(vec3(0.0, 0.0, -1.0));
+        let far = proj.transform_point(vec3(0.0, 0.0, -10.0));
+        assert!((near.z + 1.0).abs() < 1.0e-5);
+        assert!((far.z - 1.0).abs() < 1.0e-5);
+
+        // and the inverse maps back
+        let p = proj.inverse().unwrap().transform_point(far);
+        assert!((p.z + 10.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn stereo_frustum_without_offset_is_perspective() {
+        assert_near(
+            stereo_frustum(50.0, 1.5, 0.1, 100.0, 0.0, 10.0),
+            perspective(50.0, 1.5, 0.1, 100.0),
+        );
+    }
+
+    #[test]
+    fn stereo_frustum_is_asymmetric() {
+        // a 90 degree square frustum is 2 units wide at the near plane, and
+        // the left eye's is shifted 0.5 / 10 to the right
+        let left = stereo_frustum(90.0, 1.0, 1.0, 100.0, -0.5, 10.0);
+        assert!((left.transform_point(vec3(-0.95, 1.0, -1.0)).x + 1.0).abs() < 1.0e-5);
+        assert!((left.transform_point(vec3(1.05, -1.0, -1.0)).x - 1.0).abs() < 1.0e-5);
+
+        let right = stereo_frustum(90.0, 1.0, 1.0, 100.0, 0.5, 10.0);
+        assert!((right.transform_point(vec3(-1.05, 1.0, -1.0)).x + 1.0).abs() < 1.0e-5);
+        assert!((right.transform_point(vec3(0.95, -1.0, -1.0)).x - 1.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn stereo_view_moves_the_eye() {
+        // the left eye is at -x, so the center of the head appears to its
+        // right
+        let left = stereo_view(&identity(), -0.05).transform_point(vec3(0.0, 0.0, 0.0));
+        let right = stereo_view(&identity(), 0.05).transform_point(vec3(0.0, 0.0, 0.0));
+        assert!((left.x - 0.05).abs() < 1.0e-6);
+        assert!((right.x + 0.05).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn stereo_eyes_converge() {
+        let view = look_at(
+            vec3(1.0, 2.0, 3.0),
+            vec3(1.0, 2.0, -7.0),
+            vec3(0.0, 1.0, 0.0),
+        );
+        let eye_x = |offset: f32, z: f32| {
+            let proj = stereo_frustum(60.0, 1.5, 0.1, 100.0, offset, 10.0);
+            (proj * stereo_view(&view, offset))
+                .transform_point(vec3(1.0, 2.0, 3.0 - z))
+                .x
+        };
+        // both eyes see a point at the convergence distance in the same place
+        assert!(eye_x(-0.05, 10.0).abs() < 1.0e-5);
+        assert!(eye_x(0.05, 10.0).abs() < 1.0e-5);
+        // nearer points appear further right to the left eye, further ones
+        // further left
+        assert!(eye_x(-0.05, 5.0) > eye_x(0.05, 5.0));
+        assert!(eye_x(-0.05, 50.0) < eye_x(0.05, 50.0));
+    }
+}
@@ -0,0 +1,215 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gl;
+extern crate glfw;
+
+use app::{
+    apply_window_requests, cursor_mode, handle_window_event, reload_programs, App, AppInfo,
+    WindowedRect,
+};
+use context::Context;
+use error::Error;
+use gl_debug;
+use gl_info::GlInfo;
+use glfw::Context as GlfwContext;
+use std::sync::mpsc::Receiver;
+
+/// An `App` that only renders, for extra windows that don't need the other
+/// callbacks. See `Context::open_render_window`.
+pub struct RenderCallback<F> {
+    info: AppInfo,
+    render: F,
+}
+
+impl<F: FnMut(&mut Context, f64)> RenderCallback<F> {
+    pub fn new(info: AppInfo, render: F) -> RenderCallback<F> {
+        RenderCallback { info, render }
+    }
+}
+
+impl<F: FnMut(&mut Context, f64)> App for RenderCallback<F> {
+    fn get_app_info(&self) -> &AppInfo {
+        &self.info
+    }
+
+    fn render(&mut self, ctx: &mut Context, time: f64) {
+        (self.render)(ctx, time)
+    }
+}
+
+/// A window opened with `Context::open_window`. Its context shares objects
+/// with the main window's, but container objects such as vertex arrays and
+/// framebuffers are per context so each app creates its own.
+pub(crate) struct SharedWindow {
+    window: glfw::Window,
+    events: Receiver<(f64, glfw::WindowEvent)>,
+    ctx: Context,
+    app: Box<dyn App>,
+    windowed: WindowedRect,
+}
+
+impl SharedWindow {
+    /// Creates the window and calls the app's `startup` with its context
+    /// current. The caller makes the main window current again afterwards.
+    pub(crate) fn open(
+        glfw: &mut glfw::Glfw,
+        main: &glfw::Window,
+        gl_info: &GlInfo,
+        mut app: Box<dyn App>,
+    ) -> Result<SharedWindow, Error> {
+        let info = app.get_app_info().clone();
+        glfw.window_hint(glfw::WindowHint::Stereo(false));
+        glfw.window_hint(glfw::WindowHint::Samples(if info.samples > 0 {
+            Some(info.samples as u32)
+        } else {
+            None
+        }));
+        let version = (gl_info.major_version, gl_info.minor_version);
+        let (mut window, events) = try!(main
+            .create_shared(
+                info.window_width,
+                info.window_height,
+                info.title,
+                glfw::WindowMode::Windowed,
+            )
+            .ok_or(Error::ContextCreation(version, version)));
+
+        window.set_key_polling(true);
+        window.set_char_polling(true);
+        window.set_size_polling(true);
+        window.set_framebuffer_size_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_cursor_pos_polling(true);
+        window.set_scroll_polling(true);
+        window.set_focus_polling(true);
+        window.set_drag_and_drop_polling(true);
+        window.make_current();
+        // only the main window waits for vsync, so extra windows don't divide
+        // the frame rate
+        glfw.set_swap_interval(glfw::SwapInterval::None);
+        window.set_cursor_mode(cursor_mode(&info, false));
+
+        let (width, height) = window.get_size();
+        let (fb_width, fb_height) = window.get_framebuffer_size();
+        let (x, y) = window.get_pos();
+        let mut ctx_info = info.clone();
+        ctx_info.window_width = width as u32;
+        ctx_info.window_height = height as u32;
+        let mut ctx = Context::new(
+            ctx_info,
+            gl_info.clone(),
+            (fb_width as u32, fb_height as u32),
+        );
+
+        if let Err(e) = app.startup(&mut ctx) {
+            destroy(glfw, main, window);
+            return Err(e);
+        }
+
+        Ok(SharedWindow {
+            window,
+            events,
+            ctx,
+            app,
+            windowed: WindowedRect {
+                x,
+                y,
+                width: width as u32,
+                height: height as u32,
+            },
+        })
+    }
+
+    pub(crate) fn should_close(&self) -> bool {
+        self.window.should_close()
+    }
+
+    /// Updates, renders and presents a frame at `time`.
+    pub(crate) fn frame(&mut self, time: f64) {
+        self.window.make_current();
+        reload_programs(&mut *self.app, &mut self.ctx);
+        self.app.update(&mut self.ctx, time);
+        if self.ctx.auto_viewport() {
+            self.ctx.set_viewport();
+        }
+        self.app.render(&mut self.ctx, time);
+        gl_debug::check_errors("App::render");
+        self.window.swap_buffers();
+    }
+
+    /// Handles the events polled for this window, adding any windows its app
+    /// asked to open to `opened`.
+    pub(crate) fn handle_events(&mut self, glfw: &mut glfw::Glfw, opened: &mut Vec<Box<dyn App>>) {
+        self.window.make_current();
+        for (_, event) in glfw::flush_messages(&self.events) {
+            if handle_window_event(&mut *self.app, &mut self.ctx, &event) {
+                self.window.set_should_close(true);
+            }
+        }
+        apply_window_requests(
+            glfw,
+            &mut self.window,
+            &mut self.ctx,
+            &mut self.windowed,
+            opened,
+        );
+    }
+
+    /// Calls the app's `shutdown` with the window's context current, then
+    /// destroys the window and makes the main window current.
+    pub(crate) fn close(mut self, glfw: &mut glfw::Glfw, main: &glfw::Window) {
+        self.window.make_current();
+        self.app.shutdown(&mut self.ctx);
+        self.ctx.programs_mut().free();
+        self.ctx.stats_mut().free();
+        destroy(glfw, main, self.window);
+    }
+}
+
+/// Destroys a shared window, which glfw-rs only does on drop for windows it
+/// doesn't consider shared. The main window is made current first so the
+/// context being destroyed isn't current.
+fn destroy(glfw: &mut glfw::Glfw, main: &glfw::Window, mut window: glfw::Window) {
+    glfw.make_context_current(Some(main));
+    window.is_shared = false;
+    drop(window);
+}
+
+/// Opens the windows in `opened`, logging any that fail.
+pub(crate) fn open_windows(
+    glfw: &mut glfw::Glfw,
+    main: &glfw::Window,
+    gl_info: &GlInfo,
+    opened: &mut Vec<Box<dyn App>>,
+    windows: &mut Vec<SharedWindow>,
+) {
+    for app in opened.drain(..) {
+        let title = app.get_app_info().title;
+        match SharedWindow::open(glfw, main, gl_info, app) {
+            Ok(window) => windows.push(window),
+            Err(e) => error!("Failed to open window '{}': {}", title, e),
+        }
+    }
+}
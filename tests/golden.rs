@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) 2012-2013 Graham Sellers
+ * Copyright (c) 2014 Cameron Hart
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without limitation
+ * the rights to use, copy, modify, merge, publish, distribute, sublicense,
+ * and/or sell copies of the Software, and to permit persons to whom the
+ * Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice (including the next
+ * paragraph) shall be included in all copies or substantial portions of the
+ * Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.  IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+ * FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+ * DEALINGS IN THE SOFTWARE.
+ */
+
+//! These need a GL 4.x context and are ignored by default, run them with
+//! `cargo test --test golden -- --ignored`. Samples that load models or
+//! textures from the downloaded media pack are skipped when it isn't in
+//! `media`, or in `SB6_MEDIA_ROOT` if set.
+
+extern crate sb6;
+
+use sb6::golden::Golden;
+use std::env;
+use std::path::{Path, PathBuf};
+
+fn golden(example: &str) -> Golden {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let media_root = env::var_os("SB6_MEDIA_ROOT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root.join("media"));
+    Golden::new(example, root.join("tests/golden")).media_root(media_root)
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context"]
+fn singletri() {
+    golden("singletri").check();
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context"]
+fn movingtri() {
+    golden("movingtri").frame(45).check();
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context"]
+fn spinnycube() {
+    golden("spinnycube").frame(60).check();
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context"]
+fn fragcolorfrompos() {
+    golden("fragcolorfrompos").check();
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context"]
+fn tessellatedgtri() {
+    golden("tessellatedgtri").check();
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context and the media pack"]
+fn ssao() {
+    golden("ssao")
+        .requires_media("objects/dragon.sbm")
+        .requires_media("objects/cube.sbm")
+        .check();
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context and the media pack"]
+fn objectexploder() {
+    golden("objectexploder")
+        .requires_media("objects/bunny_1k.sbm")
+        .check();
+}
+
+#[test]
+#[ignore = "needs a GL 4.x context and the media pack"]
+fn starfield() {
+    golden("starfield")
+        .requires_media("textures/star.ktx")
+        .check();
+}